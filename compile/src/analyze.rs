@@ -1,49 +1,45 @@
+use std::collections::HashMap;
+
+use compact_str::CompactString;
 use itertools::Itertools;
 use log::*;
+use rustc_hir::def::{DefKind, Res};
 use rustc_hir::definitions::DefPath;
+use rustc_hir::intravisit;
 use rustc_hir::ItemId;
 use rustc_middle::hir::map::Map;
+use rustc_middle::hir::nested_filter;
 use rustc_middle::ty;
 use rustc_middle::ty::TyCtxt;
 use rustc_span::def_id::{DefId, LocalDefId};
+use rustc_span::Span;
 use smallvec::SmallVec;
 
 /// The identifier of the "std" namespace equivalent for the project.
 pub const STD_NAME: &str = "runtime";
 
+/// Loop in a function that is not allowed to have loops.
+#[derive(Debug, Clone, Copy)]
+pub struct ForbiddenLoop {
+    /// The function with the loop.
+    pub item: LocalDefId,
+
+    /// The loop expression, or the first one if there are several.
+    pub span: Span,
+}
+
 /// Ensure that there are no loops in the code.
 /// This, however, does not check for recursion.
+/// Functions with paths in `allow` (see [fn_path]) are skipped.
 // We may later permit loops that are evaluatable in constant contexts.
 // E.g. what Rust permits in `const fn`.
-pub fn no_forbidden_loops(hir: Map) -> bool {
-    fn check_item(hir: Map, item: &rustc_hir::Item) -> bool {
-        let name = item.ident.name.as_str();
-        trace!("Checking item: `{}`", name);
-        let result = match item.kind {
-            rustc_hir::ItemKind::Fn(sig, _, body_id) => {
-                if sig.header.constness == rustc_hir::Constness::Const {
-                    // Const functions are allowed to have loops,
-                    // since we know that they will terminate.
-                    return true;
-                }
-                check_body(hir, body_id)
-            }
-            _ => true,
-        };
-        if result {
-            trace!("Item `{name}` is loop-free.");
-        } else {
-            trace!("Item `{name}` contains a loop.");
-        }
-        result
-    }
-
-    fn check_body(hir: Map, body_id: rustc_hir::BodyId) -> bool {
+pub fn no_forbidden_loops(tcx: TyCtxt, allow: &[CompactString]) -> Result<(), Vec<ForbiddenLoop>> {
+    fn check_body(hir: Map, body_id: rustc_hir::BodyId) -> Result<(), Span> {
         let value = hir.body(body_id).value;
         let span_data = value.span.data();
         trace!("Checking body at: {span_data:?}");
         let result = check_expr(hir, value);
-        if result {
+        if result.is_ok() {
             trace!("Body is loop-free. {span_data:?}");
         } else {
             trace!("Body contains a loop. {span_data:?}");
@@ -51,121 +47,125 @@ pub fn no_forbidden_loops(hir: Map) -> bool {
         result
     }
 
-    fn check_expr(hir: Map, expr: &rustc_hir::Expr) -> bool {
+    fn check_exprs(hir: Map, exprs: &[rustc_hir::Expr]) -> Result<(), Span> {
+        exprs.iter().try_for_each(|expr| check_expr(hir, expr))
+    }
+
+    fn check_opt_expr(hir: Map, expr: Option<&rustc_hir::Expr>) -> Result<(), Span> {
+        expr.map_or(Ok(()), |expr| check_expr(hir, expr))
+    }
+
+    fn check_expr(hir: Map, expr: &rustc_hir::Expr) -> Result<(), Span> {
         use rustc_hir::ExprKind as K;
         if !matches!(expr.kind, K::DropTemps(..)) {
             trace!("Checking expr: {:?}", expr.precedence());
         } // else don't print, as they would just make desugared part appear twice.
 
         match expr.kind {
-            K::ConstBlock(_) => true, // Const blocks are allowed to have loops.
-            K::Array(exprs) => exprs.iter().all(|expr| check_expr(hir, expr)),
+            K::ConstBlock(_) => Ok(()), // Const blocks are allowed to have loops.
+            K::Array(exprs) => check_exprs(hir, exprs),
             K::Call(f, args) => {
                 if let K::Path(qpath) = &f.kind {
                     log_qpath(qpath);
                 }
-                args.iter().all(|arg| check_expr(hir, arg))
+                check_exprs(hir, args)
             }
             K::MethodCall(seg, recv, args, _) => {
                 trace!("Method call: {}", seg.ident.as_str());
-                check_expr(hir, recv) && args.iter().all(|arg| check_expr(hir, arg))
+                check_expr(hir, recv)?;
+                check_exprs(hir, args)
+            }
+            K::Tup(exprs) => check_exprs(hir, exprs),
+            K::Binary(_, lhs, rhs) => {
+                check_expr(hir, lhs)?;
+                check_expr(hir, rhs)
             }
-            K::Tup(exprs) => exprs.iter().all(|expr| check_expr(hir, expr)),
-            K::Binary(_, lhs, rhs) => check_expr(hir, lhs) && check_expr(hir, rhs),
             K::Unary(_, expr) => check_expr(hir, expr),
             K::Lit(lit) => {
                 trace!("Literal: {lit:?}");
-                true
+                Ok(())
             }
-            K::Cast(..) => true,
-            K::Type(..) => true,
+            K::Cast(..) => Ok(()),
+            K::Type(..) => Ok(()),
             K::DropTemps(expr) => check_expr(hir, expr),
             K::Let(let_expr) => check_expr(hir, &let_expr.init),
             K::If(cond, then, other) => {
-                check_expr(hir, cond)
-                    && check_expr(hir, then)
-                    && other.map_or(true, |e| check_expr(hir, e))
+                check_expr(hir, cond)?;
+                check_expr(hir, then)?;
+                check_opt_expr(hir, other)
             }
-            K::Loop(..) => false,
-            K::Match(expr, arms, src) => {
+            K::Loop(..) => Err(expr.span),
+            K::Match(scrutinee, arms, src) => {
                 trace!("Match source: {src:?}");
 
                 use rustc_hir::MatchSource as S;
                 if let S::ForLoopDesugar = src {
-                    return false;
-                }
-                if !check_expr(hir, expr) {
-                    return false;
+                    return Err(expr.span);
                 }
-                for arm in arms {
-                    if !check_expr(hir, &arm.body) {
-                        return false;
-                    }
-                }
-                true
+                check_expr(hir, scrutinee)?;
+                arms.iter().try_for_each(|arm| check_expr(hir, &arm.body))
             }
             K::Closure(closure) => {
                 if closure.constness == rustc_hir::Constness::Const {
                     // Const closures are allowed to have loops,
                     // since we know that they will terminate.
-                    return true;
+                    return Ok(());
                 }
                 check_body(hir, closure.body)
             }
             K::Block(block, _) => check_block(hir, block),
-            K::Assign(lhs, rhs, _) => check_expr(hir, lhs) && check_expr(hir, rhs),
-            K::AssignOp(_, lhs, rhs) => check_expr(hir, lhs) && check_expr(hir, rhs),
+            K::Assign(lhs, rhs, _) | K::AssignOp(_, lhs, rhs) | K::Index(lhs, rhs, _) => {
+                check_expr(hir, lhs)?;
+                check_expr(hir, rhs)
+            }
             K::Field(expr, _) => check_expr(hir, expr),
-            K::Index(lhs, rhs, _) => check_expr(hir, lhs) && check_expr(hir, rhs),
             K::Path(p) => {
                 log_qpath(&p);
-                true
+                Ok(())
             }
             K::AddrOf(_, _, expr) => check_expr(hir, expr),
             // We check for actual loops elsewhere, ignore control flow operators.
-            K::Break(_, expr) => expr.map_or(true, |expr| check_expr(hir, expr)),
-            K::Continue(_) => true,
-            K::Ret(expr) => expr.map_or(true, |expr| check_expr(hir, expr)),
+            K::Break(_, expr) => check_opt_expr(hir, expr),
+            K::Continue(_) => Ok(()),
+            K::Ret(expr) => check_opt_expr(hir, expr),
             K::Become(expr) => check_expr(hir, expr),
-            K::InlineAsm(_) => true,
-            K::OffsetOf(..) => true,
+            K::InlineAsm(_) => Ok(()),
+            K::OffsetOf(..) => Ok(()),
             K::Struct(_, expr_field, base) => {
-                for expr_field in expr_field {
-                    if !check_expr(hir, &expr_field.expr) {
-                        return false;
-                    }
-                }
-                base.map_or(true, |base| check_expr(hir, base))
+                expr_field
+                    .iter()
+                    .try_for_each(|expr_field| check_expr(hir, &expr_field.expr))?;
+                check_opt_expr(hir, base)
             }
             K::Repeat(expr, _) => check_expr(hir, expr),
             K::Yield(expr, _) => check_expr(hir, expr),
-            K::Err(_) => true,
+            K::Err(_) => Ok(()),
         }
     }
 
-    fn check_stmt(hir: Map, stmt: &rustc_hir::Stmt) -> bool {
+    fn check_stmt(hir: Map, stmt: &rustc_hir::Stmt) -> Result<(), Span> {
         trace!("Checking stmt: {:?}", stmt.span.data());
         use rustc_hir::StmtKind as K;
         match stmt.kind {
-            K::Item(_) => true,
+            K::Item(_) => Ok(()),
             K::Expr(expr) => check_expr(hir, expr),
             K::Semi(expr) => check_expr(hir, expr),
             K::Let(let_stmt) => {
-                let_stmt.init.map_or(true, |expr| check_expr(hir, expr))
-                    && let_stmt.els.map_or(true, |block| check_block(hir, block))
+                check_opt_expr(hir, let_stmt.init)?;
+                let_stmt.els.map_or(Ok(()), |block| check_block(hir, block))
             }
         }
     }
 
-    fn check_block(hir: Map, block: &rustc_hir::Block) -> bool {
+    fn check_block(hir: Map, block: &rustc_hir::Block) -> Result<(), Span> {
         trace!("Checking block: {:?}", block.span.data());
         for stmt in block.stmts {
-            if !check_stmt(hir, stmt) {
+            if let Err(span) = check_stmt(hir, stmt) {
                 trace!("Failed at stmt: {:?}", stmt.span.data());
-                return false;
+                return Err(span);
             }
         }
-        block.expr.map_or(true, |expr| check_expr(hir, expr))
+        check_opt_expr(hir, block.expr)
     }
 
     fn log_qpath(qpath: &rustc_hir::QPath) {
@@ -190,76 +190,498 @@ pub fn no_forbidden_loops(hir: Map) -> bool {
         }
     }
 
-    let items = hir.items();
-    for item_id in items {
-        if !check_item(hir, hir.item(item_id)) {
-            return false;
+    info!("Checking for forbidden loops.");
+    let hir = tcx.hir();
+    let mut errors = Vec::new();
+    for def in project_fns(tcx) {
+        let path = fn_path(tcx, def.to_def_id());
+        if allow.iter().any(|v| *v == path) {
+            trace!("Loops are allowed in `{path}`");
+            continue;
+        }
+
+        trace!("Checking Fn item: {path}");
+        if let Err(span) = check_body(hir, hir.body_owned_by(def).id()) {
+            error!("Loop found in `{path}`.");
+            errors.push(ForbiddenLoop { item: def, span });
+        } else {
+            trace!("Item `{path}` is loop-free.");
         }
     }
-    true
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
 }
 
-pub struct Recursion {
-    pub callee: LocalDefId,
+/// Call of one project function from another.
+#[derive(Debug, Clone, Copy)]
+pub struct CallEdge {
     pub caller: LocalDefId,
+    pub callee: LocalDefId,
+
+    /// The call expression in the caller.
+    pub span: Span,
+}
+
+/// Calls that form a cycle: each callee is the caller of the next edge,
+/// and the callee of the last edge is the caller of the first one.
+#[derive(Debug)]
+pub struct Recursion {
+    pub cycle: Vec<CallEdge>,
 }
 
 /// Ensure that there is no recursion in the user's code.
-pub fn no_recursion(tcx: TyCtxt) -> Result<(), Vec<Recursion>> {
-    let mut errors = Vec::new();
+/// Cycles that go through a function with a path in `allow` (see [fn_path]) are permitted.
+pub fn no_recursion(tcx: TyCtxt, allow: &[CompactString]) -> Result<(), Vec<Recursion>> {
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    enum Mark {
+        OnStack,
+        Done,
+    }
+
+    struct Search<'a> {
+        calls: &'a HashMap<LocalDefId, Vec<CallEdge>>,
+        marks: HashMap<LocalDefId, Mark>,
+        stack: Vec<CallEdge>,
+        found: Vec<Recursion>,
+    }
+
+    impl Search<'_> {
+        fn visit(&mut self, def: LocalDefId) {
+            self.marks.insert(def, Mark::OnStack);
+            let calls = self.calls;
+            for edge in calls.get(&def).into_iter().flatten() {
+                match self.marks.get(&edge.callee) {
+                    None => {
+                        self.stack.push(*edge);
+                        self.visit(edge.callee);
+                        self.stack.pop();
+                    }
+                    Some(Mark::OnStack) => {
+                        // The callee is on the current path, which closes the cycle there.
+                        // If no edge on the stack starts from it, it is the current function.
+                        let start = self
+                            .stack
+                            .iter()
+                            .position(|v| v.caller == edge.callee)
+                            .unwrap_or(self.stack.len());
+                        let mut cycle = self.stack[start..].to_vec();
+                        cycle.push(*edge);
+                        self.found.push(Recursion { cycle });
+                    }
+                    Some(Mark::Done) => {}
+                }
+            }
+            self.marks.insert(def, Mark::Done);
+        }
+    }
 
     info!("Checking for recursion.");
+    let fns = project_fns(tcx);
+    let calls: HashMap<_, _> = fns
+        .iter()
+        .map(|&def| (def, calls_of(tcx, def, &fns)))
+        .collect();
+
+    let mut search = Search {
+        calls: &calls,
+        marks: HashMap::with_capacity(fns.len()),
+        stack: Vec::new(),
+        found: Vec::new(),
+    };
+    for &def in &fns {
+        if !search.marks.contains_key(&def) {
+            search.visit(def);
+        }
+    }
+
+    let is_allowed = |def: LocalDefId| {
+        let path = fn_path(tcx, def.to_def_id());
+        allow.iter().any(|v| *v == path)
+    };
+    let mut errors = search.found;
+    errors.retain(|r| {
+        if r.cycle.iter().any(|edge| is_allowed(edge.caller)) {
+            trace!(
+                "Allowed recursion through `{}`",
+                tcx.def_path_str(r.cycle[0].caller)
+            );
+            false
+        } else {
+            error!(
+                "Recursion detected in `{}`.",
+                tcx.def_path_str(r.cycle[0].caller)
+            );
+            true
+        }
+    });
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Non-const functions and methods with bodies, defined in the project.
+/// Const functions are allowed to have loops and to recurse, as the compiler
+/// ensures that they terminate.
+fn project_fns(tcx: TyCtxt) -> Vec<LocalDefId> {
     let hir = tcx.hir();
-    let defs = tcx.hir_crate_items(()).definitions();
-    for def in defs {
-        let path = hir.def_path(def).to_string_no_crate_verbose();
-        let item = hir.expect_item(def);
-        if let rustc_hir::ItemKind::Fn(sig, ..) = &item.kind {
-            trace!("Checking Fn item: {path}");
-
-            if sig.header.constness == rustc_hir::Constness::Const {
-                // Const functions are allowed to be recursive.
-                trace!("Const function `{path}` is allowed to be recursive.");
-                continue;
+    tcx.hir_crate_items(())
+        .definitions()
+        .filter(|&def| matches!(tcx.def_kind(def), DefKind::Fn | DefKind::AssocFn))
+        .filter(|&def| hir.maybe_body_owned_by(def).is_some())
+        .filter(|&def| {
+            let is_const = tcx.constness(def) == rustc_hir::Constness::Const;
+            if is_const {
+                trace!("Const function `{}` is skipped.", tcx.def_path_str(def));
             }
+            !is_const
+        })
+        .collect()
+}
+
+/// Calls from the function to the other functions of `fns`, in the order they appear
+/// in the body. Trait method calls are resolved to the implementation, when known.
+fn calls_of(tcx: TyCtxt, def: LocalDefId, fns: &[LocalDefId]) -> Vec<CallEdge> {
+    struct Collector<'a, 'tcx> {
+        tcx: TyCtxt<'tcx>,
+        results: &'tcx ty::TypeckResults<'tcx>,
+        param_env: ty::ParamEnv<'tcx>,
+        caller: LocalDefId,
+        fns: &'a [LocalDefId],
+        calls: Vec<CallEdge>,
+    }
 
-            let instance_kind = ty::InstanceKind::Item(def.into());
-            let mir_callees = tcx.mir_inliner_callees(instance_kind);
-            trace!("MIR callees count: {}", mir_callees.len());
-
-            for (callee, gen_args) in mir_callees {
-                let callee_path = tcx.def_path_str(callee);
-
-                let instance = ty::Instance {
-                    def: instance_kind,
-                    args: gen_args,
-                };
-                // Analyze only local functions. We trust in the external ones, as
-                // we were the ones who added them as dependencies.
-                if let Some(local) = callee.as_local() {
-                    trace!("Inspecting callee: {callee_path}");
-
-                    let reachable = tcx.mir_callgraph_reachable((instance, local));
-                    if reachable {
-                        error!("Recursion detected in `{path}` calling `{callee_path}`.");
-                        errors.push(Recursion {
-                            callee: local,
-                            caller: def,
-                        });
+    impl<'tcx> Collector<'_, 'tcx> {
+        fn callee(&self, expr: &rustc_hir::Expr) -> Option<LocalDefId> {
+            use rustc_hir::ExprKind as K;
+            let (id, hir_id) = match &expr.kind {
+                K::Call(f, _) => {
+                    let K::Path(qpath) = &f.kind else {
+                        return None;
+                    };
+                    match self.results.qpath_res(qpath, f.hir_id) {
+                        Res::Def(DefKind::Fn | DefKind::AssocFn, id) => (id, f.hir_id),
+                        _ => return None,
                     }
-                } else {
-                    trace!("Skipping external callee: {callee_path}");
                 }
+                K::MethodCall(..) => (
+                    self.results.type_dependent_def_id(expr.hir_id)?,
+                    expr.hir_id,
+                ),
+                _ => return None,
+            };
+
+            let args = self.results.node_args(hir_id);
+            let id = match ty::Instance::try_resolve(self.tcx, self.param_env, id, args) {
+                Ok(Some(instance)) => instance.def_id(),
+                _ => id,
+            };
+            id.as_local().filter(|id| self.fns.contains(id))
+        }
+    }
+
+    impl<'tcx> intravisit::Visitor<'tcx> for Collector<'_, 'tcx> {
+        // Closures are visited as part of the function, nested items are separate functions.
+        type NestedFilter = nested_filter::OnlyBodies;
+
+        fn nested_visit_map(&mut self) -> Self::Map {
+            self.tcx.hir()
+        }
+
+        fn visit_expr(&mut self, expr: &'tcx rustc_hir::Expr<'tcx>) {
+            if let Some(callee) = self.callee(expr) {
+                trace!("Call of `{}`", self.tcx.def_path_str(callee));
+                self.calls.push(CallEdge {
+                    caller: self.caller,
+                    callee,
+                    span: expr.span,
+                });
             }
+            intravisit::walk_expr(self, expr);
         }
     }
-    if errors.is_empty() {
+
+    trace!("Collecting calls of `{}`", tcx.def_path_str(def));
+    let mut collector = Collector {
+        tcx,
+        results: tcx.typeck(def),
+        param_env: tcx.param_env(def),
+        caller: def,
+        fns,
+        calls: Vec::new(),
+    };
+    intravisit::Visitor::visit_body(&mut collector, tcx.hir().body_owned_by(def));
+    collector.calls
+}
+
+/// Path of the function, as used in the allow lists and in the errors.
+/// Methods are named after the type they are implemented for, also in trait impls,
+/// e.g. `ee_to_csv::Ee2Csv::put`.
+pub fn fn_path(tcx: TyCtxt, def: DefId) -> String {
+    let adt = tcx
+        .impl_of_method(def)
+        .and_then(|imp| impl_self_adt(tcx, imp));
+    ty::print::with_no_trimmed_paths!(match adt {
+        Some(adt) => format!("{}::{}", tcx.def_path_str(adt), tcx.item_name(def)),
+        None => tcx.def_path_str(def),
+    })
+}
+
+/// Use of an API that the project code must not use.
+#[derive(Debug, Clone)]
+pub struct ForbiddenApi {
+    /// The item with the use, e.g. the function.
+    pub item: LocalDefId,
+
+    pub span: Span,
+
+    pub api: Api,
+}
+
+#[derive(Debug, Clone)]
+pub enum Api {
+    /// Item with a path from the deny-list, e.g. `std::fs::read`.
+    Path(DefId),
+
+    UnsafeBlock,
+
+    StaticMut,
+}
+
+/// Ensure that the project code does not name the items under `deny_paths`, e.g. `std::fs`
+/// for the whole module or `std::env::set_var` for one function, and, if enabled, does not
+/// have unsafe blocks and mutable statics.
+/// Items and modules with paths in `allow` (see [fn_path]) are skipped, along with
+/// everything inside them.
+pub fn no_forbidden_apis(
+    tcx: TyCtxt,
+    deny_paths: &[CompactString],
+    deny_unsafe: bool,
+    deny_static_mut: bool,
+    allow: &[CompactString],
+) -> Result<(), Vec<ForbiddenApi>> {
+    struct Finder<'a, 'tcx> {
+        tcx: TyCtxt<'tcx>,
+        results: Option<&'tcx ty::TypeckResults<'tcx>>,
+        deny_paths: &'a [CompactString],
+        deny_unsafe: bool,
+        deny_static_mut: bool,
+        found: Vec<ForbiddenApi>,
+    }
+
+    impl Finder<'_, '_> {
+        fn check_def(&mut self, def: DefId, hir_id: rustc_hir::HirId, span: Span) {
+            let path = ty::print::with_no_trimmed_paths!(self.tcx.def_path_str(def));
+            if self.deny_paths.iter().any(|v| is_path_prefix(v, &path)) {
+                trace!("Use of forbidden `{path}`");
+                self.push(hir_id, span, Api::Path(def));
+            }
+        }
+
+        fn push(&mut self, hir_id: rustc_hir::HirId, span: Span, api: Api) {
+            // Uses inside the macros of other crates are not the project's code.
+            if rustc_middle::lint::in_external_macro(self.tcx.sess, span) {
+                return;
+            }
+            self.found.push(ForbiddenApi {
+                item: self.tcx.hir().get_parent_item(hir_id).def_id,
+                span,
+                api,
+            });
+        }
+    }
+
+    impl<'tcx> intravisit::Visitor<'tcx> for Finder<'_, 'tcx> {
+        type NestedFilter = nested_filter::All;
+
+        fn nested_visit_map(&mut self) -> Self::Map {
+            self.tcx.hir()
+        }
+
+        fn visit_nested_body(&mut self, id: rustc_hir::BodyId) {
+            let outer = self.results.replace(self.tcx.typeck_body(id));
+            let body = self.tcx.hir().body(id);
+            self.visit_body(body);
+            self.results = outer;
+        }
+
+        fn visit_item(&mut self, item: &'tcx rustc_hir::Item<'tcx>) {
+            use rustc_hir::{ItemKind, Mutability};
+            if let ItemKind::Static(_, Mutability::Mut, _) = item.kind {
+                if self.deny_static_mut {
+                    trace!("Mutable static `{}`", item.ident);
+                    self.found.push(ForbiddenApi {
+                        item: item.owner_id.def_id,
+                        span: item.span,
+                        api: Api::StaticMut,
+                    });
+                }
+            }
+            intravisit::walk_item(self, item);
+        }
+
+        fn visit_path(&mut self, path: &rustc_hir::Path<'tcx>, id: rustc_hir::HirId) {
+            if let Some(def) = path.res.opt_def_id() {
+                self.check_def(def, id, path.span);
+            }
+            intravisit::walk_path(self, path);
+        }
+
+        fn visit_expr(&mut self, expr: &'tcx rustc_hir::Expr<'tcx>) {
+            use rustc_hir::{BlockCheckMode, ExprKind, UnsafeSource};
+            match expr.kind {
+                ExprKind::MethodCall(..) => {
+                    let def = self
+                        .results
+                        .and_then(|v| v.type_dependent_def_id(expr.hir_id));
+                    if let Some(def) = def {
+                        self.check_def(def, expr.hir_id, expr.span);
+                    }
+                }
+                ExprKind::Block(block, _)
+                    if self.deny_unsafe
+                        && block.rules
+                            == BlockCheckMode::UnsafeBlock(UnsafeSource::UserProvided) =>
+                {
+                    trace!("Unsafe block");
+                    self.push(expr.hir_id, expr.span, Api::UnsafeBlock);
+                }
+                _ => {}
+            }
+            intravisit::walk_expr(self, expr);
+        }
+    }
+
+    info!("Check for forbidden APIs");
+    let mut finder = Finder {
+        tcx,
+        results: None,
+        deny_paths,
+        deny_unsafe,
+        deny_static_mut,
+        found: Vec::new(),
+    };
+    tcx.hir().walk_toplevel_module(&mut finder);
+
+    let mut found = finder.found;
+    found.retain(|v| {
+        let path = fn_path(tcx, v.item.to_def_id());
+        let is_allowed = allow.iter().any(|allowed| is_path_prefix(allowed, &path));
+        if is_allowed {
+            trace!("Forbidden APIs are allowed in `{path}`");
+        }
+        !is_allowed
+    });
+    if found.is_empty() {
         Ok(())
     } else {
-        Err(errors)
+        Err(found)
+    }
+}
+
+/// Use of an external crate that is not allowed for the project code.
+#[derive(Debug, Clone)]
+pub struct UnlistedCrate {
+    /// The item with the use.
+    pub item: LocalDefId,
+
+    pub span: Span,
+
+    /// Name of the crate.
+    pub krate: rustc_span::Symbol,
+}
+
+/// Crates that the project code can always use.
+pub const DEFAULT_CRATES: [&str; 4] = [STD_NAME, "std", "core", "alloc"];
+
+/// Ensure that the project code names no external crates besides the ones in `allow`
+/// and [DEFAULT_CRATES]. Items of other crates that are reached through an allowed
+/// crate, e.g. re-exported by the runtime, are uses of the allowed crate.
+pub fn no_unlisted_crates(tcx: TyCtxt, allow: &[CompactString]) -> Result<(), Vec<UnlistedCrate>> {
+    struct Finder<'a, 'tcx> {
+        tcx: TyCtxt<'tcx>,
+        allow: &'a [CompactString],
+        found: Vec<UnlistedCrate>,
+    }
+
+    impl Finder<'_, '_> {
+        fn check(&mut self, krate: rustc_span::Symbol, hir_id: rustc_hir::HirId, span: Span) {
+            let name = krate.as_str();
+            if DEFAULT_CRATES.contains(&name) || self.allow.iter().any(|v| v == name) {
+                return;
+            }
+            // Uses inside the macros of other crates are not the project's code.
+            if rustc_middle::lint::in_external_macro(self.tcx.sess, span) {
+                return;
+            }
+            trace!("Use of unlisted crate `{name}`");
+            self.found.push(UnlistedCrate {
+                item: self.tcx.hir().get_parent_item(hir_id).def_id,
+                span,
+                krate,
+            });
+        }
+    }
+
+    impl<'tcx> intravisit::Visitor<'tcx> for Finder<'_, 'tcx> {
+        type NestedFilter = nested_filter::All;
+
+        fn nested_visit_map(&mut self) -> Self::Map {
+            self.tcx.hir()
+        }
+
+        fn visit_item(&mut self, item: &'tcx rustc_hir::Item<'tcx>) {
+            if let rustc_hir::ItemKind::ExternCrate(orig_name) = item.kind {
+                self.check(
+                    orig_name.unwrap_or(item.ident.name),
+                    item.hir_id(),
+                    item.span,
+                );
+            }
+            intravisit::walk_item(self, item);
+        }
+
+        fn visit_path(&mut self, path: &rustc_hir::Path<'tcx>, id: rustc_hir::HirId) {
+            // The crate is named by the first segment, after `::` if the path has it.
+            let first = path
+                .segments
+                .iter()
+                .find(|v| v.ident.name != rustc_span::symbol::kw::PathRoot);
+            if let Some(Res::Def(DefKind::Mod, def)) = first.map(|v| v.res) {
+                if !def.is_local() && def.is_crate_root() {
+                    self.check(self.tcx.crate_name(def.krate), id, path.span);
+                }
+            }
+            intravisit::walk_path(self, path);
+        }
+    }
+
+    info!("Check for unlisted crates");
+    let mut finder = Finder {
+        tcx,
+        allow,
+        found: Vec::new(),
+    };
+    tcx.hir().walk_toplevel_module(&mut finder);
+    if finder.found.is_empty() {
+        Ok(())
+    } else {
+        Err(finder.found)
     }
 }
 
+/// Whether the `path` is the `prefix` or an item inside of it.
+fn is_path_prefix(prefix: &str, path: &str) -> bool {
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
+
 /// Find all public types in the HIR. These can be
 /// later used to be registered into the frontend context.
 pub fn type_ids(tcx: TyCtxt) -> Vec<DefId> {
@@ -276,7 +698,7 @@ pub fn type_ids(tcx: TyCtxt) -> Vec<DefId> {
     for item in adt_items {
         let id = item.hir_id().as_owner().unwrap().def_id;
         if visibilities.is_directly_public(id) {
-            println!("{:?}", item.ident);
+            trace!("Public type: {:?}", item.ident);
             vec.push(id.into());
         }
     }
@@ -315,9 +737,34 @@ pub fn impls(tcx: TyCtxt) -> Vec<ItemId> {
     vec.into_vec()
 }
 
+/// Implementation of the `Sink` or `Source` trait.
+#[derive(Debug)]
+pub struct TraitImpl {
+    /// The "impl" item.
+    pub impl_id: DefId,
+
+    /// The ADT that the trait is implemented for. None if the self type is
+    /// not an ADT, e.g. a reference or a trait object.
+    pub self_id: Option<DefId>,
+
+    /// For sinks this is `T` of `Sink<T>`, for sources this is `Source::Item`.
+    /// None if the type cannot be determined.
+    pub item_ty: Option<ImplTy>,
+}
+
+/// Type in a trait impl, printed with full paths.
+#[derive(Debug)]
+pub struct ImplTy {
+    pub ty: String,
+
+    /// Type parameters of the impl that the type names, e.g. `T` of
+    /// `impl<T> Sink<Vec<T>> for ...`. They stand for any type.
+    pub params: Vec<CompactString>,
+}
+
 pub struct SinksAndSources {
-    pub sinks: Vec<DefId>,
-    pub sources: Vec<DefId>,
+    pub sinks: Vec<TraitImpl>,
+    pub sources: Vec<TraitImpl>,
 }
 
 impl SinksAndSources {
@@ -330,28 +777,173 @@ impl SinksAndSources {
             .hir()
             .trait_impls(sink_def_id)
             .iter()
-            .map(|v| v.to_def_id())
+            .map(|v| {
+                let impl_id = v.to_def_id();
+                TraitImpl {
+                    impl_id,
+                    self_id: impl_self_adt(tcx, impl_id),
+                    item_ty: sink_item_ty(tcx, impl_id),
+                }
+            })
             .collect();
         let sources = tcx
             .hir()
             .trait_impls(source_def_id)
             .iter()
-            .map(|v| v.to_def_id())
+            .map(|v| {
+                let impl_id = v.to_def_id();
+                TraitImpl {
+                    impl_id,
+                    self_id: impl_self_adt(tcx, impl_id),
+                    item_ty: source_item_ty(tcx, impl_id),
+                }
+            })
             .collect();
 
         SinksAndSources { sinks, sources }
     }
 
-    /// Filter out all items that are not in the given slice.
+    /// Filter out all items whose implementing ADT is not in the given slice.
     /// Can be used to filter out items that are not in the project or
     /// should be ignored for some other reason. E.g. this can be used to
     /// only retain public items.
     pub fn filter_not_in(&mut self, slice: &[DefId]) {
-        self.sinks.retain(|sink| !slice.contains(sink));
-        self.sources.retain(|source| !slice.contains(source));
+        let is_in = |imp: &TraitImpl| imp.self_id.is_some_and(|id| slice.contains(&id));
+        self.sinks.retain(is_in);
+        self.sources.retain(is_in);
     }
 }
 
+/// The ADT of the `impl ... for Type` item, if the self type is an ADT.
+fn impl_self_adt(tcx: TyCtxt, impl_id: DefId) -> Option<DefId> {
+    let self_ty = tcx.type_of(impl_id).instantiate_identity();
+    let adt = self_ty.ty_adt_def().map(|adt| adt.did());
+    if adt.is_none() {
+        trace!(
+            "Impl `{}` is not for an ADT, but for `{self_ty}`",
+            tcx.def_path_str(impl_id)
+        );
+    }
+    adt
+}
+
+/// Public associated function that returns `Self`, found in an inherent impl.
+#[derive(Debug)]
+pub struct Constructor {
+    /// The ADT that the function returns.
+    pub self_id: DefId,
+
+    pub name: String,
+
+    /// Names and types of the parameters, in order.
+    pub params: Vec<(String, String)>,
+}
+
+/// Collect constructors of the given ADTs: public associated functions without `self`
+/// in their inherent impls that return `Self`.
+pub fn constructors(tcx: TyCtxt, adts: &[DefId]) -> Vec<Constructor> {
+    use rustc_hir::{AssocItemKind, ItemKind};
+
+    let mut vec = Vec::new();
+    for id in tcx.hir().items() {
+        let ItemKind::Impl(imp) = tcx.hir().item(id).kind else {
+            continue;
+        };
+        if imp.of_trait.is_some() {
+            continue;
+        }
+        let impl_id = id.owner_id.to_def_id();
+        let Some(self_id) = impl_self_adt(tcx, impl_id).filter(|v| adts.contains(v)) else {
+            continue;
+        };
+
+        for item in imp.items {
+            let def_id = item.id.owner_id.to_def_id();
+            if !matches!(item.kind, AssocItemKind::Fn { has_self: false })
+                || !tcx.visibility(def_id).is_public()
+            {
+                continue;
+            }
+            let sig = tcx.fn_sig(def_id).instantiate_identity().skip_binder();
+            if sig.output().ty_adt_def().map(|adt| adt.did()) != Some(self_id) {
+                trace!(
+                    "Skip `{}`, it does not return Self",
+                    tcx.def_path_str(def_id)
+                );
+                continue;
+            }
+
+            let names = tcx.fn_arg_names(def_id);
+            let params = names
+                .iter()
+                .zip(sig.inputs())
+                .map(|(name, ty)| (name.to_string(), ty_to_string(*ty)))
+                .collect();
+            trace!("Found constructor `{}`", tcx.def_path_str(def_id));
+            vec.push(Constructor {
+                self_id,
+                name: item.ident.to_string(),
+                params,
+            });
+        }
+    }
+    vec
+}
+
+/// Type `T` of the `impl Sink<T> for ...` item.
+fn sink_item_ty(tcx: TyCtxt, impl_id: DefId) -> Option<ImplTy> {
+    let trait_ref = tcx.impl_trait_ref(impl_id)?.instantiate_identity();
+
+    // Generic arguments of the trait are `[Self, T]`.
+    let ty = trait_ref.args.types().nth(1);
+    if ty.is_none() {
+        warn!(
+            "Sink impl `{}` has no type argument",
+            tcx.def_path_str(impl_id)
+        );
+    }
+    ty.map(impl_ty)
+}
+
+/// Associated type `Item` of the `impl Source for ...` item.
+fn source_item_ty(tcx: TyCtxt, impl_id: DefId) -> Option<ImplTy> {
+    let item = tcx
+        .associated_items(impl_id)
+        .in_definition_order()
+        .find(|item| item.kind == ty::AssocKind::Type && item.name.as_str() == "Item");
+    if item.is_none() {
+        warn!(
+            "Source impl `{}` has no `Item` type",
+            tcx.def_path_str(impl_id)
+        );
+    }
+    item.map(|item| impl_ty(tcx.type_of(item.def_id).instantiate_identity()))
+}
+
+/// Type of the impl with the type parameters that it names.
+fn impl_ty(ty: ty::Ty) -> ImplTy {
+    let mut params = Vec::new();
+    for arg in ty.walk() {
+        if let ty::GenericArgKind::Type(arg) = arg.unpack() {
+            if let ty::Param(param) = arg.kind() {
+                let name = CompactString::from(param.name.as_str());
+                if !params.contains(&name) {
+                    params.push(name);
+                }
+            }
+        }
+    }
+    ImplTy {
+        ty: ty_to_string(ty),
+        params,
+    }
+}
+
+/// Print the type with full paths, e.g. `chrono::NaiveDate` or `ee_to_csv::Record`.
+fn ty_to_string(ty: ty::Ty) -> String {
+    ty::print::with_no_trimmed_paths!(ty.to_string())
+}
+
 fn sink_trait_def_id(tcx: TyCtxt) -> Option<DefId> {
     trait_def_id(tcx, "Sink")
 }
@@ -0,0 +1,282 @@
+//! Cache of the analysis results, to skip the compiler for the projects that did not
+//! change since the last analysis.
+//!
+//! Each entry is a JSON file named after the key of the project, which is a SHA-256
+//! digest of everything the analysis depends on: the Rust files with the added content,
+//! the compiler, the compiled dependencies and the configuration. The entry holds
+//! the key too, and is used only if it matches. Only successful analyses are cached.
+
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use compact_str::CompactString;
+use log::*;
+use sha2::{Digest, Sha256};
+
+use crate::{Allow, AnalyzeConfig, Edition, Forbid, ProjectContent, RsFile};
+
+const EXT: &str = "json";
+
+/// Key of the project in the cache.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Key([u8; 32]);
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Entry of the cache as stored in its file.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Entry<C> {
+    /// [Key] of the project, to tell the entry from the one of another project.
+    key: String,
+
+    content: C,
+}
+
+/// Hasher of the key inputs. Each input is written with its length, so that the
+/// boundaries between the inputs are a part of the key too. Unlike [std::hash::Hash],
+/// the bytes are the same for all the Rust versions and platforms.
+struct KeyHasher(Sha256);
+
+impl KeyHasher {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.len(bytes.len());
+        self.0.update(bytes);
+    }
+
+    fn len(&mut self, len: usize) {
+        self.u64(len as u64);
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.update(value.to_le_bytes());
+    }
+
+    fn str(&mut self, s: &str) {
+        self.bytes(s.as_bytes());
+    }
+
+    fn strs(&mut self, list: &[CompactString]) {
+        self.len(list.len());
+        for s in list {
+            self.str(s);
+        }
+    }
+
+    fn path(&mut self, path: &Path) {
+        self.bytes(path.as_os_str().as_encoded_bytes());
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.0.update([value as u8]);
+    }
+
+    fn finish(self) -> Key {
+        Key(self.0.finalize().into())
+    }
+}
+
+/// Key of the project, from the fake main file and the other project files.
+pub(crate) fn key(main: &str, files: &[RsFile], allow: &Allow, config: &AnalyzeConfig) -> Key {
+    let mut hasher = KeyHasher(Sha256::new());
+    // Entries of another version of the crate or of the compiler may not be compatible.
+    hasher.str(env!("CARGO_PKG_VERSION"));
+    hasher.str(rustc_interface::util::rustc_version_str().unwrap_or_default());
+
+    hasher.str(main);
+    hasher.len(files.len());
+    for file in files {
+        hasher.path(&file.path);
+        hasher.str(&file.content);
+    }
+
+    let Allow {
+        loops,
+        recursion,
+        apis,
+        crates,
+    } = allow;
+    for list in [loops, recursion, apis, crates] {
+        hasher.strs(list);
+    }
+    let AnalyzeConfig {
+        deps_dir,
+        externs,
+        sysroot,
+        edition,
+        cfg,
+        forbid,
+        cache: _,
+    } = config;
+    // The sysroot that the compiler uses, also when it is not configured.
+    let sysroot = match sysroot {
+        Some(path) => Ok(path.clone()),
+        None => rustc_session::filesearch::get_or_default_sysroot(),
+    };
+    match sysroot {
+        Ok(path) => {
+            hasher.bool(true);
+            hasher.path(&path);
+        }
+        Err(e) => {
+            debug!("Sysroot is not hashed: {e}");
+            hasher.bool(false);
+        }
+    }
+    hasher.str(match edition {
+        Edition::Edition2015 => "2015",
+        Edition::Edition2018 => "2018",
+        Edition::Edition2021 => "2021",
+    });
+    hasher.strs(cfg);
+    let Forbid {
+        paths,
+        unsafe_blocks,
+        static_mut,
+    } = forbid;
+    hasher.strs(paths);
+    hasher.bool(*unsafe_blocks);
+    hasher.bool(*static_mut);
+
+    // Dependencies are identified by their paths, sizes and modification times,
+    // which change whenever Cargo rebuilds them.
+    let mut deps: Vec<PathBuf> = match std::fs::read_dir(deps_dir) {
+        Ok(entries) => entries.filter_map(|v| Some(v.ok()?.path())).collect(),
+        Err(e) => {
+            debug!("Dependency directory is not hashed: {e}");
+            Vec::new()
+        }
+    };
+    deps.sort();
+    deps.extend(externs.iter().map(|(_, path)| path.clone()));
+    hasher.len(externs.len());
+    for (name, _) in externs {
+        hasher.str(name);
+    }
+    hasher.len(deps.len());
+    for path in deps {
+        hash_file_meta(&path, &mut hasher);
+    }
+
+    hasher.finish()
+}
+
+fn hash_file_meta(path: &Path, hasher: &mut KeyHasher) {
+    hasher.path(path);
+    let Ok(meta) = std::fs::metadata(path) else {
+        hasher.bool(false);
+        return;
+    };
+    hasher.bool(true);
+    hasher.u64(meta.len());
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|v| v.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    hasher.u64(modified.as_secs());
+    hasher.u64(modified.subsec_nanos().into());
+}
+
+fn entry_path(dir: &Path, key: Key) -> PathBuf {
+    dir.join(format!("{key}.{EXT}"))
+}
+
+/// Cached content of the project, if there is a valid entry for the key.
+pub(crate) fn read(dir: &Path, key: Key) -> Option<ProjectContent> {
+    let path = entry_path(dir, key);
+    let bytes = match std::fs::read(&path) {
+        Ok(v) => v,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            debug!("No cache entry for key {key}");
+            return None;
+        }
+        Err(e) => {
+            warn!("Cannot read cache entry `{}`: {e}", path.display());
+            return None;
+        }
+    };
+    match serde_json::from_slice::<Entry<ProjectContent>>(&bytes) {
+        Ok(entry) if entry.key == key.to_string() => {
+            debug!("Using cache entry `{}`", path.display());
+            Some(entry.content)
+        }
+        Ok(entry) => {
+            warn!(
+                "Skipping cache entry `{}` of another key {}",
+                path.display(),
+                entry.key
+            );
+            None
+        }
+        Err(e) => {
+            warn!("Skipping invalid cache entry `{}`: {e}", path.display());
+            None
+        }
+    }
+}
+
+/// Store the content of the project. Failures are logged, as the cache is
+/// not required for the analysis.
+pub(crate) fn write(dir: &Path, key: Key, content: &ProjectContent) {
+    let path = entry_path(dir, key);
+    let result = (|| -> io::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        let entry = Entry {
+            key: key.to_string(),
+            content,
+        };
+        let bytes = serde_json::to_vec(&entry)?;
+        // Write to a temporary file first, so that readers never see a partial entry.
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, bytes)?;
+        std::fs::rename(&tmp, &path)
+    })();
+    match result {
+        Ok(()) => debug!("Stored cache entry `{}`", path.display()),
+        Err(e) => warn!("Cannot store cache entry `{}`: {e}", path.display()),
+    }
+}
+
+/// Whether the file is an entry of the cache or its temporary file, named after a [Key].
+fn is_entry(path: &Path) -> bool {
+    let ext = path.extension().and_then(|v| v.to_str());
+    let stem = path
+        .file_stem()
+        .and_then(|v| v.to_str())
+        .unwrap_or_default();
+    (ext == Some(EXT) || ext == Some("tmp"))
+        && stem.len() == 64
+        && stem.bytes().all(|v| matches!(v, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Remove the entries and their temporary files from the cache directory.
+/// Other files, even other JSON files, are left in place.
+pub(crate) fn clear(dir: &Path) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(v) => v,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return,
+        Err(e) => {
+            warn!("Cannot clear cache directory `{}`: {e}", dir.display());
+            return;
+        }
+    };
+    for path in entries.filter_map(|v| Some(v.ok()?.path())) {
+        if !is_entry(&path) {
+            continue;
+        }
+        match std::fs::remove_file(&path) {
+            Ok(()) => trace!("Removed cache entry `{}`", path.display()),
+            Err(e) => warn!("Cannot remove cache entry `{}`: {e}", path.display()),
+        }
+    }
+}
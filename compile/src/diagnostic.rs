@@ -0,0 +1,229 @@
+//! Diagnostics of the compiler, collected instead of being printed to stderr.
+
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use compact_str::{CompactString, ToCompactString};
+use log::*;
+use rustc_data_structures::sync::Lrc;
+use rustc_errors::{
+    emitter::Emitter,
+    translation::{to_fluent_args, Translate},
+    DiagInner, DiagMessage, FluentBundle, LazyFallbackBundle, MultiSpan, Style,
+};
+use rustc_span::source_map::SourceMap;
+
+use crate::SrcLoc;
+
+/// Diagnostic reported by the compiler for the project files.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub level: Level,
+
+    /// Error code, e.g. `E0308`.
+    pub code: Option<CompactString>,
+
+    pub message: String,
+
+    /// Location of the primary span, if the diagnostic has one.
+    pub loc: Option<SrcLoc>,
+
+    /// Notes and help messages attached to the diagnostic.
+    pub children: Vec<Diagnostic>,
+
+    /// Suggested changes to the code to fix the problem.
+    pub suggestions: Vec<Suggestion>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Error,
+    Warning,
+    Note,
+    Help,
+}
+
+/// Change to the code suggested by the compiler.
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub message: String,
+
+    /// Code to put in place of the code at the locations. All parts
+    /// are to be applied together.
+    pub parts: Vec<SuggestionPart>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SuggestionPart {
+    /// Start of the replaced code.
+    pub loc: SrcLoc,
+
+    /// End of the replaced code.
+    pub end: SrcLoc,
+
+    pub snippet: String,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Level::Error => "error",
+            Level::Warning => "warning",
+            Level::Note => "note",
+            Level::Help => "help",
+        };
+        f.write_str(s)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(loc) = &self.loc {
+            write!(f, "{loc}: ")?;
+        }
+        write!(f, "{}", self.level)?;
+        if let Some(code) = &self.code {
+            write!(f, "[{code}]")?;
+        }
+        write!(f, ": {}", self.message)?;
+
+        for child in &self.children {
+            write!(f, "\n    = {}: {}", child.level, child.message)?;
+            if let Some(loc) = &child.loc {
+                write!(f, " ({loc})")?;
+            }
+        }
+        for suggestion in &self.suggestions {
+            write!(f, "\n    = help: {}", suggestion.message)?;
+            for part in &suggestion.parts {
+                write!(f, "\n        {}: `{}`", part.loc, part.snippet)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Errors among the collected diagnostics.
+pub(crate) fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|v| v.level == Level::Error)
+}
+
+/// Emitter that collects the diagnostics into a shared list.
+pub(crate) struct Collector {
+    source_map: Lrc<SourceMap>,
+    fallback_bundle: LazyFallbackBundle,
+    out: Arc<Mutex<Vec<Diagnostic>>>,
+}
+
+impl Collector {
+    pub fn new(source_map: Lrc<SourceMap>, out: Arc<Mutex<Vec<Diagnostic>>>) -> Self {
+        let fallback_bundle = rustc_errors::fallback_fluent_bundle(
+            rustc_driver::DEFAULT_LOCALE_RESOURCES.to_vec(),
+            false,
+        );
+        Collector {
+            source_map,
+            fallback_bundle,
+            out,
+        }
+    }
+
+    fn primary_loc(&self, span: &MultiSpan) -> Option<SrcLoc> {
+        span.primary_span()
+            .filter(|v| !v.is_dummy())
+            .map(|v| SrcLoc::from_source_map(&self.source_map, v.lo()))
+    }
+
+    fn translate(&self, messages: &[(DiagMessage, Style)], diag: &DiagInner) -> String {
+        let args = to_fluent_args(diag.args.iter());
+        self.translate_messages(messages, &args).into_owned()
+    }
+}
+
+impl Translate for Collector {
+    fn fluent_bundle(&self) -> Option<&Lrc<FluentBundle>> {
+        None
+    }
+
+    fn fallback_fluent_bundle(&self) -> &FluentBundle {
+        &self.fallback_bundle
+    }
+}
+
+impl Emitter for Collector {
+    fn emit_diagnostic(&mut self, diag: DiagInner) {
+        use rustc_errors::Level::*;
+
+        let level = match diag.level {
+            Bug | Fatal | Error | DelayedBug => Level::Error,
+            ForceWarning(_) | Warning => Level::Warning,
+            Note | OnceNote => Level::Note,
+            Help | OnceHelp => Level::Help,
+            FailureNote | Allow | Expect(_) => {
+                // Summaries like "aborting due to 2 previous errors".
+                trace!("Skipping diagnostic: {:?}", diag.messages);
+                return;
+            }
+        };
+
+        let children = diag
+            .children
+            .iter()
+            .map(|sub| Diagnostic {
+                level: match sub.level {
+                    Help | OnceHelp => Level::Help,
+                    Warning => Level::Warning,
+                    _ => Level::Note,
+                },
+                code: None,
+                message: self.translate(&sub.messages, &diag),
+                loc: self.primary_loc(&sub.span),
+                children: Vec::new(),
+                suggestions: Vec::new(),
+            })
+            .collect();
+
+        let suggestions = diag
+            .suggestions
+            .iter()
+            .flatten()
+            .filter_map(|sugg| {
+                // Alternatives to choose from, only the first one is taken.
+                let substitution = sugg.substitutions.first()?;
+                let parts = substitution
+                    .parts
+                    .iter()
+                    .map(|part| SuggestionPart {
+                        loc: SrcLoc::from_source_map(&self.source_map, part.span.lo()),
+                        end: SrcLoc::from_source_map(&self.source_map, part.span.hi()),
+                        snippet: part.snippet.clone(),
+                    })
+                    .collect();
+                Some(Suggestion {
+                    message: self.translate(&[(sugg.msg.clone(), Style::NoStyle)], &diag),
+                    parts,
+                })
+            })
+            .collect();
+
+        let diagnostic = Diagnostic {
+            level,
+            code: diag.code.map(|v| v.to_compact_string()),
+            message: self.translate(&diag.messages, &diag),
+            loc: self.primary_loc(&diag.span),
+            children,
+            suggestions,
+        };
+        debug!("Compiler diagnostic: {diagnostic}");
+        self.out
+            .lock()
+            .expect("emitter does not panic while holding the lock")
+            .push(diagnostic);
+    }
+
+    fn source_map(&self) -> Option<&Lrc<SourceMap>> {
+        Some(&self.source_map)
+    }
+}
@@ -9,6 +9,7 @@ use std::{
 use compact_str::{CompactString, ToCompactString};
 use smallvec::SmallVec;
 
+extern crate rustc_data_structures;
 extern crate rustc_driver;
 extern crate rustc_error_codes;
 extern crate rustc_errors;
@@ -22,10 +23,12 @@ extern crate rustc_ast;
 extern crate rustc_middle;
 
 pub mod analyze;
+mod cache;
+pub mod diagnostic;
 
 pub type ItemId = u32;
 
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct ProjectContent {
     /// Public types that are accessible in the configuration files.
     pub pub_types: Vec<ItemPath>,
@@ -37,9 +40,54 @@ pub struct ProjectContent {
     /// Public sources that are accessible in the configuration files.
     /// ID into [pub_types].
     pub sources: Vec<ItemId>,
+
+    /// Types that sinks accept, as `T` of each `impl Sink<T>`.
+    /// One sink can implement the trait for several types.
+    pub sink_tys: Vec<ItemTy>,
+
+    /// Types that sources produce, as `Source::Item` of each `impl Source`.
+    pub source_tys: Vec<ItemTy>,
+
+    /// Constructors of the sinks and sources, to initialize the native bindings with.
+    pub constructors: Vec<Constructor>,
 }
 
-#[derive(Debug)]
+/// Type associated with a sink or a source.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct ItemTy {
+    /// ID into [ProjectContent::pub_types].
+    pub item: ItemId,
+
+    /// Full path of the type, e.g. `chrono::NaiveDate` or `ee_to_csv::Record`.
+    pub ty: CompactString,
+
+    /// Type parameters of the impl that the type names, e.g. `T` of
+    /// `impl<T> Sink<Vec<T>> for Log`. They stand for any type.
+    pub params: Vec<CompactString>,
+}
+
+/// Public associated function of a sink or a source that returns `Self`,
+/// e.g. `Ee2Csv::new(csv: CsvSink) -> Self`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Constructor {
+    /// ID into [ProjectContent::pub_types].
+    pub item: ItemId,
+
+    /// Name of the function, e.g. `new`.
+    pub name: CompactString,
+
+    pub params: Vec<CtorParam>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CtorParam {
+    pub name: CompactString,
+
+    /// Full path of the type, as in [ItemTy::ty].
+    pub ty: CompactString,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct ItemPath {
     pub segments: Vec<CompactString>,
 }
@@ -50,51 +98,499 @@ impl fmt::Display for ItemPath {
     }
 }
 
+/// Functions that are exempt from the analysis checks, by their paths as printed
+/// in the errors, e.g. `csv_sink::RowSequence::advance`. Methods are named after
+/// the type they are implemented for, also in trait impls.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Allow {
+    /// Functions that may have loops.
+    pub loops: Vec<CompactString>,
+
+    /// Functions that may be part of a recursion cycle. The cycle is allowed
+    /// if any of its functions is listed.
+    pub recursion: Vec<CompactString>,
+
+    /// Items that may use the forbidden APIs. Modules are allowed with all
+    /// their items, e.g. `csv_sink` allows `csv_sink::write`.
+    pub apis: Vec<CompactString>,
+
+    /// External crates that the project files may use, e.g. `chrono`. The runtime
+    /// and the standard library crates are always allowed. Each crate should be
+    /// among the [AnalyzeConfig::crates].
+    pub crates: Vec<CompactString>,
+}
+
+/// Configuration of the compiler that analyzes the project files.
+#[derive(Debug, Clone)]
+pub struct AnalyzeConfig {
+    /// Directory with the compiled dependencies of the project files, `.rmeta` and `.so`
+    /// files named as Cargo names them, e.g. `libruntime-0123456789abcdef.rmeta`.
+    /// Each one is available as an extern crate. The runtime crate must be among them.
+    pub deps_dir: PathBuf,
+
+    /// Extern crates in addition to the ones of [Self::deps_dir], as crate name and
+    /// path to the compiled crate.
+    pub externs: Vec<(CompactString, PathBuf)>,
+
+    /// Sysroot of the toolchain. If not set, it is found by the location of the
+    /// compiler libraries, same as `rustc` does.
+    pub sysroot: Option<PathBuf>,
+
+    /// Edition of the project files.
+    pub edition: Edition,
+
+    /// Configuration flags as for `--cfg` of `rustc`, e.g. `feature="csv"` or `test`.
+    pub cfg: Vec<CompactString>,
+
+    /// APIs that the project files must not use.
+    pub forbid: Forbid,
+
+    /// Cache of the analysis results.
+    pub cache: Cache,
+}
+
+/// APIs that the project files must not use, to keep the pipelines deterministic
+/// and free of side effects outside of the sinks.
+#[derive(Debug, Clone)]
+pub struct Forbid {
+    /// Paths of the items that cannot be called or named. Modules forbid all
+    /// their items, e.g. `std::fs` forbids `std::fs::read`.
+    pub paths: Vec<CompactString>,
+
+    /// Forbid `unsafe` blocks.
+    pub unsafe_blocks: bool,
+
+    /// Forbid `static mut` items.
+    pub static_mut: bool,
+}
+
+/// Cache of the analysis results. A project whose Rust files, added content,
+/// dependencies and configuration did not change since the last successful
+/// analysis is loaded from the cache without running the compiler.
+#[derive(Debug, Clone, Default)]
+pub struct Cache {
+    /// Directory to store the cache entries in. The cache is disabled if not set.
+    pub dir: Option<PathBuf>,
+
+    pub mode: CacheMode,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CacheMode {
+    /// Load the content from the cache if present, and store it after the analysis.
+    #[default]
+    Use,
+
+    /// Run the analysis without reading or writing the cache.
+    Bypass,
+
+    /// Remove all entries from the cache directory, then run the analysis
+    /// and store its result.
+    Clear,
+}
+
+impl Default for Forbid {
+    fn default() -> Self {
+        Forbid {
+            paths: ["std::fs", "std::net", "std::process", "std::env::set_var"]
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+            unsafe_blocks: true,
+            static_mut: true,
+        }
+    }
+}
+
+impl Default for AnalyzeConfig {
+    fn default() -> Self {
+        AnalyzeConfig {
+            deps_dir: Path::new(env!("CARGO_MANIFEST_DIR")).join("rlibs"),
+            externs: Vec::new(),
+            sysroot: None,
+            edition: Edition::default(),
+            cfg: Vec::new(),
+            forbid: Forbid::default(),
+            cache: Cache::default(),
+        }
+    }
+}
+
+/// Rust edition of the project files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Edition {
+    Edition2015,
+    Edition2018,
+    #[default]
+    Edition2021,
+}
+
+impl From<Edition> for rustc_span::edition::Edition {
+    fn from(edition: Edition) -> Self {
+        match edition {
+            Edition::Edition2015 => Self::Edition2015,
+            Edition::Edition2018 => Self::Edition2018,
+            Edition::Edition2021 => Self::Edition2021,
+        }
+    }
+}
+
+impl AnalyzeConfig {
+    /// Names of the crates that are available to the project files, which
+    /// are the ones of [Self::deps_dir] and [Self::externs].
+    pub fn crates(&self) -> Result<Vec<CompactString>, ProjectContentError> {
+        let externs = self.externs()?;
+        Ok(externs
+            .iter()
+            .map(|(name, _)| name.as_str().into())
+            .collect())
+    }
+
+    fn sysroot(&self) -> Result<Option<PathBuf>, ProjectContentError> {
+        match &self.sysroot {
+            Some(path) if !path.is_dir() => Err(ProjectContentError::SysrootNotFound(path.clone())),
+            v => Ok(v.clone()),
+        }
+    }
+
+    fn crate_cfg(&self) -> Result<Vec<String>, ProjectContentError> {
+        self.cfg
+            .iter()
+            .map(|cfg| {
+                let is_valid = match cfg.split_once('=') {
+                    None => is_ident(cfg),
+                    Some((name, value)) => {
+                        is_ident(name)
+                            && value.len() >= 2
+                            && value.starts_with('"')
+                            && value.ends_with('"')
+                            && !value[1..value.len() - 1].contains('"')
+                    }
+                };
+                if is_valid {
+                    Ok(cfg.to_string())
+                } else {
+                    Err(ProjectContentError::InvalidCfg(cfg.clone()))
+                }
+            })
+            .collect()
+    }
+
+    fn externs(&self) -> Result<rustc_session::config::Externs, ProjectContentError> {
+        use rustc_session::{config, utils::CanonicalizedPath};
+        use std::collections::btree_map::Entry;
+
+        let mut map = std::collections::BTreeMap::new();
+        let mut insert = |name: &str, path: &Path| {
+            debug!("Registering extern library: {name} - ({})", path.display());
+            // Upsert the entry.
+            match map.entry(name.to_string()) {
+                Entry::Vacant(e) => {
+                    let cfg = config::ExternEntry {
+                        location: config::ExternLocation::ExactPaths({
+                            let mut tree = std::collections::BTreeSet::new();
+                            tree.insert(CanonicalizedPath::new(path));
+                            tree
+                        }),
+                        is_private_dep: false,
+                        add_prelude: true,
+                        nounused_dep: false,
+                        force: false,
+                    };
+                    e.insert(cfg);
+                }
+                Entry::Occupied(mut e) => {
+                    if let config::ExternLocation::ExactPaths(location) = &mut e.get_mut().location
+                    {
+                        location.insert(CanonicalizedPath::new(path));
+                    } else {
+                        unreachable!("location is always ExactPaths");
+                    }
+                }
+            }
+        };
+
+        let deps_err = |err| ProjectContentError::DepsDir {
+            path: self.deps_dir.clone(),
+            err,
+        };
+        for entry in std::fs::read_dir(&self.deps_dir).map_err(deps_err)? {
+            let path = entry.map_err(deps_err)?.path();
+            let ext = path
+                .extension()
+                .and_then(|v| v.to_str())
+                .unwrap_or_default();
+            if ext != "rmeta" && ext != "so" {
+                trace!("Skipping file: {}", path.display());
+                continue;
+            }
+
+            // Strip off the extension, hash suffix and lib prefix.
+            let name = path
+                .file_stem()
+                .and_then(|v| v.to_str())
+                .and_then(|v| v.rsplit_once('-'))
+                .and_then(|(v, _hash)| v.strip_prefix("lib"));
+            match name {
+                Some(name) => insert(name, &path),
+                None => warn!("Skipping file with unexpected name: {}", path.display()),
+            }
+        }
+
+        for (name, path) in &self.externs {
+            if !path.is_file() {
+                return Err(ProjectContentError::ExternNotFound {
+                    name: name.clone(),
+                    path: path.clone(),
+                });
+            }
+            insert(name, path);
+        }
+
+        if !map.contains_key(analyze::STD_NAME) {
+            return Err(ProjectContentError::NoRuntime(self.deps_dir.clone()));
+        }
+        Ok(config::Externs::new(map))
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum ProjectContentError {
     #[error(transparent)]
     Io(#[from] std::io::Error),
 
-    #[error("Forbidden loops in the project")]
-    ForbiddenLoops,
+    #[error("Dependency directory `{}` cannot be read. {err}", path.display())]
+    DepsDir { path: PathBuf, err: io::Error },
+
+    #[error("Extern crate `{name}` is not found at `{}`", path.display())]
+    ExternNotFound { name: CompactString, path: PathBuf },
+
+    #[error("Runtime crate `{}` is not found in `{}`", analyze::STD_NAME, .0.display())]
+    NoRuntime(PathBuf),
+
+    #[error("Sysroot directory `{}` does not exist", .0.display())]
+    SysrootNotFound(PathBuf),
+
+    #[error("Invalid cfg flag `{0}`, expected `name` or `name=\"value\"`")]
+    InvalidCfg(CompactString),
+
+    #[error("Allowed crate `{0}` is not among the dependencies available to the analyzer")]
+    UnknownCrate(CompactString),
+
+    #[error("File `{}` cannot be a module, path segments should be identifiers", .0.display())]
+    InvalidModPath(PathBuf),
+
+    #[error(
+        "File for the module is found at both `{}` and `{}`, only one of them should exist",
+        .0.display(),
+        .1.display()
+    )]
+    AmbiguousModFile(PathBuf, PathBuf),
+
+    #[error(
+        "Project files failed to compile:\n{}",
+        .0
+        .iter()
+        .map(ToString::to_string).collect::<Vec<_>>().join("\n")
+    )]
+    Compile(Vec<diagnostic::Diagnostic>),
+
+    #[error(
+        "Forbidden loops in the project:\n{}",
+        .0
+        .iter()
+        .map(ToString::to_string).collect::<Vec<_>>().join("\n")
+    )]
+    ForbiddenLoops(Vec<ForbiddenLoop>),
 
     #[error(
-        "Recursions found in the project: {}",
+        "Recursions found in the project:\n{}",
         .0
         .iter()
-        .map(ToString::to_string).collect::<Vec<_>>().join(", ")
+        .map(ToString::to_string).collect::<Vec<_>>().join("\n")
     )]
     Recursions(Vec<Recursion>),
+
+    #[error(
+        "Forbidden APIs used in the project:\n{}",
+        .0
+        .iter()
+        .map(ToString::to_string).collect::<Vec<_>>().join("\n")
+    )]
+    ForbiddenApis(Vec<ForbiddenApi>),
+
+    #[error(
+        "Crates used in the project are not allowed, list them in `allow.crates`:\n{}",
+        .0
+        .iter()
+        .map(ToString::to_string).collect::<Vec<_>>().join("\n")
+    )]
+    UnlistedCrates(Vec<UnlistedCrate>),
+}
+
+/// Position in a project file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SrcLoc {
+    /// Path of the file, relative to the project directory.
+    pub file: CompactString,
+
+    /// Line, starting from 1.
+    pub line: usize,
+
+    /// Column in characters, starting from 1.
+    pub col: usize,
+}
+
+impl SrcLoc {
+    fn from_span(tcx: rustc_middle::ty::TyCtxt, span: rustc_span::Span) -> Self {
+        Self::from_source_map(tcx.sess.source_map(), span.lo())
+    }
+
+    fn from_source_map(
+        source_map: &rustc_span::source_map::SourceMap,
+        pos: rustc_span::BytePos,
+    ) -> Self {
+        let loc = source_map.lookup_char_pos(pos);
+        SrcLoc {
+            file: loc.file.name.prefer_local().to_compact_string(),
+            line: loc.line,
+            col: loc.col_display + 1,
+        }
+    }
+}
+
+impl fmt::Display for SrcLoc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.col)
+    }
+}
+
+#[derive(Debug)]
+pub struct ForbiddenLoop {
+    /// Path of the function with the loop.
+    pub item: String,
+
+    /// Location of the loop.
+    pub loc: SrcLoc,
+}
+
+impl fmt::Display for ForbiddenLoop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: loop in `{}`", self.loc, self.item)
+    }
+}
+
+#[derive(Debug)]
+pub struct ForbiddenApi {
+    /// Path of the item that uses the API.
+    pub item: String,
+
+    pub api: Api,
+
+    /// Location of the use.
+    pub loc: SrcLoc,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Api {
+    /// Item from [Forbid::paths], e.g. `std::fs::read`.
+    Path(String),
+
+    UnsafeBlock,
+
+    StaticMut,
+}
+
+impl fmt::Display for ForbiddenApi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.api {
+            Api::Path(path) => write!(f, "{}: `{}` uses `{path}`", self.loc, self.item),
+            Api::UnsafeBlock => write!(f, "{}: unsafe block in `{}`", self.loc, self.item),
+            Api::StaticMut => write!(f, "{}: `{}` is a mutable static", self.loc, self.item),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct UnlistedCrate {
+    /// Name of the crate.
+    pub krate: CompactString,
+
+    /// Path of the item that uses the crate, or of its module if the crate is not
+    /// among the dependencies.
+    pub item: String,
+
+    /// Location of the use.
+    pub loc: SrcLoc,
+}
+
+impl fmt::Display for UnlistedCrate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: `{}` uses crate `{}`",
+            self.loc, self.item, self.krate
+        )
+    }
 }
 
+/// Cycle of calls. The callee of each edge is the caller of the next one,
+/// and the callee of the last edge is the caller of the first one.
 #[derive(Debug)]
 pub struct Recursion {
-    pub callee: String,
+    pub cycle: Vec<CallEdge>,
+}
+
+#[derive(Debug)]
+pub struct CallEdge {
     pub caller: String,
+    pub callee: String,
+
+    /// Location of the call in the caller.
+    pub loc: SrcLoc,
 }
 
 impl fmt::Display for Recursion {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{} -> {}", self.caller, self.callee)
+        for edge in &self.cycle {
+            write!(f, "`{}` -> ", edge.caller)?;
+        }
+        if let Some(first) = self.cycle.first() {
+            write!(f, "`{}`", first.caller)?;
+        }
+        for edge in &self.cycle {
+            write!(
+                f,
+                "\n    {}: `{}` calls `{}`",
+                edge.loc, edge.caller, edge.callee
+            )?;
+        }
+        Ok(())
     }
 }
 
 impl ProjectContent {
     /// Load all "rs" files from a project directory. This function does not validate
     /// if the directory has a valid project structure.
-    /// Attach to the project content tokens that are required to be present in the main file.
+    /// Attach to the project content tokens that are required to be present in the project,
+    /// by the paths of the modules to put them into, e.g. `module1::module2`. Empty path is
+    /// the crate root.
+    /// The loop and recursion checks skip the functions listed in `allow`.
+    /// The result is taken from the cache if [AnalyzeConfig::cache] has an entry
+    /// for the project.
     pub fn load_from_project_dir(
         project_dir: &Path,
-        added_content: proc_macro2::TokenStream,
+        added_content: &[(CompactString, proc_macro2::TokenStream)],
+        allow: &Allow,
+        config: &AnalyzeConfig,
     ) -> Result<Self, ProjectContentError> {
         let rust_files = {
             let mut buf = SmallVec::new();
             collect_files(project_dir, &mut buf)?;
             buf
         };
-        let main = fake_main_for(&rust_files, added_content);
 
-        let other_files = {
+        let mut other_files = {
             let maybe_err: Vec<io::Result<RsFile>> = rust_files
                 .into_iter()
                 .map(|path| {
@@ -119,8 +615,28 @@ impl ProjectContent {
 
             arr.into_vec()
         };
+        let main = fake_main_for(&mut other_files, added_content)?;
+
+        let cache_dir = match config.cache.mode {
+            CacheMode::Bypass => None,
+            CacheMode::Use | CacheMode::Clear => config.cache.dir.as_deref(),
+        };
+        let Some(cache_dir) = cache_dir else {
+            return run_analyze(main, other_files, allow, config);
+        };
 
-        run_analyze(main, other_files)
+        if config.cache.mode == CacheMode::Clear {
+            cache::clear(cache_dir);
+        }
+        let key = cache::key(&main, &other_files, allow, config);
+        if let Some(content) = cache::read(cache_dir, key) {
+            info!("Project content is loaded from the cache");
+            return Ok(content);
+        }
+
+        let content = run_analyze(main, other_files, allow, config)?;
+        cache::write(cache_dir, key, &content);
+        Ok(content)
     }
 
     pub fn sinks(&self) -> impl Iterator<Item = &ItemPath> {
@@ -130,6 +646,21 @@ impl ProjectContent {
     pub fn sources(&self) -> impl Iterator<Item = &ItemPath> {
         self.sources.iter().map(|id| &self.pub_types[*id as usize])
     }
+
+    /// Types `T` for which the sink implements `Sink<T>`.
+    pub fn sink_tys(&self, sink: ItemId) -> impl Iterator<Item = &ItemTy> {
+        self.sink_tys.iter().filter(move |v| v.item == sink)
+    }
+
+    /// Type `Source::Item` of the source.
+    pub fn source_ty(&self, source: ItemId) -> Option<&ItemTy> {
+        self.source_tys.iter().find(|v| v.item == source)
+    }
+
+    /// Constructors of the sink or the source.
+    pub fn constructors(&self, item: ItemId) -> impl Iterator<Item = &Constructor> {
+        self.constructors.iter().filter(move |v| v.item == item)
+    }
 }
 
 /// Collect all files that have 'rs' extension inside this and children directories.
@@ -162,27 +693,106 @@ fn collect_files(dir: &Path, buf: &mut SmallVec<[PathBuf; 64]>) -> Result<(), Pr
 }
 
 /// Create a fake main file content that declares all found files as modules and serves as a crate
-/// root.
-fn fake_main_for(files: &[PathBuf], added_content: proc_macro2::TokenStream) -> String {
+/// root. Modules follow the directory tree, so `module1/file.rs` is `crate::module1::file`.
+/// Directory without `module1.rs` or `module1/mod.rs` becomes an inline module. Otherwise, that
+/// file is the module body, and declarations of the nested modules are appended to its content.
+/// Having both of the files is an error.
+/// Added content goes to the modules the same way.
+fn fake_main_for(
+    files: &mut [RsFile],
+    added_content: &[(CompactString, proc_macro2::TokenStream)],
+) -> Result<String, ProjectContentError> {
     debug!("Creating fake main file content");
 
-    let added_content = added_content.to_string();
-    let mut main = String::with_capacity(files.len() * 32 + added_content.len());
-    main.push_str("extern crate runtime as permute;\n");
+    let mut root = ModTree::default();
+    for (idx, file) in files.iter().enumerate() {
+        let invalid = || ProjectContentError::InvalidModPath(file.path.clone());
+        let mut segments = file
+            .path
+            .with_extension("")
+            .iter()
+            .map(|v| v.to_str().filter(|v| is_ident(v)).ok_or_else(invalid))
+            .collect::<Result<SmallVec<[_; 8]>, _>>()?;
+        if segments.last() == Some(&"mod") {
+            segments.pop();
+            if segments.is_empty() {
+                // Crate root is the fake main itself.
+                return Err(invalid());
+            }
+        }
 
-    main.push_str(added_content.as_str());
-    for file in files {
-        let module = file
-            .file_stem()
-            .expect("Path was created in function that already operated on the file name to find 'rs' extension")
-            .to_string_lossy();
-        trace!("Declaring module: {module}");
-        main.push_str(&format!("pub mod {module};\n"));
+        let node = segments.into_iter().fold(&mut root, |node, name| {
+            node.children.entry(name.into()).or_default()
+        });
+        // Same as `rustc`, which does not pick between `module.rs` and `module/mod.rs`.
+        if let Some(other) = node.file.replace(idx) {
+            return Err(ProjectContentError::AmbiguousModFile(
+                files[other].path.clone(),
+                file.path.clone(),
+            ));
+        }
+    }
+
+    for (module, tokens) in added_content {
+        let segments = module.split("::").filter(|v| !v.is_empty());
+        let node = segments.fold(&mut root, |node, name| {
+            node.children.entry(name.into()).or_default()
+        });
+        node.added.push_str(&tokens.to_string());
+        node.added.push('\n');
     }
+
+    let mut main = String::with_capacity(files.len() * 32 + root.added.len());
+    main.push_str("extern crate runtime as permute;\n");
+    main.push_str(&root.added);
+    root.declare_children(files, &mut main);
     main.shrink_to_fit();
 
     debug!("Created fake main file content: {} bytes", main.len());
-    main
+    Ok(main)
+}
+
+/// Module of the project, as found in the directory tree.
+#[derive(Default)]
+struct ModTree {
+    /// Index of the file with the module body, if any.
+    file: Option<usize>,
+
+    /// Code to add to the module, besides the file content.
+    added: String,
+
+    children: std::collections::BTreeMap<CompactString, ModTree>,
+}
+
+impl ModTree {
+    fn declare_children(&self, files: &mut [RsFile], out: &mut String) {
+        for (name, child) in &self.children {
+            trace!("Declaring module: {name}");
+            if let Some(idx) = child.file {
+                out.push_str(&format!("pub mod {name};\n"));
+
+                // Appended at the end, so that positions in the file stay as they are.
+                let mut body = std::mem::take(&mut files[idx].content);
+                if !child.children.is_empty() || !child.added.is_empty() {
+                    body.push('\n');
+                    body.push_str(&child.added);
+                    child.declare_children(files, &mut body);
+                }
+                files[idx].content = body;
+            } else {
+                out.push_str(&format!("pub mod {name} {{\n"));
+                out.push_str(&child.added);
+                child.declare_children(files, out);
+                out.push_str("}\n");
+            }
+        }
+    }
+}
+
+fn is_ident(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
 }
 
 struct RsFile {
@@ -221,95 +831,38 @@ impl rustc_span::source_map::FileLoader for RsFileLoader {
 fn run_analyze(
     main: String,
     other_files: Vec<RsFile>,
+    allow: &Allow,
+    analyze_cfg: &AnalyzeConfig,
 ) -> Result<ProjectContent, ProjectContentError> {
-    use analyze::*;
     use rustc_errors::registry;
     use rustc_hash::FxHashMap;
-    use rustc_session::{
-        config,
-        utils::{CanonicalizedPath, NativeLib, NativeLibKind},
-    };
+    use rustc_session::config;
+    use std::sync::{Arc, Mutex};
     debug!("Validating project content");
 
-    let out = std::process::Command::new("rustc")
-        .arg("--print=sysroot")
-        .current_dir(".")
-        .output()
-        .unwrap();
-    let sysroot = std::str::from_utf8(&out.stdout).unwrap().trim();
-
-    let search_path_dir: std::path::PathBuf = "../compile/rlibs".into();
-    assert!(
-        search_path_dir.exists(),
-        "search path directory does not exist"
-    );
-
-    let externs = {
-        const HASH_LEN: usize = 18;
-
-        let mut map = std::collections::BTreeMap::new();
-
-        for entry in std::fs::read_dir(&search_path_dir).unwrap() {
-            let entry = entry.unwrap();
-            let path = entry.path();
-            let filename = path.file_name().unwrap();
-            let ext = path.extension().unwrap_or_default();
-            let filename = filename.to_str().unwrap();
-            let ext = ext.to_str().unwrap_or_default();
-            if ext == "rmeta" || ext == "so" {
-                let skip_prefix = "lib".len();
-
-                // Strip off the extension, hash suffix and lib prefix.
-                let strip = &filename[skip_prefix..filename.len() - HASH_LEN - ext.len()];
-                debug!("Registering extern library: {strip} - ({filename})");
-
-                // Upsert the entry.
-                use std::collections::btree_map::Entry;
-                match map.entry(strip.to_string()) {
-                    Entry::Vacant(e) => {
-                        let cfg = config::ExternEntry {
-                            location: config::ExternLocation::ExactPaths({
-                                let mut tree = std::collections::BTreeSet::new();
-                                tree.insert(CanonicalizedPath::new(&path));
-                                tree
-                            }),
-                            is_private_dep: false,
-                            add_prelude: true,
-                            nounused_dep: false,
-                            force: false,
-                        };
-                        e.insert(cfg);
-                    }
-                    Entry::Occupied(mut e) => {
-                        if let config::ExternLocation::ExactPaths(location) =
-                            &mut e.get_mut().location
-                        {
-                            location.insert(CanonicalizedPath::new(&path));
-                        } else {
-                            unreachable!("location is always ExactPaths");
-                        }
-                    }
-                }
-            } else {
-                trace!("Skipping file: {filename}");
-            }
-        }
-
-        config::Externs::new(map)
-    };
+    let sysroot = analyze_cfg.sysroot()?;
+    let crate_cfg = analyze_cfg.crate_cfg()?;
+    let externs = analyze_cfg.externs()?;
+    if let Some(name) = allow.crates.iter().find(|v| externs.get(v).is_none()) {
+        return Err(ProjectContentError::UnknownCrate(name.clone()));
+    }
+    let diagnostics = Arc::new(Mutex::new(Vec::new()));
 
     use rustc_session::search_paths::PathKind as SearchPathKind;
     let config = rustc_interface::Config {
         // Command line options
         opts: config::Options {
-            // maybe_sysroot: Some(PathBuf::from(sysroot)),
+            maybe_sysroot: sysroot,
             externs,
-            edition: rustc_span::edition::Edition::Edition2021,
-            search_paths: vec![new_search_path(SearchPathKind::Dependency, search_path_dir)],
+            edition: analyze_cfg.edition.into(),
+            search_paths: vec![new_search_path(
+                SearchPathKind::Dependency,
+                analyze_cfg.deps_dir.clone(),
+            )],
             ..config::Options::default()
         },
         // cfg! configuration in addition to the default ones
-        crate_cfg: Vec::new(),
+        crate_cfg,
         crate_check_cfg: Vec::new(),
         output_dir: None,
         output_file: None,
@@ -321,7 +874,14 @@ fn run_analyze(
         locale_resources: rustc_driver::DEFAULT_LOCALE_RESOURCES,
         lint_caps: FxHashMap::default(),
         // This is a callback from the driver that is called when [`ParseSess`] is created.
-        psess_created: None,
+        // Diagnostics are collected to be returned as errors instead of printed to stderr.
+        psess_created: Some(Box::new({
+            let diagnostics = diagnostics.clone();
+            move |psess| {
+                let collector = diagnostic::Collector::new(psess.clone_source_map(), diagnostics);
+                psess.dcx().set_emitter(Box::new(collector));
+            }
+        })),
         // This is a callback from the driver that is called when we're registering lints;
         // it is called during plugin registration when we have the LintStore in a non-shared state.
         //
@@ -342,90 +902,258 @@ fn run_analyze(
         using_internal_features: std::sync::Arc::default(),
     };
 
-    rustc_interface::run_compiler(config, |compiler| {
-        compiler.enter(|queries| {
-            queries.global_ctxt().unwrap().enter(|tcx| {
-                info!("Started compiler, entered global context");
-                // let hir = tcx.hir();
-
-                // info!("Run check for forbidden loops");
-                // if !no_forbidden_loops(hir) {
-                //     return Err(ProjectContentError::ForbiddenLoops);
-                // }
-
-                // info!("Run check for recursion");
-                // match no_recursion(tcx) {
-                //     Ok(()) => {
-                //         info!("No recursion found.");
-                //     }
-                //     Err(recursions) => {
-                //         let r = recursions
-                //             .iter()
-                //             .map(|r| {
-                //                 let callee = tcx.def_path_str(r.callee);
-                //                 let caller = tcx.def_path_str(r.caller);
-                //                 self::Recursion {
-                //                     callee: callee.to_string(),
-                //                     caller: caller.to_string(),
-                //                 }
-                //             })
-                //             .collect();
-                //         return Err(ProjectContentError::Recursions(r));
-                //     }
-                // }
-
-                let pub_types = type_ids(tcx);
-                trace!("Public types: {:#?}", pub_types);
-                let sinks_and_sources = {
-                    let mut val = SinksAndSources::collect_from(tcx);
-                    val.filter_not_in(pub_types.as_slice());
-                    val
+    // The compiler aborts with a fatal error after the closure if any errors were emitted.
+    let result = rustc_driver::catch_fatal_errors(|| {
+        rustc_interface::run_compiler(config, |compiler| {
+            compiler.enter(|queries| {
+                let Ok(gcx) = queries.global_ctxt() else {
+                    // Filled with the diagnostics reported by the compiler.
+                    return Err(ProjectContentError::Compile(Vec::new()));
                 };
-                info!("Sinks and sources collected");
-                trace!("Sinks: {:#?}", sinks_and_sources.sinks);
-                trace!("Sources: {:#?}", sinks_and_sources.sources);
-
-                let sinks = sinks_and_sources
-                    .sinks
-                    .iter()
-                    .map(|id| {
-                        pub_types.iter().position(|v| v == id).expect(
-                            "should be present as we've got the IDs in the same compilation process",
-                        ) as ItemId
-                    })
-                    .collect();
-                let sources = sinks_and_sources
-                    .sources
-                    .iter()
-                    .map(|id| {
-                        pub_types.iter().position(|v| v == id).expect(
-                            "should be present as we've got the IDs in the same compilation process",
-                        ) as ItemId
-                    })
-                    .collect();
-                debug!("Collected type IDs of sinks and sources from the compiler context");
-
-                let pub_type_paths = pub_types
-                    .into_iter()
-                    .map(|id| {
-                        let path = tcx.def_path(id);
-                        let segments = path.data.iter().map(|s| s.to_compact_string()).collect();
-                        ItemPath { segments }
-                    })
-                    .collect();
-                info!("Public types mapped to paths");
-
-                info!("Project content validated");
-                Ok(ProjectContent {
-                    pub_types: pub_type_paths,
-                    sinks,
-                    sources,
+                gcx.enter(|tcx| analyze_crate(tcx, allow, analyze_cfg))
+            })
+        })
+    });
+
+    let diagnostics = std::mem::take(&mut *diagnostics.lock().expect("compiler has finished"));
+    for diagnostic in diagnostics
+        .iter()
+        .filter(|v| v.level != diagnostic::Level::Error)
+    {
+        warn!("{diagnostic}");
+    }
+    match result {
+        Ok(result) if !diagnostic::has_errors(&diagnostics) => result,
+        _ => match unresolved_crates(&diagnostics) {
+            Some(found) => Err(ProjectContentError::UnlistedCrates(found)),
+            None => Err(ProjectContentError::Compile(diagnostics)),
+        },
+    }
+}
+
+/// Crates that the compiler could not find, if these are the only errors. The crates
+/// of the dependencies always resolve and are checked after the analysis, so these are
+/// the crates that are neither allowed nor available. Item of such use is the module
+/// of the file, as the analysis did not get to the items.
+fn unresolved_crates(diagnostics: &[diagnostic::Diagnostic]) -> Option<Vec<UnlistedCrate>> {
+    fn crate_name(error: &diagnostic::Diagnostic) -> Option<&str> {
+        let prefix = match error.code.as_deref()? {
+            "E0463" => "can't find crate for `",
+            "E0433" => "failed to resolve: use of undeclared crate or module `",
+            "E0432" => "unresolved import `",
+            _ => return None,
+        };
+        let name = error.message.strip_prefix(prefix)?.strip_suffix('`')?;
+        is_ident(name).then_some(name)
+    }
+
+    fn module_of(file: &str) -> String {
+        let path = file.strip_suffix(".rs").unwrap_or(file);
+        let path = path.strip_suffix("/mod").unwrap_or(path);
+        path.replace('/', "::")
+    }
+
+    let mut found = Vec::new();
+    for error in diagnostics
+        .iter()
+        .filter(|v| v.level == diagnostic::Level::Error)
+    {
+        let krate = crate_name(error)?;
+        let loc = error.loc.clone()?;
+        found.push(UnlistedCrate {
+            krate: krate.into(),
+            item: module_of(&loc.file),
+            loc,
+        });
+    }
+    (!found.is_empty()).then_some(found)
+}
+
+/// Run the checks and collect the content of the compiled crate.
+fn analyze_crate(
+    tcx: rustc_middle::ty::TyCtxt,
+    allow: &Allow,
+    config: &AnalyzeConfig,
+) -> Result<ProjectContent, ProjectContentError> {
+    use analyze::*;
+    use itertools::Itertools;
+    use rustc_middle::ty;
+
+    info!("Started compiler, entered global context");
+    if tcx.analysis(()).is_err() {
+        error!("Project files have compilation errors");
+        // Filled with the diagnostics reported by the compiler.
+        return Err(ProjectContentError::Compile(Vec::new()));
+    }
+
+    let fn_path = |def: rustc_span::def_id::LocalDefId| fn_path(tcx, def.to_def_id());
+
+    info!("Run check for unlisted crates");
+    if let Err(found) = no_unlisted_crates(tcx, &allow.crates) {
+        let found = found
+            .into_iter()
+            .map(|v| crate::UnlistedCrate {
+                krate: v.krate.as_str().into(),
+                item: fn_path(v.item),
+                loc: SrcLoc::from_span(tcx, v.span),
+            })
+            .collect();
+        return Err(ProjectContentError::UnlistedCrates(found));
+    }
+
+    info!("Run check for forbidden loops");
+    if let Err(loops) = no_forbidden_loops(tcx, &allow.loops) {
+        let loops = loops
+            .into_iter()
+            .map(|v| crate::ForbiddenLoop {
+                item: fn_path(v.item),
+                loc: SrcLoc::from_span(tcx, v.span),
+            })
+            .collect();
+        return Err(ProjectContentError::ForbiddenLoops(loops));
+    }
+
+    info!("Run check for recursion");
+    match no_recursion(tcx, &allow.recursion) {
+        Ok(()) => {
+            info!("No recursion found.");
+        }
+        Err(recursions) => {
+            let r = recursions
+                .into_iter()
+                .map(|r| crate::Recursion {
+                    cycle: r
+                        .cycle
+                        .into_iter()
+                        .map(|edge| crate::CallEdge {
+                            caller: fn_path(edge.caller),
+                            callee: fn_path(edge.callee),
+                            loc: SrcLoc::from_span(tcx, edge.span),
+                        })
+                        .collect(),
                 })
+                .collect();
+            return Err(ProjectContentError::Recursions(r));
+        }
+    }
+
+    let forbid = &config.forbid;
+    let apis = no_forbidden_apis(
+        tcx,
+        &forbid.paths,
+        forbid.unsafe_blocks,
+        forbid.static_mut,
+        &allow.apis,
+    );
+    if let Err(found) = apis {
+        let found = found
+            .into_iter()
+            .map(|v| crate::ForbiddenApi {
+                item: fn_path(v.item),
+                api: match v.api {
+                    Api::Path(def) => {
+                        crate::Api::Path(ty::print::with_no_trimmed_paths!(tcx.def_path_str(def)))
+                    }
+                    Api::UnsafeBlock => crate::Api::UnsafeBlock,
+                    Api::StaticMut => crate::Api::StaticMut,
+                },
+                loc: SrcLoc::from_span(tcx, v.span),
             })
+            .collect();
+        return Err(ProjectContentError::ForbiddenApis(found));
+    }
+
+    let pub_types = type_ids(tcx);
+    trace!("Public types: {:#?}", pub_types);
+    let sinks_and_sources = {
+        let mut val = SinksAndSources::collect_from(tcx);
+        val.filter_not_in(pub_types.as_slice());
+        val
+    };
+    info!("Sinks and sources collected");
+    trace!("Sinks: {:#?}", sinks_and_sources.sinks);
+    trace!("Sources: {:#?}", sinks_and_sources.sources);
+
+    let item_tys = |impls: &[TraitImpl]| -> Vec<ItemTy> {
+        impls
+            .iter()
+            .filter_map(|imp| {
+                let item = pub_type_pos(&pub_types, imp);
+                imp.item_ty.as_ref().map(|ty| ItemTy {
+                    item,
+                    ty: ty.ty.as_str().into(),
+                    params: ty.params.clone(),
+                })
+            })
+            .collect()
+    };
+    let sink_tys = item_tys(&sinks_and_sources.sinks);
+    let source_tys = item_tys(&sinks_and_sources.sources);
+
+    let unique_ids = |impls: &[TraitImpl]| -> Vec<ItemId> {
+        let ids = impls.iter().map(|imp| pub_type_pos(&pub_types, imp));
+        ids.unique().collect()
+    };
+    let sinks = unique_ids(&sinks_and_sources.sinks);
+    let sources = unique_ids(&sinks_and_sources.sources);
+    debug!("Collected type IDs of sinks and sources from the compiler context");
+
+    let adts: SmallVec<[_; 32]> = sinks
+        .iter()
+        .chain(&sources)
+        .map(|&id| pub_types[id as usize])
+        .collect();
+    let constructors = analyze::constructors(tcx, &adts)
+        .into_iter()
+        .map(|ctor| Constructor {
+            item: pub_types
+                .iter()
+                .position(|v| *v == ctor.self_id)
+                .expect("constructors are collected for the public types")
+                as ItemId,
+            name: ctor.name.into(),
+            params: ctor
+                .params
+                .into_iter()
+                .map(|(name, ty)| CtorParam {
+                    name: name.into(),
+                    ty: ty.into(),
+                })
+                .collect(),
+        })
+        .collect();
+    debug!("Collected constructors of sinks and sources");
+
+    let pub_type_paths = pub_types
+        .into_iter()
+        .map(|id| {
+            let path = tcx.def_path(id);
+            let segments = path.data.iter().map(|s| s.to_compact_string()).collect();
+            ItemPath { segments }
         })
+        .collect();
+    info!("Public types mapped to paths");
+
+    info!("Project content validated");
+    Ok(ProjectContent {
+        pub_types: pub_type_paths,
+        sinks,
+        sources,
+        sink_tys,
+        source_tys,
+        constructors,
     })
 }
 
+/// Position of the implementing type of the sink or source in the public types.
+fn pub_type_pos(pub_types: &[rustc_span::def_id::DefId], imp: &analyze::TraitImpl) -> ItemId {
+    pub_types
+        .iter()
+        .position(|v| Some(*v) == imp.self_id)
+        .expect("filtered to public types, which we've got in the same compilation process")
+        as ItemId
+}
+
 // https://doc.rust-lang.org/beta/nightly-rustc/src/rustc_session/search_paths.rs.html#53-93
 // Remove when we get 'pub fn' in stable.
 fn new_search_path(
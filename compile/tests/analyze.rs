@@ -0,0 +1,381 @@
+use std::path::Path;
+
+use compile::{
+    diagnostic::Level, Allow, AnalyzeConfig, Api, Cache, CacheMode, ProjectContent,
+    ProjectContentError,
+};
+
+fn load(fixture: &str, allow: &Allow) -> Result<ProjectContent, ProjectContentError> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(fixture);
+    ProjectContent::load_from_project_dir(&dir, &[], allow, &AnalyzeConfig::default())
+}
+
+#[test]
+fn native_sink() {
+    let content = load("ee_to_csv", &Allow::default()).unwrap();
+
+    let sinks: Vec<String> = content.sinks().map(ToString::to_string).collect();
+    assert_eq!(sinks, ["ee_to_csv::Ee2Csv"]);
+    assert_eq!(content.sources().count(), 0);
+
+    let id = content.sinks[0];
+    let tys: Vec<&str> = content.sink_tys(id).map(|v| v.ty.as_str()).collect();
+    assert_eq!(tys, ["ee_to_csv::Record"]);
+
+    let ctors: Vec<_> = content
+        .constructors(id)
+        .map(|ctor| {
+            let params: Vec<_> = ctor
+                .params
+                .iter()
+                .map(|v| format!("{}: {}", v.name, v.ty))
+                .collect();
+            (ctor.name.as_str(), params)
+        })
+        .collect();
+    assert_eq!(
+        ctors,
+        [
+            ("new", vec!["capacity: usize".to_owned()]),
+            (
+                "with_rows",
+                vec!["rows: std::vec::Vec<ee_to_csv::Record>".to_owned()]
+            ),
+        ]
+    );
+}
+
+#[test]
+fn generic_sink() {
+    let content = load("generic_sink", &Allow::default()).unwrap();
+
+    let id = content.sinks[0];
+    let tys: Vec<_> = content
+        .sink_tys(id)
+        .map(|v| (v.ty.as_str(), v.params.clone()))
+        .collect();
+    assert_eq!(tys, [("std::vec::Vec<T>", vec!["T".into()])]);
+}
+
+#[test]
+fn forbidden_loop() {
+    let err = load("calls", &Allow::default()).unwrap_err();
+    let ProjectContentError::ForbiddenLoops(loops) = err else {
+        panic!("expected forbidden loops, got: {err}");
+    };
+    assert_eq!(loops.len(), 1);
+    assert_eq!(loops[0].item, "calls::sum");
+    assert_eq!(loops[0].loc.file, "calls.rs");
+    assert_eq!((loops[0].loc.line, loops[0].loc.col), (3, 5));
+}
+
+#[test]
+fn recursion_cycle() {
+    let allow = Allow {
+        loops: vec!["calls::sum".into()],
+        ..Default::default()
+    };
+    let err = load("calls", &allow).unwrap_err();
+    let ProjectContentError::Recursions(recursions) = err else {
+        panic!("expected recursions, got: {err}");
+    };
+    assert_eq!(recursions.len(), 1);
+
+    let cycle: Vec<_> = recursions[0]
+        .cycle
+        .iter()
+        .map(|edge| (edge.caller.as_str(), edge.callee.as_str(), edge.loc.line))
+        .collect();
+    assert_eq!(
+        cycle,
+        [
+            ("calls::even", "calls::odd", 13),
+            ("calls::odd", "calls::even", 21)
+        ]
+    );
+}
+
+#[test]
+fn allowed_recursion() {
+    let allow = Allow {
+        loops: vec!["calls::sum".into()],
+        recursion: vec!["calls::odd".into()],
+        ..Default::default()
+    };
+    load("calls", &allow).unwrap();
+}
+
+#[test]
+fn missing_deps_dir() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ee_to_csv");
+    let config = AnalyzeConfig {
+        deps_dir: dir.join("no_such_dir"),
+        ..Default::default()
+    };
+    let err =
+        ProjectContent::load_from_project_dir(&dir, &[], &Allow::default(), &config).unwrap_err();
+    assert!(matches!(err, ProjectContentError::DepsDir { .. }), "{err}");
+}
+
+#[test]
+fn invalid_cfg() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ee_to_csv");
+    let config = AnalyzeConfig {
+        cfg: vec!["feature=csv".into()],
+        ..Default::default()
+    };
+    let err =
+        ProjectContent::load_from_project_dir(&dir, &[], &Allow::default(), &config).unwrap_err();
+    assert!(matches!(err, ProjectContentError::InvalidCfg(_)), "{err}");
+}
+
+#[test]
+fn module_tree() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/modules");
+    let added = [
+        ("second::deep".into(), "pub struct Added;".parse().unwrap()),
+        ("generated".into(), "pub struct Generated;".parse().unwrap()),
+    ];
+    let content = ProjectContent::load_from_project_dir(
+        &dir,
+        &added,
+        &Allow::default(),
+        &AnalyzeConfig::default(),
+    )
+    .unwrap();
+
+    let mut types: Vec<String> = content.pub_types.iter().map(ToString::to_string).collect();
+    types.sort();
+    assert_eq!(
+        types,
+        [
+            "first::item::Item",
+            "generated::Generated",
+            "second::Second",
+            "second::deep::Added",
+            "second::deep::leaf::Leaf",
+            "second::item::Item",
+            "top::Top",
+        ]
+    );
+}
+
+#[test]
+fn ambiguous_module_file() {
+    let err = load("ambiguous_module", &Allow::default()).unwrap_err();
+    let ProjectContentError::AmbiguousModFile(first, second) = err else {
+        panic!("expected ambiguous module file, got: {err}");
+    };
+    let mut paths = [first, second];
+    paths.sort();
+    assert_eq!(paths, [Path::new("both.rs"), Path::new("both/mod.rs")]);
+}
+
+#[test]
+fn compile_error() {
+    let err = load("type_error", &Allow::default()).unwrap_err();
+    let ProjectContentError::Compile(diagnostics) = err else {
+        panic!("expected compile errors, got: {err}");
+    };
+    let errors: Vec<_> = diagnostics
+        .iter()
+        .filter(|v| v.level == Level::Error)
+        .collect();
+    assert_eq!(errors.len(), 1, "{diagnostics:#?}");
+    assert_eq!(errors[0].code.as_deref(), Some("E0308"));
+
+    let loc = errors[0].loc.as_ref().unwrap();
+    assert_eq!(loc.file, "broken.rs");
+    assert_eq!((loc.line, loc.col), (3, 5));
+}
+
+#[test]
+fn forbidden_apis() {
+    let err = load("apis", &Allow::default()).unwrap_err();
+    let ProjectContentError::ForbiddenApis(mut found) = err else {
+        panic!("expected forbidden APIs, got: {err}");
+    };
+    found.sort_by_key(|v| v.loc.line);
+
+    let found: Vec<_> = found
+        .iter()
+        .map(|v| (v.item.as_str(), &v.api, v.loc.line, v.loc.col))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            (
+                "apis::read_config",
+                &Api::Path("std::fs::read_to_string".into()),
+                2,
+                5
+            ),
+            ("apis::COUNTER", &Api::StaticMut, 5, 1),
+            ("apis::count", &Api::UnsafeBlock, 8, 5),
+        ]
+    );
+}
+
+#[test]
+fn allowed_apis() {
+    let allow = Allow {
+        apis: vec!["apis::read_config".into(), "apis::count".into()],
+        ..Default::default()
+    };
+    let err = load("apis", &allow).unwrap_err();
+    let ProjectContentError::ForbiddenApis(found) = err else {
+        panic!("expected forbidden APIs, got: {err}");
+    };
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].api, Api::StaticMut);
+
+    // The whole module is allowed.
+    let allow = Allow {
+        apis: vec!["apis".into()],
+        ..Default::default()
+    };
+    load("apis", &allow).unwrap();
+}
+
+#[test]
+fn unlisted_crates() {
+    let err = load("crates", &Allow::default()).unwrap_err();
+    let ProjectContentError::UnlistedCrates(mut found) = err else {
+        panic!("expected unlisted crates, got: {err}");
+    };
+    found.sort_by_key(|v| v.loc.line);
+
+    let found: Vec<_> = found
+        .iter()
+        .map(|v| (v.krate.as_str(), v.loc.line, v.loc.col))
+        .collect();
+    assert_eq!(found, [("log", 1, 1), ("chrono", 4, 15)]);
+
+    let allow = Allow {
+        crates: vec!["chrono".into(), "log".into()],
+        ..Default::default()
+    };
+    load("crates", &allow).unwrap();
+}
+
+#[test]
+fn missing_crates() {
+    for (fixture, file) in [
+        ("missing_extern_crate", "extern_crate.rs"),
+        ("missing_use_crate", "use_crate.rs"),
+    ] {
+        let err = load(fixture, &Allow::default()).unwrap_err();
+        let ProjectContentError::UnlistedCrates(found) = err else {
+            panic!("expected unlisted crates in `{fixture}`, got: {err}");
+        };
+        let found: Vec<_> = found
+            .iter()
+            .map(|v| (v.krate.as_str(), v.item.as_str(), v.loc.file.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [("no_such_crate", file.trim_end_matches(".rs"), file)]
+        );
+
+        // Allow list cannot name a crate that is not among the dependencies.
+        let allow = Allow {
+            crates: vec!["no_such_crate".into()],
+            ..Default::default()
+        };
+        let err = load(fixture, &allow).unwrap_err();
+        assert!(matches!(err, ProjectContentError::UnknownCrate(_)), "{err}");
+    }
+}
+
+#[test]
+fn unknown_crate() {
+    let allow = Allow {
+        crates: vec!["no_such_crate".into()],
+        ..Default::default()
+    };
+    let err = load("crates", &allow).unwrap_err();
+    assert!(
+        matches!(&err, ProjectContentError::UnknownCrate(name) if name == "no_such_crate"),
+        "{err}"
+    );
+
+    let crates = AnalyzeConfig::default().crates().unwrap();
+    assert!(crates.iter().any(|v| v == "chrono"), "{crates:?}");
+}
+
+#[test]
+fn cache() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ee_to_csv");
+    let cache_dir = std::env::temp_dir().join(format!("permute-cache-test-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&cache_dir);
+    let load = |mode| {
+        let config = AnalyzeConfig {
+            cache: Cache {
+                dir: Some(cache_dir.clone()),
+                mode,
+            },
+            ..Default::default()
+        };
+        ProjectContent::load_from_project_dir(&dir, &[], &Allow::default(), &config).unwrap()
+    };
+    let entries = || -> Vec<_> {
+        std::fs::read_dir(&cache_dir)
+            .unwrap()
+            .map(|v| v.unwrap().path())
+            .collect()
+    };
+
+    let content = load(CacheMode::Use);
+    let [entry] = &entries()[..] else {
+        panic!("expected one cache entry: {:?}", entries());
+    };
+
+    // Tamper with the entry to tell the cached result from a fresh analysis.
+    let mut cached: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(entry).unwrap()).unwrap();
+    cached["content"]["sinks"] = serde_json::json!([]);
+    std::fs::write(entry, cached.to_string()).unwrap();
+
+    assert_eq!(load(CacheMode::Use).sinks.len(), 0);
+    assert_eq!(load(CacheMode::Bypass).sinks, content.sinks);
+    // Clearing removes only the entries.
+    let other_file = cache_dir.join("settings.json");
+    std::fs::write(&other_file, "{}").unwrap();
+    assert_eq!(load(CacheMode::Clear).sinks, content.sinks);
+    assert!(other_file.exists());
+    std::fs::remove_file(&other_file).unwrap();
+    assert_eq!(load(CacheMode::Use).sinks, content.sinks);
+    assert_eq!(entries().len(), 1);
+
+    // Entry of another project in place of the one of the project is not used.
+    let other_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/modules");
+    let other_config = AnalyzeConfig {
+        cache: Cache {
+            dir: Some(cache_dir.clone()),
+            mode: CacheMode::Use,
+        },
+        ..Default::default()
+    };
+    let load_other = || {
+        ProjectContent::load_from_project_dir(&other_dir, &[], &Allow::default(), &other_config)
+            .unwrap()
+    };
+    let other = load_other();
+    let [other_entry] = &entries()
+        .into_iter()
+        .filter(|v| v != entry)
+        .collect::<Vec<_>>()[..]
+    else {
+        panic!("expected two cache entries: {:?}", entries());
+    };
+    std::fs::copy(entry, other_entry).unwrap();
+    let types = |content: &ProjectContent| -> Vec<String> {
+        content.pub_types.iter().map(ToString::to_string).collect()
+    };
+    assert_ne!(types(&other), types(&content));
+    assert_eq!(types(&load_other()), types(&other));
+
+    std::fs::remove_dir_all(&cache_dir).unwrap();
+}
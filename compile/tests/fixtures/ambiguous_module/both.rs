@@ -0,0 +1,3 @@
+//! Module body as a file next to the directory.
+
+pub struct First;
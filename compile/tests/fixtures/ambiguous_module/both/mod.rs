@@ -0,0 +1,3 @@
+//! Module body inside the directory, same module as `both.rs`.
+
+pub struct Second;
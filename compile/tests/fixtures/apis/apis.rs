@@ -0,0 +1,12 @@
+pub fn read_config() -> String {
+    std::fs::read_to_string("config.txt").unwrap_or_default()
+}
+
+static mut COUNTER: u32 = 0;
+
+pub fn count() -> u32 {
+    unsafe {
+        COUNTER += 1;
+        COUNTER
+    }
+}
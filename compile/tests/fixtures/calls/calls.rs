@@ -0,0 +1,31 @@
+pub fn sum(values: &[u32]) -> u32 {
+    let mut sum = 0;
+    for v in values {
+        sum += v;
+    }
+    sum
+}
+
+pub fn even(n: u32) -> bool {
+    if n == 0 {
+        true
+    } else {
+        odd(n - 1)
+    }
+}
+
+pub fn odd(n: u32) -> bool {
+    if n == 0 {
+        false
+    } else {
+        even(n - 1)
+    }
+}
+
+pub const fn fact(n: u32) -> u32 {
+    if n == 0 {
+        1
+    } else {
+        n * fact(n - 1)
+    }
+}
@@ -0,0 +1,10 @@
+extern crate log;
+
+pub struct Hire {
+    pub date: chrono::NaiveDate,
+}
+
+/// The runtime is always allowed.
+pub fn error() -> permute::SinkError {
+    permute::SinkError::new("no hire")
+}
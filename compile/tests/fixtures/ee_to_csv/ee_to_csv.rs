@@ -0,0 +1,58 @@
+//! Native sink, as the user would write it in the project directory.
+
+pub struct Record {
+    pub id: String,
+}
+
+pub struct Ee2Csv {
+    rows: Vec<Record>,
+}
+
+impl Ee2Csv {
+    pub fn new(capacity: usize) -> Self {
+        Ee2Csv {
+            rows: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn with_rows(rows: Vec<Record>) -> Ee2Csv {
+        Ee2Csv { rows }
+    }
+
+    /// Not public, so is not a constructor for the configuration files.
+    fn empty() -> Self {
+        Self::new(0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+}
+
+impl permute::Sink<Record> for Ee2Csv {
+    type Error = ();
+
+    fn put(&mut self, value: Record) -> Result<(), Self::Error> {
+        self.rows.push(value);
+        Ok(())
+    }
+
+    fn done(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Not public, so should not be visible to the configuration files.
+struct Hidden;
+
+impl permute::Sink<Record> for Hidden {
+    type Error = ();
+
+    fn put(&mut self, _: Record) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn done(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,18 @@
+//! Native sink that accepts rows of any type.
+
+pub struct Count {
+    pub rows: usize,
+}
+
+impl<T> permute::Sink<Vec<T>> for Count {
+    type Error = ();
+
+    fn put(&mut self, value: Vec<T>) -> Result<(), Self::Error> {
+        self.rows += value.len();
+        Ok(())
+    }
+
+    fn done(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
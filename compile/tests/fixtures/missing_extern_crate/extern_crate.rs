@@ -0,0 +1,3 @@
+extern crate no_such_crate;
+
+pub struct Hire;
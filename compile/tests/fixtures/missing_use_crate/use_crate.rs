@@ -0,0 +1,5 @@
+use no_such_crate::NaiveDate;
+
+pub struct Hire {
+    pub date: NaiveDate,
+}
@@ -0,0 +1 @@
+pub struct Leaf(pub crate::first::item::Item);
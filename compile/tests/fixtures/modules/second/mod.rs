@@ -0,0 +1,3 @@
+//! Body of the `second` module.
+
+pub struct Second;
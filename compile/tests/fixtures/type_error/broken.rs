@@ -0,0 +1,4 @@
+pub fn answer() -> u32 {
+    let text: &str = "42";
+    text
+}
@@ -0,0 +1,695 @@
+//! Analyze the Rust files of a project directory and print what was found in them:
+//! public types, sinks and sources with their item types and constructors, or the
+//! errors of the analysis checks. The items generated from the YAML sinks and sources
+//! are added to the Rust files, so that the files that use them compile. The checks
+//! use the allow lists of the main file, extended with the `--allow-*` options.
+//!
+//! Exit code is 0 if the project passes the analysis, 1 if the project has errors,
+//! like invalid YAML files, loops or compilation errors, and 2 if the analysis cannot
+//! run, e.g. because of invalid arguments or a missing dependency directory.
+
+use std::{path::PathBuf, process::ExitCode};
+
+use compact_str::CompactString;
+use serde_json::{json, Value};
+
+use compile::{
+    diagnostic::Diagnostic, Allow, AnalyzeConfig, Api, CacheMode, Edition, ProjectContent,
+    ProjectContentError, SrcLoc,
+};
+use permute_front::yaml::load::{LoadError, LoadProjectDir};
+
+const USAGE: &str = "\
+Usage: permute-analyze [OPTIONS] <PROJECT_DIR>
+
+Options:
+      --main <PATH>              Main file with the allow lists [default: main.yaml]
+      --format <FORMAT>          Output format, `text` or `json` [default: text]
+      --deps-dir <DIR>           Directory with the compiled dependencies of the project
+      --extern <NAME=PATH>       Extern crate in addition to the ones of the deps dir
+      --sysroot <DIR>            Sysroot of the toolchain
+      --edition <EDITION>        Edition of the project files [default: 2021]
+      --cfg <SPEC>               Configuration flag, `name` or `name=\"value\"`
+      --allow-loops <FN>         Function that may have loops
+      --allow-recursion <FN>     Function that may be part of a recursion cycle
+      --allow-apis <ITEM>        Item or module that may use the forbidden APIs
+      --allow-crate <NAME>       External crate that the project files may use
+      --cache-dir <DIR>          Directory to cache the analysis results in
+      --no-cache                 Do not read or write the cache
+      --clear-cache              Remove the cached results before the analysis
+  -v, --verbose                  Print the log of the analysis to stderr
+  -h, --help                     Print help
+
+Options that take a list can be repeated. The `--allow-*` options add to the allow lists
+of the main file.";
+
+const EXIT_FAILED: u8 = 1;
+const EXIT_ERROR: u8 = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+}
+
+struct Args {
+    project_dir: PathBuf,
+    main: Option<PathBuf>,
+    format: Format,
+    verbose: bool,
+    allow: Allow,
+    config: AnalyzeConfig,
+}
+
+impl Args {
+    /// Parse the arguments, without the program name. None if help is requested.
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Option<Self>, String> {
+        let mut project_dir = None;
+        let mut main = None;
+        let mut format = Format::Text;
+        let mut verbose = false;
+        let mut allow = Allow::default();
+        let mut config = AnalyzeConfig::default();
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("option `{arg}` requires a value"))
+            };
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "-v" | "--verbose" => verbose = true,
+                "--main" => main = Some(value()?.into()),
+                "--format" => {
+                    format = match value()?.as_str() {
+                        "text" => Format::Text,
+                        "json" => Format::Json,
+                        other => return Err(format!("unknown format `{other}`")),
+                    }
+                }
+                "--deps-dir" => config.deps_dir = value()?.into(),
+                "--extern" => {
+                    let value = value()?;
+                    let Some((name, path)) = value.split_once('=') else {
+                        return Err(format!("extern `{value}` should be `NAME=PATH`"));
+                    };
+                    config.externs.push((name.into(), path.into()));
+                }
+                "--sysroot" => config.sysroot = Some(value()?.into()),
+                "--edition" => {
+                    config.edition = match value()?.as_str() {
+                        "2015" => Edition::Edition2015,
+                        "2018" => Edition::Edition2018,
+                        "2021" => Edition::Edition2021,
+                        other => return Err(format!("unsupported edition `{other}`")),
+                    }
+                }
+                "--cfg" => config.cfg.push(value()?.into()),
+                "--allow-loops" => allow.loops.push(value()?.into()),
+                "--allow-recursion" => allow.recursion.push(value()?.into()),
+                "--allow-apis" => allow.apis.push(value()?.into()),
+                "--allow-crate" => allow.crates.push(value()?.into()),
+                "--cache-dir" => config.cache.dir = Some(value()?.into()),
+                "--no-cache" => config.cache.mode = CacheMode::Bypass,
+                "--clear-cache" => config.cache.mode = CacheMode::Clear,
+                _ if arg.starts_with('-') => return Err(format!("unknown option `{arg}`")),
+                _ if project_dir.is_some() => {
+                    return Err(format!("unexpected argument `{arg}`"));
+                }
+                _ => project_dir = Some(PathBuf::from(arg)),
+            }
+        }
+
+        let project_dir = project_dir.ok_or("project directory is required")?;
+        Ok(Some(Args {
+            project_dir,
+            main,
+            format,
+            verbose,
+            allow,
+            config,
+        }))
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            return ExitCode::from(EXIT_ERROR);
+        }
+    };
+
+    simple_logger::SimpleLogger::new()
+        .with_colors(true)
+        .with_level(if args.verbose {
+            log::LevelFilter::Debug
+        } else {
+            log::LevelFilter::Off
+        })
+        .without_timestamps()
+        .init()
+        .unwrap();
+
+    let load = LoadProjectDir {
+        path: &args.project_dir,
+        main: args.main.as_deref(),
+        analyze: args.config,
+    };
+    let input = match load.analysis_input() {
+        Ok(input) => input,
+        Err(errors) => {
+            match args.format {
+                Format::Text => {
+                    for e in &errors {
+                        eprintln!("error: {e}");
+                    }
+                }
+                Format::Json => println!("{:#}", load_errors_json(&errors)),
+            }
+            return if errors.iter().any(is_path_error) {
+                ExitCode::from(EXIT_ERROR)
+            } else {
+                ExitCode::from(EXIT_FAILED)
+            };
+        }
+    };
+
+    let mut allow = input.allow;
+    extend_allow(&mut allow, args.allow);
+    let result =
+        ProjectContent::load_from_project_dir(load.path, &input.generated, &allow, &load.analyze);
+    match args.format {
+        Format::Text => print_text(&result),
+        Format::Json => println!("{:#}", to_json(&result)),
+    }
+
+    match &result {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) if is_project_error(e) => ExitCode::from(EXIT_FAILED),
+        Err(_) => ExitCode::from(EXIT_ERROR),
+    }
+}
+
+/// Add the allow lists of the options to the ones of the main file.
+fn extend_allow(allow: &mut Allow, options: Allow) {
+    let Allow {
+        loops,
+        recursion,
+        apis,
+        crates,
+    } = options;
+    for (list, options) in [
+        (&mut allow.loops, loops),
+        (&mut allow.recursion, recursion),
+        (&mut allow.apis, apis),
+        (&mut allow.crates, crates),
+    ] {
+        for v in options {
+            if !list.contains(&v) {
+                list.push(v);
+            }
+        }
+    }
+}
+
+/// Whether the error is found in the project files, rather than preventing the analysis.
+fn is_project_error(err: &ProjectContentError) -> bool {
+    use ProjectContentError::*;
+    match err {
+        InvalidModPath(_) | AmbiguousModFile(..) | Compile(_) | ForbiddenLoops(_)
+        | Recursions(_) | ForbiddenApis(_) | UnlistedCrates(_) => true,
+        Io(_)
+        | DepsDir { .. }
+        | ExternNotFound { .. }
+        | NoRuntime(_)
+        | SysrootNotFound(_)
+        | InvalidCfg(_)
+        | UnknownCrate(_) => false,
+    }
+}
+
+/// Whether the project directory is missing, so that no files can be loaded.
+fn is_path_error(err: &LoadError) -> bool {
+    matches!(
+        err,
+        LoadError::PathDoesNotExist(_) | LoadError::PathIsNotDir(_)
+    )
+}
+
+fn print_text(result: &Result<ProjectContent, ProjectContentError>) {
+    let content = match result {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return;
+        }
+    };
+
+    println!("Public types:");
+    for ty in &content.pub_types {
+        println!("  {ty}");
+    }
+
+    println!("Sinks:");
+    for &id in &content.sinks {
+        println!("  {}", content.pub_types[id as usize]);
+        for ty in content.sink_tys(id) {
+            println!("    accepts: {}", ty.ty);
+        }
+        print_constructors(content, id);
+    }
+
+    println!("Sources:");
+    for &id in &content.sources {
+        println!("  {}", content.pub_types[id as usize]);
+        if let Some(ty) = content.source_ty(id) {
+            println!("    produces: {}", ty.ty);
+        }
+        print_constructors(content, id);
+    }
+}
+
+fn print_constructors(content: &ProjectContent, id: compile::ItemId) {
+    for ctor in content.constructors(id) {
+        let params: Vec<_> = ctor
+            .params
+            .iter()
+            .map(|v| format!("{}: {}", v.name, v.ty))
+            .collect();
+        println!("    constructor: {}({})", ctor.name, params.join(", "));
+    }
+}
+
+fn to_json(result: &Result<ProjectContent, ProjectContentError>) -> Value {
+    let content = match result {
+        Ok(content) => content,
+        Err(e) => {
+            return json!({
+                "ok": false,
+                "error": {
+                    "kind": error_kind(e),
+                    "message": e.to_string(),
+                    "findings": findings_json(e),
+                },
+            })
+        }
+    };
+
+    let constructors = |id| -> Value {
+        content
+            .constructors(id)
+            .map(|ctor| {
+                let params: Vec<_> = ctor
+                    .params
+                    .iter()
+                    .map(|v| json!({ "name": v.name.as_str(), "ty": v.ty.as_str() }))
+                    .collect();
+                json!({ "name": ctor.name.as_str(), "params": params })
+            })
+            .collect()
+    };
+    let sinks: Vec<_> = content
+        .sinks
+        .iter()
+        .map(|&id| {
+            json!({
+                "path": content.pub_types[id as usize].to_string(),
+                "accepts": content.sink_tys(id).map(|v| v.ty.as_str()).collect::<Vec<_>>(),
+                "constructors": constructors(id),
+            })
+        })
+        .collect();
+    let sources: Vec<_> = content
+        .sources
+        .iter()
+        .map(|&id| {
+            json!({
+                "path": content.pub_types[id as usize].to_string(),
+                "produces": content.source_ty(id).map(|v| v.ty.as_str()),
+                "constructors": constructors(id),
+            })
+        })
+        .collect();
+    let pub_types: Vec<_> = content.pub_types.iter().map(ToString::to_string).collect();
+
+    json!({
+        "ok": true,
+        "pub_types": pub_types,
+        "sinks": sinks,
+        "sources": sources,
+    })
+}
+
+fn error_kind(err: &ProjectContentError) -> &'static str {
+    use ProjectContentError::*;
+    match err {
+        Io(_) => "io",
+        DepsDir { .. } => "deps_dir",
+        ExternNotFound { .. } => "extern_not_found",
+        NoRuntime(_) => "no_runtime",
+        SysrootNotFound(_) => "sysroot_not_found",
+        InvalidCfg(_) => "invalid_cfg",
+        UnknownCrate(_) => "unknown_crate",
+        InvalidModPath(_) => "invalid_mod_path",
+        AmbiguousModFile(..) => "ambiguous_mod_file",
+        Compile(_) => "compile",
+        ForbiddenLoops(_) => "forbidden_loops",
+        Recursions(_) => "recursions",
+        ForbiddenApis(_) => "forbidden_apis",
+        UnlistedCrates(_) => "unlisted_crates",
+    }
+}
+
+/// Errors found in the project files, each with its location.
+fn findings_json(err: &ProjectContentError) -> Value {
+    use ProjectContentError::*;
+    match err {
+        Compile(diagnostics) => diagnostics.iter().map(diagnostic_json).collect(),
+        ForbiddenLoops(loops) => loops
+            .iter()
+            .map(|v| json!({ "item": v.item, "loc": loc_json(&v.loc) }))
+            .collect(),
+        Recursions(recursions) => recursions
+            .iter()
+            .map(|r| {
+                let cycle: Vec<_> = r
+                    .cycle
+                    .iter()
+                    .map(|edge| {
+                        json!({
+                            "caller": edge.caller,
+                            "callee": edge.callee,
+                            "loc": loc_json(&edge.loc),
+                        })
+                    })
+                    .collect();
+                json!({ "cycle": cycle })
+            })
+            .collect(),
+        ForbiddenApis(found) => found
+            .iter()
+            .map(|v| {
+                let api = match &v.api {
+                    Api::Path(path) => path.as_str(),
+                    Api::UnsafeBlock => "unsafe",
+                    Api::StaticMut => "static mut",
+                };
+                json!({ "item": v.item, "api": api, "loc": loc_json(&v.loc) })
+            })
+            .collect(),
+        UnlistedCrates(found) => found
+            .iter()
+            .map(|v| json!({ "item": v.item, "crate": v.krate.as_str(), "loc": loc_json(&v.loc) }))
+            .collect(),
+        _ => json!([]),
+    }
+}
+
+fn diagnostic_json(diagnostic: &Diagnostic) -> Value {
+    let suggestions: Vec<_> = diagnostic
+        .suggestions
+        .iter()
+        .map(|v| {
+            let parts: Vec<_> = v
+                .parts
+                .iter()
+                .map(|part| {
+                    json!({
+                        "loc": loc_json(&part.loc),
+                        "end": loc_json(&part.end),
+                        "snippet": part.snippet,
+                    })
+                })
+                .collect();
+            json!({ "message": v.message, "parts": parts })
+        })
+        .collect();
+    json!({
+        "level": diagnostic.level.to_string(),
+        "code": diagnostic.code.as_ref().map(CompactString::as_str),
+        "message": diagnostic.message,
+        "loc": diagnostic.loc.as_ref().map(loc_json),
+        "children": diagnostic.children.iter().map(diagnostic_json).collect::<Vec<_>>(),
+        "suggestions": suggestions,
+    })
+}
+
+/// Errors of the YAML files, which prevent generating the items for the analysis,
+/// or the error of the missing project directory.
+fn load_errors_json(errors: &[LoadError]) -> Value {
+    let findings: Vec<_> = errors
+        .iter()
+        .map(|e| {
+            let (line, col) = e.position().unzip();
+            json!({
+                "message": e.to_string(),
+                "file": e.file().map(|v| v.display().to_string()),
+                "line": line,
+                "col": col,
+            })
+        })
+        .collect();
+    json!({
+        "ok": false,
+        "error": {
+            "kind": if errors.iter().any(is_path_error) { "project_dir" } else { "yaml" },
+            "message": "YAML files of the project failed to load",
+            "findings": findings,
+        },
+    })
+}
+
+fn loc_json(loc: &SrcLoc) -> Value {
+    json!({ "file": loc.file.as_str(), "line": loc.line, "col": loc.col })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use compile::{diagnostic::Level, Constructor, CtorParam, ItemPath, ItemTy};
+
+    fn parse(args: &[&str]) -> Result<Option<Args>, String> {
+        Args::parse(args.iter().map(|v| v.to_string()))
+    }
+
+    fn loc(line: usize) -> SrcLoc {
+        SrcLoc {
+            file: "ee_to_csv.rs".into(),
+            line,
+            col: 5,
+        }
+    }
+
+    #[test]
+    fn parse_args() {
+        let args = parse(&[
+            "--main",
+            "daily.yaml",
+            "--format",
+            "json",
+            "--deps-dir",
+            "deps",
+            "--extern",
+            "chrono=deps/libchrono.rlib",
+            "--edition",
+            "2018",
+            "--cfg",
+            "test",
+            "--allow-loops",
+            "a::f",
+            "--allow-loops",
+            "a::g",
+            "--allow-crate",
+            "chrono",
+            "--no-cache",
+            "-v",
+            "project",
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!(args.project_dir, PathBuf::from("project"));
+        assert_eq!(args.main, Some(PathBuf::from("daily.yaml")));
+        assert!(args.format == Format::Json);
+        assert!(args.verbose);
+        assert_eq!(args.config.deps_dir, PathBuf::from("deps"));
+        assert_eq!(
+            args.config.externs,
+            [("chrono".into(), PathBuf::from("deps/libchrono.rlib"))]
+        );
+        assert_eq!(args.config.edition, Edition::Edition2018);
+        assert_eq!(args.config.cfg, ["test"]);
+        assert_eq!(args.config.cache.mode, CacheMode::Bypass);
+        assert_eq!(args.allow.loops, ["a::f", "a::g"]);
+        assert_eq!(args.allow.crates, ["chrono"]);
+
+        let args = parse(&["project"]).unwrap().unwrap();
+        assert_eq!(args.main, None);
+        assert!(args.format == Format::Text);
+        assert!(!args.verbose);
+        assert_eq!(args.config.cache.mode, CacheMode::Use);
+
+        assert!(parse(&["project", "--help"]).unwrap().is_none());
+    }
+
+    #[test]
+    fn parse_args_errors() {
+        let err = |args: &[&str]| parse(args).err().unwrap();
+        assert_eq!(err(&[]), "project directory is required");
+        assert_eq!(err(&["a", "b"]), "unexpected argument `b`");
+        assert_eq!(
+            err(&["a", "--format"]),
+            "option `--format` requires a value"
+        );
+        assert_eq!(err(&["a", "--format", "xml"]), "unknown format `xml`");
+        assert_eq!(
+            err(&["a", "--edition", "2024"]),
+            "unsupported edition `2024`"
+        );
+        assert_eq!(
+            err(&["a", "--extern", "chrono"]),
+            "extern `chrono` should be `NAME=PATH`"
+        );
+        assert_eq!(err(&["a", "--quiet"]), "unknown option `--quiet`");
+    }
+
+    #[test]
+    fn allow_of_main_file() {
+        let mut allow = Allow {
+            apis: vec!["csv_sink".into()],
+            crates: vec!["chrono".into()],
+            ..Default::default()
+        };
+        let options = Allow {
+            loops: vec!["a::f".into()],
+            crates: vec!["chrono".into(), "log".into()],
+            ..Default::default()
+        };
+        extend_allow(&mut allow, options);
+        assert_eq!(allow.loops, ["a::f"]);
+        assert!(allow.recursion.is_empty());
+        assert_eq!(allow.apis, ["csv_sink"]);
+        assert_eq!(allow.crates, ["chrono", "log"]);
+    }
+
+    #[test]
+    fn project_errors() {
+        use ProjectContentError::*;
+
+        // Exit with EXIT_FAILED.
+        assert!(is_project_error(&Compile(vec![])));
+        assert!(is_project_error(&ForbiddenLoops(vec![])));
+        assert!(is_project_error(&InvalidModPath("a-b.rs".into())));
+        assert!(is_project_error(&AmbiguousModFile(
+            "a.rs".into(),
+            "a/mod.rs".into()
+        )));
+
+        // Exit with EXIT_ERROR.
+        assert!(!is_project_error(&Io(std::io::ErrorKind::NotFound.into())));
+        assert!(!is_project_error(&NoRuntime("deps".into())));
+        assert!(!is_project_error(&UnknownCrate("chrono".into())));
+        assert!(!is_project_error(&InvalidCfg("a=b".into())));
+
+        assert!(is_path_error(&LoadError::PathDoesNotExist(
+            "project".into()
+        )));
+        assert!(!is_path_error(&LoadError::InvalidItemPath(
+            "a-b.yaml".into()
+        )));
+    }
+
+    #[test]
+    fn content_json() {
+        let path = |v: &str| ItemPath {
+            segments: v.split("::").map(Into::into).collect(),
+        };
+        let content = ProjectContent {
+            pub_types: vec![path("ee_to_csv::Ee2Csv"), path("ee_to_csv::Record")],
+            sinks: vec![0],
+            sources: vec![],
+            sink_tys: vec![ItemTy {
+                item: 0,
+                ty: "EmploymentRecord::EmploymentRecordRow".into(),
+                params: vec![],
+            }],
+            source_tys: vec![],
+            constructors: vec![Constructor {
+                item: 0,
+                name: "new".into(),
+                params: vec![CtorParam {
+                    name: "csv".into(),
+                    ty: "Csv::Csv".into(),
+                }],
+            }],
+        };
+        assert_eq!(
+            to_json(&Ok(content)),
+            json!({
+                "ok": true,
+                "pub_types": ["ee_to_csv::Ee2Csv", "ee_to_csv::Record"],
+                "sinks": [{
+                    "path": "ee_to_csv::Ee2Csv",
+                    "accepts": ["EmploymentRecord::EmploymentRecordRow"],
+                    "constructors": [{
+                        "name": "new",
+                        "params": [{ "name": "csv", "ty": "Csv::Csv" }],
+                    }],
+                }],
+                "sources": [],
+            })
+        );
+    }
+
+    #[test]
+    fn error_json() {
+        let diagnostic = Diagnostic {
+            level: Level::Error,
+            code: Some("E0308".into()),
+            message: "mismatched types".into(),
+            loc: Some(loc(3)),
+            children: vec![],
+            suggestions: vec![],
+        };
+        let json = to_json(&Err(ProjectContentError::Compile(vec![diagnostic])));
+        assert_eq!(json["ok"], false);
+        assert_eq!(json["error"]["kind"], "compile");
+        assert_eq!(
+            json["error"]["findings"],
+            json!([{
+                "level": "error",
+                "code": "E0308",
+                "message": "mismatched types",
+                "loc": { "file": "ee_to_csv.rs", "line": 3, "col": 5 },
+                "children": [],
+                "suggestions": [],
+            }])
+        );
+
+        let found = compile::ForbiddenApi {
+            item: "ee_to_csv::write".into(),
+            api: Api::UnsafeBlock,
+            loc: loc(7),
+        };
+        let json = to_json(&Err(ProjectContentError::ForbiddenApis(vec![found])));
+        assert_eq!(json["error"]["kind"], "forbidden_apis");
+        assert_eq!(
+            json["error"]["findings"],
+            json!([{
+                "item": "ee_to_csv::write",
+                "api": "unsafe",
+                "loc": { "file": "ee_to_csv.rs", "line": 7, "col": 5 },
+            }])
+        );
+
+        let json = to_json(&Err(ProjectContentError::NoRuntime("deps".into())));
+        assert_eq!(json["error"]["kind"], "no_runtime");
+        assert_eq!(json["error"]["findings"], json!([]));
+    }
+}
@@ -0,0 +1,144 @@
+//! Migrate YAML files of projects to a newer version of the format, rewriting them in
+//! place. Directories are searched for YAML files recursively.
+//!
+//! Exit code is 0 if all files are migrated, 1 if some files need changes that could not
+//! be made automatically, or are outdated in the check mode, and 2 on errors.
+
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use permute_front::yaml::{migrate, Version};
+
+const USAGE: &str = "\
+Usage: permute-migrate [OPTIONS] <PATH>...
+
+Options:
+      --to <VERSION>    Version to migrate to [default: latest]
+      --check           Report the outdated files without rewriting them
+  -h, --help            Print help";
+
+const EXIT_FAILED: u8 = 1;
+const EXIT_ERROR: u8 = 2;
+
+struct Args {
+    paths: Vec<PathBuf>,
+    to: Version,
+    check: bool,
+}
+
+impl Args {
+    /// Parse the arguments, without the program name. None if help is requested.
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Option<Self>, String> {
+        let mut paths = Vec::new();
+        let mut to = Version::LATEST;
+        let mut check = false;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "--check" => check = true,
+                "--to" => {
+                    let value = args.next().ok_or("option `--to` requires a value")?;
+                    to = Version::parse(&value).map_err(|e| e.to_string())?;
+                }
+                _ if arg.starts_with('-') => return Err(format!("unknown option `{arg}`")),
+                _ => paths.push(PathBuf::from(arg)),
+            }
+        }
+
+        if paths.is_empty() {
+            return Err("at least one path is required".into());
+        }
+        Ok(Some(Args { paths, to, check }))
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            return ExitCode::from(EXIT_ERROR);
+        }
+    };
+
+    let mut files = Vec::new();
+    for path in &args.paths {
+        if let Err(e) = collect_yaml_files(path, &mut files) {
+            eprintln!("error: {}: {e}", path.display());
+            return ExitCode::from(EXIT_ERROR);
+        }
+    }
+
+    let mut failed = false;
+    let mut has_errors = false;
+    for file in &files {
+        let migrated = match migrate::migrate_file(file, args.to, !args.check) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("error: {}: {e}", file.display());
+                has_errors = true;
+                continue;
+            }
+        };
+
+        if !migrated.is_changed() {
+            println!("{}: up to date ({})", file.display(), migrated.from);
+            continue;
+        }
+        if args.check {
+            println!(
+                "{}: outdated ({} -> {})",
+                file.display(),
+                migrated.from,
+                migrated.to
+            );
+            failed = true;
+        } else {
+            println!(
+                "{}: migrated ({} -> {})",
+                file.display(),
+                migrated.from,
+                migrated.to
+            );
+        }
+        for change in &migrated.manual {
+            println!("  needs manual change: {change}");
+            failed = true;
+        }
+    }
+
+    if has_errors {
+        ExitCode::from(EXIT_ERROR)
+    } else if failed {
+        ExitCode::from(EXIT_FAILED)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn collect_yaml_files(path: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_owned());
+        return Ok(());
+    }
+
+    let mut entries = std::fs::read_dir(path)?
+        .map(|v| v.map(|v| v.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_unstable();
+    for entry in entries {
+        if entry.is_dir() {
+            collect_yaml_files(&entry, files)?;
+        } else if entry.extension() == Some("yaml".as_ref()) {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
@@ -1,7 +1,8 @@
 use crate::yaml::hir;
-use compact_str::{CompactString, ToCompactString};
+use compact_str::{format_compact, CompactString, ToCompactString};
 use hashbrown::HashMap;
 use log::*;
+use quote::ToTokens;
 use smallvec::SmallVec;
 
 type IdentId = usize;
@@ -10,7 +11,11 @@ type BindingId = IdentId;
 /// Code generation for the [Ctx](crate::context::Ctx).
 pub mod codegen;
 
+/// Validation of the values set on the bindings.
+pub mod validate;
+
 /// Context for the project.
+#[derive(Clone)]
 pub struct Ctx {
     /// The name of the project. Cannot be empty.
     name: CompactString,
@@ -27,23 +32,30 @@ pub struct Ctx {
     srcs_bindings: Vec<Binding>,
     sinks_bindings: Vec<Binding>,
 
-    /// Parameter values for sink bindings. Key is a tuple of sink identifier and parameter name.
+    /// Parameter values for sink bindings. Key is a tuple of sink binding identifier
+    /// and parameter name.
     /// It also stores initialization expressions for native sinks, in this case
     /// [ParamKey] part of the key is blank.
-    sink_params: HashMap<(IdentId, ParamKey), syn::Expr>,
+    sink_params: HashMap<(BindingId, ParamKey), syn::Expr>,
 
-    /// Filter values for source bindings. Key is a tuple of source identifier and parameter name.
+    /// Filter values for source bindings. Key is a tuple of source binding identifier
+    /// and parameter name.
     /// It also stores initialization expressions for native sources, in this case
     /// [ParamKey] part of the key is blank.
-    src_filters: HashMap<(IdentId, ParamKey), syn::Expr>,
+    src_filters: HashMap<(BindingId, ParamKey), syn::Expr>,
 
     /// Pipes that connect sources to sinks.
     /// Each pipe here is a tuple of source and sink indexes,
     /// in [Self::srcs_bindings] and [Self::sinks_bindings].
     pipes: Vec<(BindingId, BindingId)>,
+
+    /// What to do with the source rows that fail the column checks, by source binding.
+    /// Reject policy holds the sink binding index. Sources not listed abort the pipe.
+    check_policies: HashMap<BindingId, hir::CheckPolicy<BindingId>>,
 }
 
 /// Bindings are used to configure individual instances of sources and sinks.
+#[derive(Clone)]
 pub struct Binding {
     /// Name of the binding identified. Cannot be empty.
     name: CompactString,
@@ -52,6 +64,38 @@ pub struct Binding {
     target: IdentId,
 }
 
+impl Binding {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+fn map_entries<'a>(
+    values: &'a HashMap<(BindingId, ParamKey), syn::Expr>,
+    binding: BindingId,
+    name: &str,
+) -> Vec<(&'a str, &'a syn::Expr)> {
+    let mut entries: Vec<_> = values
+        .iter()
+        .filter(|((id, key), _)| *id == binding && key.len() == 2)
+        .filter_map(|((_, key), value)| {
+            let mut key = key.iter();
+            (key.next() == Some(name)).then(|| (key.next().unwrap(), value))
+        })
+        .collect();
+    entries.sort_unstable_by_key(|(key, _)| *key);
+    entries
+}
+
+/// Whether the tokens name the binding, at any depth of the groups.
+fn references(tokens: proc_macro2::TokenStream, name: &str) -> bool {
+    tokens.into_iter().any(|tt| match tt {
+        proc_macro2::TokenTree::Ident(ident) => ident == name,
+        proc_macro2::TokenTree::Group(group) => references(group.stream(), name),
+        _ => false,
+    })
+}
+
 #[derive(Debug, thiserror::Error)]
 #[error("Project name cannot be empty")]
 pub struct EmptyNameError;
@@ -74,7 +118,7 @@ pub enum AddInitErr {
     DestNotFound(CompactString),
 
     #[error("Item {0} already initialized with `{1:?}`")]
-    AlreadySet(ParamKey, syn::Expr),
+    AlreadySet(CompactString, syn::Expr),
 
     #[error("Expected native item `{0}` to attach initializer, but it instead has YAML origin.")]
     ExpectNative(CompactString),
@@ -115,9 +159,26 @@ impl Ctx {
             sink_params: HashMap::new(),
             src_filters: HashMap::new(),
             pipes: Vec::new(),
+            check_policies: HashMap::new(),
         })
     }
 
+    /// Set the name and the comment of the context, e.g. of a copy of the one with the
+    /// items shared by several main files.
+    pub fn rename(
+        &mut self,
+        name: CompactString,
+        explain: Option<CompactString>,
+    ) -> Result<(), EmptyNameError> {
+        if name.is_empty() {
+            return Err(EmptyNameError);
+        }
+
+        self.name = name;
+        self.explain = explain.unwrap_or_default();
+        Ok(())
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -139,25 +200,29 @@ impl Ctx {
     }
 
     fn sink_id(&self, sink: &str) -> Option<IdentId> {
-        self.sinks.iter().position(|s| s.name() == sink).map(|v| {
+        let key = self.item_ty(sink, &[]).to_compact_string();
+        self.sinks.iter().position(|s| s.name() == key).map(|v| {
             trace!("Found sink with name `{sink}` at index {v}");
             v
         })
     }
 
-    fn sink_with_id(&self, sink: &str) -> Option<(&Sink, IdentId)> {
-        self.sink_id(sink).map(|v| (&self.sinks[v], v))
+    /// Find the sink by name. Name may be spelled as any path to the same item.
+    pub fn sink(&self, sink: &str) -> Option<&Sink> {
+        self.sink_id(sink).map(|v| &self.sinks[v])
     }
 
     fn source_id(&self, src: &str) -> Option<IdentId> {
-        self.srcs.iter().position(|s| s.name() == src).map(|v| {
+        let key = self.item_ty(src, &[]).to_compact_string();
+        self.srcs.iter().position(|s| s.name() == key).map(|v| {
             trace!("Found source with name `{src}` at index {v}");
             v
         })
     }
 
-    fn source_with_id(&self, src: &str) -> Option<(&DataSource, IdentId)> {
-        self.source_id(src).map(|v| (&self.srcs[v], v))
+    /// Find the source by name. Name may be spelled as any path to the same item.
+    pub fn source(&self, src: &str) -> Option<&DataSource> {
+        self.source_id(src).map(|v| &self.srcs[v])
     }
 
     pub fn add_source(&mut self, src: impl Into<DataSource>) -> Result<(), AddSourceErr> {
@@ -188,35 +253,181 @@ impl Ctx {
         Ok(())
     }
 
+    /// Add a pipe from the source binding to the sink binding.
     pub fn add_pipe(&mut self, src: &str, sink: &str) -> Result<(), AddPipeErr> {
-        let src_idx = self
-            .source_id(src)
-            .ok_or_else(|| AddPipeErr::SourceNotFound(src.into()))?;
-        let sink_idx = self
-            .sink_id(sink)
-            .ok_or_else(|| AddPipeErr::SinkNotFound(sink.into()))?;
+        let src_idx = match self.source_binding_id(src) {
+            Some(idx) => idx,
+            None if self.sink_binding_id(src).is_some() => {
+                return Err(AddPipeErr::SinkAsSource(src.into()))
+            }
+            None => return Err(AddPipeErr::BindingNotFound(src.into())),
+        };
+        let sink_idx = match self.sink_binding_id(sink) {
+            Some(idx) => idx,
+            None if self.source_binding_id(sink).is_some() => {
+                return Err(AddPipeErr::SourceAsSink(sink.into()))
+            }
+            None => return Err(AddPipeErr::BindingNotFound(sink.into())),
+        };
+
+        let src_item = &self.srcs[self.srcs_bindings[src_idx].target];
+        let sink_item = &self.sinks[self.sinks_bindings[sink_idx].target];
+        let Some(item_ty) = src_item.item_ty() else {
+            return Err(AddPipeErr::UnknownItemTy(src.into()));
+        };
+        if !sink_item.accepts(item_ty) {
+            return Err(AddPipeErr::TypeMismatch {
+                src: src.into(),
+                item_ty: item_ty.to_compact_string(),
+                sink: sink.into(),
+                accepts: sink_item
+                    .item_tys
+                    .iter()
+                    .map(|v| v.to_compact_string())
+                    .collect(),
+            });
+        }
 
         debug!("Add pipe from `{src}` to `{sink}`");
         self.pipes.push((src_idx, sink_idx));
         Ok(())
     }
 
-    pub fn pipes(&self) -> impl Iterator<Item = (&DataSource, &Sink)> {
+    /// Set what to do with the rows of the YAML source binding that fail the column checks.
+    /// Rejected rows go to the sink binding, which should accept the source items.
+    /// The source takes the reject sink over, so the policy goes after the pipes and
+    /// the initializers, which may not use that sink.
+    pub fn set_check_policy(
+        &mut self,
+        src: &str,
+        policy: hir::CheckPolicy<&str>,
+    ) -> Result<(), CheckPolicyErr> {
+        let src_idx = match self.source_binding_id(src) {
+            Some(idx) => idx,
+            None if self.sink_binding_id(src).is_some() => {
+                return Err(CheckPolicyErr::NotSource(src.into()))
+            }
+            None => return Err(CheckPolicyErr::BindingNotFound(src.into())),
+        };
+        let src_item = &self.srcs[self.srcs_bindings[src_idx].target];
+        if src_item.is_native() {
+            return Err(CheckPolicyErr::NativeSource(src.into()));
+        }
+
+        let policy = match policy {
+            hir::CheckPolicy::Abort => hir::CheckPolicy::Abort,
+            hir::CheckPolicy::Skip => hir::CheckPolicy::Skip,
+            hir::CheckPolicy::Reject(sink) => {
+                let sink_idx = match self.sink_binding_id(sink) {
+                    Some(idx) => idx,
+                    None if self.source_binding_id(sink).is_some() => {
+                        return Err(CheckPolicyErr::NotSink(sink.into()))
+                    }
+                    None => return Err(CheckPolicyErr::BindingNotFound(sink.into())),
+                };
+                let sink_item = &self.sinks[self.sinks_bindings[sink_idx].target];
+                let item_ty = src_item.item_ty().expect("YAML sources produce their rows");
+                if !sink_item.accepts(item_ty) {
+                    return Err(CheckPolicyErr::TypeMismatch {
+                        item_ty: item_ty.to_compact_string(),
+                        sink: sink.into(),
+                    });
+                }
+                if self.is_sink_in_use(sink_idx, src_idx) {
+                    return Err(CheckPolicyErr::SinkInUse(sink.into()));
+                }
+                hir::CheckPolicy::Reject(sink_idx)
+            }
+        };
+
+        debug!("Set check policy of `{src}` to {policy:?}");
+        self.check_policies.insert(src_idx, policy);
+        Ok(())
+    }
+
+    /// Whether the sink binding is a pipe output, a reject sink of a source other than
+    /// the given one, or is named in a value of any binding, e.g. a native initializer.
+    fn is_sink_in_use(&self, sink: BindingId, src: BindingId) -> bool {
+        let name = self.sinks_bindings[sink].name();
+        self.pipes.iter().any(|(_, v)| *v == sink)
+            || self
+                .check_policies
+                .iter()
+                .any(|(k, v)| *k != src && matches!(v, hir::CheckPolicy::Reject(v) if *v == sink))
+            || self
+                .sink_params
+                .values()
+                .chain(self.src_filters.values())
+                .any(|v| references(v.to_token_stream(), name))
+    }
+
+    /// Check policies of the source bindings. Reject policy holds the sink binding.
+    pub fn check_policies(&self) -> impl Iterator<Item = (&Binding, hir::CheckPolicy<&Binding>)> {
+        self.check_policies.iter().map(|(src, policy)| {
+            let policy = policy.map(|sink| &self.sinks_bindings[sink]);
+            (&self.srcs_bindings[*src], policy)
+        })
+    }
+
+    /// Pipes of the project, as pairs of source and sink bindings.
+    pub fn pipes(&self) -> impl Iterator<Item = (&Binding, &Binding)> {
         self.pipes
             .iter()
             .copied()
-            .map(|(src, sink)| (&self.srcs[src], &self.sinks[sink]))
+            .map(|(src, sink)| (&self.srcs_bindings[src], &self.sinks_bindings[sink]))
     }
 
-    /// Get the source binding by name. Return error if binding is native.
-    pub fn yaml_source_binding(&self, name: &str) -> Result<Option<IdentId>, UnexpectedNative> {
-        let binding = self
-            .srcs_bindings
+    /// Source bindings, each with the source it configures.
+    pub fn source_bindings(&self) -> impl Iterator<Item = (BindingId, &Binding, &DataSource)> {
+        self.srcs_bindings
             .iter()
-            .find(|b| b.name == name)
-            .map(|v| v.target);
+            .enumerate()
+            .map(|(id, b)| (id, b, &self.srcs[b.target]))
+    }
+
+    /// Sink bindings, each with the sink it configures.
+    pub fn sink_bindings(&self) -> impl Iterator<Item = (BindingId, &Binding, &Sink)> {
+        self.sinks_bindings
+            .iter()
+            .enumerate()
+            .map(|(id, b)| (id, b, &self.sinks[b.target]))
+    }
+
+    /// Value of the filter set for the source binding in the main file.
+    /// Blank key returns the initializer of the native source.
+    pub fn src_filter(&self, binding: BindingId, key: &ParamKey) -> Option<&syn::Expr> {
+        self.src_filters.get(&(binding, key.to_owned()))
+    }
+
+    /// Value of the parameter set for the sink binding in the main file.
+    /// Blank key returns the initializer of the native sink.
+    pub fn sink_param(&self, binding: BindingId, key: &ParamKey) -> Option<&syn::Expr> {
+        self.sink_params.get(&(binding, key.to_owned()))
+    }
+
+    /// Entries of the map filter set by nested keys in the main file, ordered by key.
+    pub fn src_filter_entries(&self, binding: BindingId, name: &str) -> Vec<(&str, &syn::Expr)> {
+        map_entries(&self.src_filters, binding, name)
+    }
+
+    /// Entries of the map parameter set by nested keys in the main file, ordered by key.
+    pub fn sink_param_entries(&self, binding: BindingId, name: &str) -> Vec<(&str, &syn::Expr)> {
+        map_entries(&self.sink_params, binding, name)
+    }
+
+    fn source_binding_id(&self, name: &str) -> Option<BindingId> {
+        self.srcs_bindings.iter().position(|b| b.name == name)
+    }
+
+    fn sink_binding_id(&self, name: &str) -> Option<BindingId> {
+        self.sinks_bindings.iter().position(|b| b.name == name)
+    }
+
+    /// Get the source binding by name. Return error if binding is native.
+    pub fn yaml_source_binding(&self, name: &str) -> Result<Option<BindingId>, UnexpectedNative> {
+        let binding = self.source_binding_id(name);
         if let Some(binding) = binding {
-            if self.srcs[binding].is_native() {
+            if self.srcs[self.srcs_bindings[binding].target].is_native() {
                 return Err(UnexpectedNative(name.to_compact_string()));
             }
         }
@@ -224,14 +435,10 @@ impl Ctx {
     }
 
     /// Get the sink binding by name. Return error if binding is native.
-    pub fn yaml_sink_binding(&self, name: &str) -> Result<Option<IdentId>, UnexpectedNative> {
-        let binding = self
-            .sinks_bindings
-            .iter()
-            .find(|b| b.name == name)
-            .map(|v| v.target);
+    pub fn yaml_sink_binding(&self, name: &str) -> Result<Option<BindingId>, UnexpectedNative> {
+        let binding = self.sink_binding_id(name);
         if let Some(binding) = binding {
-            if self.sinks[binding].is_native() {
+            if self.sinks[self.sinks_bindings[binding].target].is_native() {
                 return Err(UnexpectedNative(name.to_compact_string()));
             }
         }
@@ -248,7 +455,7 @@ impl Ctx {
             .ok_or_else(|| AddBindingErr::NotFound(src.into()))?;
 
         // Check if the binding with the same name already exists.
-        if self.yaml_source_binding(&name)?.is_some() {
+        if self.source_binding_id(&name).is_some() || self.sink_binding_id(&name).is_some() {
             return Err(AddBindingErr::NameExists(name));
         }
 
@@ -271,7 +478,7 @@ impl Ctx {
             .ok_or_else(|| AddBindingErr::NotFound(sink.into()))?;
 
         // Check if the binding with the same name already exists.
-        if self.yaml_sink_binding(&name)?.is_some() {
+        if self.source_binding_id(&name).is_some() || self.sink_binding_id(&name).is_some() {
             return Err(AddBindingErr::NameExists(name));
         }
 
@@ -305,6 +512,8 @@ impl Ctx {
             explain: Default::default(),
             params: HashMap::new(),
             checks: Vec::new(),
+            item_tys: Vec::new(),
+            constructors: Vec::new(),
             uses: Vec::new(),
         })
     }
@@ -319,10 +528,115 @@ impl Ctx {
             columns: Vec::new(),
             filter_checks: Vec::new(),
             column_checks: Vec::new(),
+            item_ty: None,
+            constructors: Vec::new(),
             uses: Vec::new(),
         })
     }
 
+    /// Register type `T` for which the sink implements `Sink<T>`. Type parameters
+    /// of the impl that `T` names match any type.
+    pub fn add_sink_item_ty(
+        &mut self,
+        sink: &str,
+        ty: &str,
+        params: &[CompactString],
+    ) -> Result<(), ItemNotFound> {
+        let idx = self
+            .sink_id(sink)
+            .ok_or_else(|| ItemNotFound(sink.into()))?;
+
+        debug!("Sink `{sink}` accepts `{ty}`");
+        let ty = self.item_ty(ty, params);
+        self.sinks[idx].item_tys.push(ty);
+        Ok(())
+    }
+
+    /// Set the `Source::Item` type of the source. Type parameters of the impl that
+    /// the type names match any type.
+    pub fn set_source_item_ty(
+        &mut self,
+        src: &str,
+        ty: &str,
+        params: &[CompactString],
+    ) -> Result<(), ItemNotFound> {
+        let idx = self
+            .source_id(src)
+            .ok_or_else(|| ItemNotFound(src.into()))?;
+
+        debug!("Source `{src}` produces `{ty}`");
+        self.srcs[idx].item_ty = Some(self.item_ty(ty, params));
+        Ok(())
+    }
+
+    /// Item type of a sink or a source as the Rust code spells it, with the paths
+    /// normalized so that different spellings of the same type compare equal.
+    /// Leading `crate::` is removed, and items of the generated YAML modules are
+    /// named directly, e.g. `_Csv::Csv` becomes `Csv` and
+    /// `Vec<_EmploymentRecord::EmploymentRecordRow>` becomes `Vec<EmploymentRecordRow>`.
+    fn item_ty(&self, ty: &str, params: &[CompactString]) -> ItemTy {
+        struct Normalize<'a>(&'a Ctx);
+
+        impl syn::visit_mut::VisitMut for Normalize<'_> {
+            fn visit_path_mut(&mut self, path: &mut syn::Path) {
+                let segments = std::mem::take(&mut path.segments);
+                let len = segments.len();
+                let mut module = CompactString::default();
+                for (i, segment) in segments.into_iter().enumerate() {
+                    let ident = segment.ident.to_compact_string();
+                    if i == 0 && ident == "crate" && len > 1 {
+                        path.leading_colon = None;
+                        continue;
+                    }
+                    // Module `_Name` generated for the YAML item `Name` of the same module.
+                    let item = ident.strip_prefix('_').map(|item| match module.as_str() {
+                        "" => item.to_compact_string(),
+                        module => format_compact!("{module}::{item}"),
+                    });
+                    if item.is_some_and(|item| self.0.is_yaml_item(&item)) {
+                        continue;
+                    }
+                    if !module.is_empty() {
+                        module.push_str("::");
+                    }
+                    module.push_str(&ident);
+                    path.segments.push(segment);
+                }
+                syn::visit_mut::visit_path_mut(self, path);
+            }
+        }
+
+        let mut ty = ItemTy::parse(ty, params);
+        if let Some(parsed) = &mut ty.ty {
+            syn::visit_mut::VisitMut::visit_type_mut(&mut Normalize(self), parsed);
+            ty.name = ty_name(parsed);
+        }
+        ty
+    }
+
+    /// Whether the name is of a sink or a source loaded from a YAML file.
+    fn is_yaml_item(&self, name: &str) -> bool {
+        self.sinks.iter().any(|v| !v.is_native && v.name == name)
+            || self.srcs.iter().any(|v| !v.is_native && v.name == name)
+    }
+
+    /// Register a constructor of the native sink or source.
+    pub fn add_constructor(
+        &mut self,
+        item: &str,
+        ctor: compile::Constructor,
+    ) -> Result<(), ItemNotFound> {
+        debug!("Item `{item}` has constructor `{}`", ctor.name);
+        if let Some(idx) = self.sink_id(item) {
+            self.sinks[idx].constructors.push(ctor);
+        } else if let Some(idx) = self.source_id(item) {
+            self.srcs[idx].constructors.push(ctor);
+        } else {
+            return Err(ItemNotFound(item.into()));
+        }
+        Ok(())
+    }
+
     /// Add a parameter value to the sink.
     // See the comment on Clippy in the `add_param` method.
     #[allow(clippy::result_large_err)]
@@ -389,34 +703,45 @@ impl Ctx {
         param: ParamKey,
         value: syn::Expr,
     ) -> Result<(), AddParamErr> {
-        if self.sink_id(sink_or_src_binding_name).is_some() {
+        if self.sink_binding_id(sink_or_src_binding_name).is_some() {
             self.add_sink_param(sink_or_src_binding_name, param, value)
         } else {
             self.add_src_filter(sink_or_src_binding_name, param, value)
         }
     }
 
-    /// Add a native sink/source initializer to the context.
+    /// Add a native sink/source binding initializer to the context.
+    #[allow(clippy::result_large_err)]
     pub fn add_native_init(
         &mut self,
         sink_or_src_binding_name: &str,
         expr: syn::Expr,
     ) -> Result<(), AddInitErr> {
-        debug!("Add native init expression");
+        debug!("Add native init expression to `{sink_or_src_binding_name}`");
         macro_rules! try_add {
-            ($name:ident, $map:ident) => {
-                if let Some((bind, id)) = self.$name(sink_or_src_binding_name) {
-                    return if bind.is_native() {
-                        self.$map.insert((id, ParamKey::new()), expr);
-                        Ok(())
-                    } else {
-                        Err(AddInitErr::ExpectNative(sink_or_src_binding_name.into()))
+            ($id:ident, $bindings:ident, $items:ident, $map:ident) => {
+                if let Some(id) = self.$id(sink_or_src_binding_name) {
+                    let target = self.$bindings[id].target;
+                    if !self.$items[target].is_native() {
+                        return Err(AddInitErr::ExpectNative(sink_or_src_binding_name.into()));
+                    }
+
+                    use hashbrown::hash_map::Entry;
+                    return match self.$map.entry((id, ParamKey::new())) {
+                        Entry::Occupied(e) => Err(AddInitErr::AlreadySet(
+                            sink_or_src_binding_name.into(),
+                            e.get().clone(),
+                        )),
+                        Entry::Vacant(e) => {
+                            e.insert(expr);
+                            Ok(())
+                        }
                     };
                 }
             };
         }
-        try_add!(sink_with_id, sink_params);
-        try_add!(source_with_id, src_filters);
+        try_add!(sink_binding_id, sinks_bindings, sinks, sink_params);
+        try_add!(source_binding_id, srcs_bindings, srcs, src_filters);
         Err(AddInitErr::DestNotFound(sink_or_src_binding_name.into()))
     }
 }
@@ -435,13 +760,183 @@ pub enum AddSinkErr {
 
 #[derive(Debug, thiserror::Error)]
 pub enum AddPipeErr {
-    #[error("Source with the name {0} not found")]
-    SourceNotFound(CompactString),
+    #[error("Binding with the name {0} not found")]
+    BindingNotFound(CompactString),
 
-    #[error("Sink with the name {0} not found")]
-    SinkNotFound(CompactString),
+    #[error("Binding {0} is a sink and cannot be used as a pipe input")]
+    SinkAsSource(CompactString),
+
+    #[error("Binding {0} is a source and cannot be used as a pipe output")]
+    SourceAsSink(CompactString),
+
+    #[error("Type of items produced by source {0} is unknown")]
+    UnknownItemTy(CompactString),
+
+    #[error(
+        "Sink {sink} does not accept `{item_ty}` produced by source {src}. {}",
+        if accepts.is_empty() {
+            "The sink accepts no types".to_owned()
+        } else {
+            format!("The sink accepts: `{}`", accepts.join("`, `"))
+        }
+    )]
+    TypeMismatch {
+        src: CompactString,
+        item_ty: CompactString,
+        sink: CompactString,
+        accepts: Vec<CompactString>,
+    },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CheckPolicyErr {
+    #[error("Binding with the name {0} not found")]
+    BindingNotFound(CompactString),
+
+    #[error("Binding {0} is not a source and has no column checks")]
+    NotSource(CompactString),
+
+    #[error("Binding {0} is a native source, its rows are not checked")]
+    NativeSource(CompactString),
+
+    #[error("Binding {0} is not a sink and cannot take rejected rows")]
+    NotSink(CompactString),
+
+    #[error("Sink {sink} does not accept rejected rows of type `{item_ty}`")]
+    TypeMismatch {
+        item_ty: CompactString,
+        sink: CompactString,
+    },
+
+    #[error("Sink {0} is used by a pipe or another binding and cannot take rejected rows")]
+    SinkInUse(CompactString),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Item with the name {0} not found")]
+pub struct ItemNotFound(pub CompactString);
+
+/// Type that a sink accepts or a source produces, see [Ctx::item_ty].
+#[derive(Clone, Debug)]
+pub struct ItemTy {
+    /// None if the type is not in Rust syntax, then it only matches the same name.
+    ty: Option<syn::Type>,
+
+    /// The type as shown in the messages, e.g. `Vec<data::Row>`.
+    name: CompactString,
+
+    /// Type parameters of the impl, which match any type.
+    params: SmallVec<[CompactString; 2]>,
+}
+
+impl ItemTy {
+    fn parse(ty: &str, params: &[CompactString]) -> Self {
+        let parsed = syn::parse_str::<syn::Type>(ty).ok();
+        Self {
+            name: parsed.as_ref().map_or_else(|| ty.into(), ty_name),
+            ty: parsed,
+            params: params.iter().cloned().collect(),
+        }
+    }
+
+    /// Whether the values of the type can be of the other type. Type parameters of
+    /// either type match any type, e.g. `Vec<T>` matches `Vec<data::Row>`.
+    pub fn matches(&self, other: &ItemTy) -> bool {
+        match (&self.ty, &other.ty) {
+            (Some(a), Some(b)) => TyMatch(&self.params, &other.params).ty(a, b),
+            _ => self.name == other.name,
+        }
+    }
+}
+
+impl std::fmt::Display for ItemTy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+/// Type as shown in the messages, without the spaces that separate no words,
+/// e.g. `Vec<a::B>` and `&mut dyn Fn(A) -> B`.
+fn ty_name(ty: &syn::Type) -> CompactString {
+    let tokens = ty.to_token_stream().to_string();
+    let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    let mut name = CompactString::default();
+    let mut chars = tokens.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == ' ' && !(is_word(name.chars().last()) && is_word(chars.peek().copied())) {
+            continue;
+        }
+        name.push(c);
+    }
+    name
+}
+
+/// Structural match of two types, with the type parameters of each.
+struct TyMatch<'a>(&'a [CompactString], &'a [CompactString]);
+
+impl TyMatch<'_> {
+    fn ty(&self, a: &syn::Type, b: &syn::Type) -> bool {
+        use syn::Type;
+
+        if is_param(a, self.0) || is_param(b, self.1) {
+            return true;
+        }
+        match (a, b) {
+            (Type::Paren(a), _) => self.ty(&a.elem, b),
+            (_, Type::Paren(b)) => self.ty(a, &b.elem),
+            (Type::Group(a), _) => self.ty(&a.elem, b),
+            (_, Type::Group(b)) => self.ty(a, &b.elem),
+            (Type::Path(a), Type::Path(b)) => {
+                a.qself.is_none() && b.qself.is_none() && self.path(&a.path, &b.path)
+            }
+            (Type::Reference(a), Type::Reference(b)) => {
+                a.mutability.is_some() == b.mutability.is_some() && self.ty(&a.elem, &b.elem)
+            }
+            (Type::Slice(a), Type::Slice(b)) => self.ty(&a.elem, &b.elem),
+            (Type::Array(a), Type::Array(b)) => a.len == b.len && self.ty(&a.elem, &b.elem),
+            (Type::Tuple(a), Type::Tuple(b)) => {
+                a.elems.len() == b.elems.len()
+                    && a.elems.iter().zip(&b.elems).all(|(a, b)| self.ty(a, b))
+            }
+            (a, b) => a == b,
+        }
+    }
+
+    fn path(&self, a: &syn::Path, b: &syn::Path) -> bool {
+        use syn::{GenericArgument, PathArguments};
+
+        a.segments.len() == b.segments.len()
+            && a.segments.iter().zip(&b.segments).all(|(a, b)| {
+                a.ident == b.ident
+                    && match (&a.arguments, &b.arguments) {
+                        (PathArguments::AngleBracketed(a), PathArguments::AngleBracketed(b)) => {
+                            a.args.len() == b.args.len()
+                                && a.args.iter().zip(&b.args).all(|pair| match pair {
+                                    (GenericArgument::Type(a), GenericArgument::Type(b)) => {
+                                        self.ty(a, b)
+                                    }
+                                    (a, b) => a == b,
+                                })
+                        }
+                        (a, b) => a == b,
+                    }
+            })
+    }
+}
+
+/// Whether the type is one of the type parameters.
+fn is_param(ty: &syn::Type, params: &[CompactString]) -> bool {
+    let syn::Type::Path(ty) = ty else {
+        return false;
+    };
+    ty.qself.is_none()
+        && ty
+            .path
+            .get_ident()
+            .is_some_and(|v| params.iter().any(|param| v == param.as_str()))
 }
 
+#[derive(Clone)]
 pub struct DataSource {
     /// Identifier name for this data source. Cannot be empty. Is valid Rust identifier.
     name: CompactString,
@@ -468,6 +963,13 @@ pub struct DataSource {
     /// and conformity to the schema.
     column_checks: Vec<ExplainExpr>,
 
+    /// Type of the items the source produces. YAML sources produce the generated
+    /// row struct, for native sources it comes from `Source::Item` in the Rust code.
+    item_ty: Option<ItemTy>,
+
+    /// Constructors of the native source, found in the Rust code.
+    constructors: Vec<compile::Constructor>,
+
     /// External types that should be "used" in the generated code, for
     /// column type resolution. This is in form of "use" syntax tree
     /// as these expressions can be complex.
@@ -510,11 +1012,32 @@ impl DataSource {
     pub fn uses(&self) -> &[syn::UseTree] {
         &self.uses
     }
+
+    /// Type of the items the source produces, if known.
+    pub fn item_ty(&self) -> Option<&ItemTy> {
+        self.item_ty.as_ref()
+    }
+
+    /// Constructors of the native source.
+    pub fn constructors(&self) -> &[compile::Constructor] {
+        &self.constructors
+    }
+
+    /// Name of the row struct generated from the columns of a YAML source.
+    pub fn row_name(&self) -> CompactString {
+        row_name(&self.name)
+    }
+}
+
+pub(crate) fn row_name(src: &str) -> CompactString {
+    format_compact!("{src}Row")
 }
 
 impl From<hir::Source> for DataSource {
     fn from(src: hir::Source) -> Self {
         Self {
+            item_ty: Some(ItemTy::parse(&row_name(&src.name), &[])),
+            constructors: Vec::new(),
             name: src.name,
             is_native: false,
             explain: src.explain,
@@ -539,6 +1062,7 @@ impl From<hir::Source> for DataSource {
     }
 }
 
+#[derive(Clone)]
 pub struct FilterTy {
     /// Default value for the filter, to use when it is not explicitly set.
     default: Option<syn::Expr>,
@@ -586,6 +1110,7 @@ impl From<hir::SourceFilter> for FilterTy {
     }
 }
 
+#[derive(Clone)]
 pub struct SourceColumn {
     /// Name of the column. Cannot be empty.
     name: CompactString,
@@ -633,10 +1158,14 @@ impl From<hir::SourceColumn> for SourceColumn {
     }
 }
 
+#[derive(Clone)]
 pub struct ExplainExpr {
     /// Optional explanation for the expression. Empty string means no explanation.
     explain: String,
     expr: syn::Expr,
+
+    /// Whether the check passes when `?` in the expression meets `None`.
+    none_passes: bool,
 }
 
 impl ExplainExpr {
@@ -651,6 +1180,10 @@ impl ExplainExpr {
     pub fn expr(&self) -> &syn::Expr {
         &self.expr
     }
+
+    pub fn none_passes(&self) -> bool {
+        self.none_passes
+    }
 }
 
 impl From<hir::Check> for ExplainExpr {
@@ -658,11 +1191,13 @@ impl From<hir::Check> for ExplainExpr {
         Self {
             explain: check.explain,
             expr: check.define,
+            none_passes: check.none_passes,
         }
     }
 }
 
 /// Data sink.
+#[derive(Clone)]
 pub struct Sink {
     /// Name of the sink. Cannot be empty.
     name: CompactString,
@@ -681,6 +1216,13 @@ pub struct Sink {
     /// and can operate on multiple parameters.
     checks: Vec<ExplainExpr>,
 
+    /// Types `T` for which the sink implements `Sink<T>`.
+    /// These come from the Rust code, and are empty until it is analyzed.
+    item_tys: Vec<ItemTy>,
+
+    /// Constructors of the native sink, found in the Rust code.
+    constructors: Vec<compile::Constructor>,
+
     /// External types that should be "used" in the generated code, for
     /// param type resolution. This is in form of "use" syntax tree
     /// as these expressions can be complex.
@@ -715,6 +1257,21 @@ impl Sink {
     pub fn uses(&self) -> &[syn::UseTree] {
         &self.uses
     }
+
+    /// Types `T` for which the sink implements `Sink<T>`.
+    pub fn item_tys(&self) -> &[ItemTy] {
+        &self.item_tys
+    }
+
+    /// Whether the sink implements `Sink<T>` for the given type.
+    pub fn accepts(&self, ty: &ItemTy) -> bool {
+        self.item_tys.iter().any(|v| v.matches(ty))
+    }
+
+    /// Constructors of the native sink.
+    pub fn constructors(&self) -> &[compile::Constructor] {
+        &self.constructors
+    }
 }
 
 impl From<hir::Sink> for Sink {
@@ -733,12 +1290,15 @@ impl From<hir::Sink> for Sink {
                 .into_iter()
                 .map(ExplainExpr::from)
                 .collect(),
+            item_tys: Vec::new(),
+            constructors: Vec::new(),
             uses: sink.uses,
         }
     }
 }
 
 /// Sink parameter.
+#[derive(Clone)]
 pub struct SinkParam {
     /// Default value for the parameter, to use when it is not explicitly set.
     default: Option<syn::Expr>,
@@ -811,6 +1371,22 @@ impl ParamKey {
     pub fn iter(&self) -> impl Iterator<Item = &str> {
         self.0.iter().map(|s| s.as_str())
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl From<&str> for ParamKey {
+    fn from(part: &str) -> Self {
+        let mut key = Self::new();
+        key.push(part.into());
+        key
+    }
 }
 
 impl std::fmt::Display for ParamKey {
@@ -841,3 +1417,123 @@ pub struct TrackField {
 pub struct TrackFn {
     pub fn_path: Vec<CompactString>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx_with_bindings() -> Ctx {
+        let path = |s: &str| compile::ItemPath {
+            segments: s.split("::").map(Into::into).collect(),
+        };
+
+        let mut ctx = Ctx::new("Test".into(), None).unwrap();
+        ctx.add_native_source(&path("src::Src")).unwrap();
+        ctx.set_source_item_ty("src::Src", "crate::data::Row", &[])
+            .unwrap();
+        ctx.add_native_sink(&path("sink::Sink")).unwrap();
+        ctx.add_sink_item_ty("sink::Sink", "data :: Row", &[])
+            .unwrap();
+        ctx.add_binding("input".into(), "src::Src").unwrap();
+        ctx.add_binding("output".into(), "sink::Sink").unwrap();
+        ctx
+    }
+
+    #[test]
+    fn add_pipe() {
+        let mut ctx = ctx_with_bindings();
+        ctx.add_pipe("input", "output").unwrap();
+
+        let pipes: Vec<_> = ctx
+            .pipes()
+            .map(|(src, sink)| (src.name(), sink.name()))
+            .collect();
+        assert_eq!(pipes, [("input", "output")]);
+    }
+
+    #[test]
+    fn add_pipe_errors() {
+        let mut ctx = ctx_with_bindings();
+
+        let err = ctx.add_pipe("unknown", "output").unwrap_err();
+        assert!(matches!(err, AddPipeErr::BindingNotFound(name) if name == "unknown"));
+
+        let err = ctx.add_pipe("output", "output").unwrap_err();
+        assert!(matches!(err, AddPipeErr::SinkAsSource(name) if name == "output"));
+
+        let err = ctx.add_pipe("input", "input").unwrap_err();
+        assert!(matches!(err, AddPipeErr::SourceAsSink(name) if name == "input"));
+
+        assert_eq!(ctx.pipes().count(), 0);
+    }
+
+    #[test]
+    fn add_pipe_type_mismatch() {
+        let mut ctx = ctx_with_bindings();
+        let path = compile::ItemPath {
+            segments: vec!["other".into(), "Other".into()],
+        };
+        ctx.add_native_sink(&path).unwrap();
+        ctx.add_sink_item_ty("other::Other", "data::Other", &[])
+            .unwrap();
+        ctx.add_binding("other".into(), "other::Other").unwrap();
+
+        let err = ctx.add_pipe("input", "other").unwrap_err();
+        assert!(
+            matches!(&err, AddPipeErr::TypeMismatch { item_ty, .. } if item_ty == "data::Row"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn add_pipe_generic_sink() {
+        let mut ctx = ctx_with_bindings();
+        let path = compile::ItemPath {
+            segments: vec!["log".into(), "Log".into()],
+        };
+        ctx.add_native_sink(&path).unwrap();
+        ctx.add_sink_item_ty("log::Log", "T", &["T".into()])
+            .unwrap();
+        ctx.add_binding("log".into(), "log::Log").unwrap();
+        ctx.add_pipe("input", "log").unwrap();
+
+        let vec = |ty: &str| ctx.item_ty(ty, &["T".into()]);
+        let row = |ty: &str| ctx.item_ty(ty, &[]);
+        assert!(vec("std::vec::Vec<T>").matches(&row("std::vec::Vec<data::Row>")));
+        assert!(vec("(T, u32)").matches(&row("(data::Row, u32)")));
+        assert!(!vec("Vec<T>").matches(&row("Option<data::Row>")));
+        assert!(!vec("(T, u32)").matches(&row("(data::Row, u64)")));
+    }
+
+    #[test]
+    fn item_ty() {
+        let mut ctx = Ctx::new("Test".into(), None).unwrap();
+        for name in ["Csv", "a::Csv"] {
+            ctx.add_sink(Sink {
+                name: name.into(),
+                is_native: false,
+                explain: Default::default(),
+                params: HashMap::new(),
+                checks: Vec::new(),
+                item_tys: Vec::new(),
+                constructors: Vec::new(),
+                uses: Vec::new(),
+            })
+            .unwrap();
+        }
+
+        let ty = |ty: &str| ctx.item_ty(ty, &[]).to_string();
+        assert_eq!(ty("crate :: a :: B"), "a::B");
+        assert_eq!(ty("_Csv::Csv"), "Csv");
+        assert_eq!(ty("_Csv::CsvRow"), "CsvRow");
+        assert_eq!(ty("a::_Csv::CsvRow"), "a::CsvRow");
+        assert_eq!(ty("Vec<crate::_Csv::Csv>"), "Vec<Csv>");
+        assert_eq!(ty("Vec < a :: B >"), "Vec<a::B>");
+        assert_eq!(ty("&mut dyn Fn(a::B) -> u32"), "&mut dyn Fn(a::B)->u32");
+        assert_eq!(ty("_::Csv"), "_::Csv");
+
+        // Modules that are not generated for a YAML item are kept.
+        assert_eq!(ty("_Csv2::Csv2Row"), "_Csv2::Csv2Row");
+        assert_eq!(ty("b::_Csv::CsvRow"), "b::_Csv::CsvRow");
+    }
+}
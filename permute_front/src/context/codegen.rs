@@ -1,19 +1,33 @@
 use super::*;
 use proc_macro2::{Span, TokenStream};
 use quote::{quote, TokenStreamExt};
+use syn::visit_mut::VisitMut;
 
 pub fn gen_main(ctx: &Ctx) -> TokenStream {
     info!("Generator main function started");
 
     let name = ctx.name();
-    let explain = ctx.explain();
+    let explain = opt_str_tokens(ctx.explain());
+    let bindings = gen_bindings(ctx);
+    let policies = gen_check_policies(ctx);
+    let pipes = ctx.pipes().map(|(src, sink)| {
+        let src_name = src.name();
+        let sink_name = sink.name();
+        let src = src_name.ident();
+        let sink = sink_name.ident();
+        quote! {
+            info!("Run pipe `{}` -> `{}`", #src_name, #sink_name);
+            permute::sys::pipe(#src_name, &mut #src, #sink_name, &mut #sink)?;
+        }
+    });
+
     let mut tokens = quote! {
         use log::*;
 
         const NAME: &'static str = #name;
         const EXPLAIN: Option<&'static str> = #explain;
 
-        fn main() {
+        fn main() -> std::process::ExitCode {
             permute::sys::init_logger();
 
             info!("{NAME}");
@@ -21,27 +35,287 @@ pub fn gen_main(ctx: &Ctx) -> TokenStream {
                 info!("{explain}");
             }
 
-            // TODO: Implement the main logic.
+            match run() {
+                Ok(()) => {
+                    info!("{NAME} finished");
+                    std::process::ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    error!("{NAME} failed. {e}");
+                    std::process::ExitCode::FAILURE
+                }
+            }
+        }
+
+        #[allow(unused_mut)]
+        fn run() -> Result<(), Box<dyn std::error::Error>> {
+            #bindings
+            #policies
+            #(#pipes)*
+            Ok(())
         }
     };
 
+    tokens.append_all(gen_modules(&gen_yaml_items(ctx)));
+
+    info!("Generator main function finished");
+    tokens
+}
+
+/// Generate the YAML sources and sinks, each along with the path of its module,
+/// e.g. `module1` for `module1::FileName`. Empty path is the crate root.
+/// Native items are already written in Rust, so only YAML ones are generated.
+pub fn gen_yaml_items(ctx: &Ctx) -> Vec<(CompactString, TokenStream)> {
+    fn module_of(name: &str) -> CompactString {
+        name.rsplit_once("::")
+            .map(|(module, _)| module)
+            .unwrap_or_default()
+            .into()
+    }
+
+    let mut items = Vec::with_capacity(ctx.sources().len() + ctx.sinks().len());
     info!("Generating sources");
-    for src in ctx.sources() {
-        tokens.append_all(gen_data_src(src));
+    for src in ctx.sources().iter().filter(|v| !v.is_native()) {
+        items.push((module_of(src.name()), gen_data_src(src)));
     }
     info!("Generating sinks");
-    for sink in ctx.sinks() {
-        tokens.append_all(gen_data_sink(sink));
+    for sink in ctx.sinks().iter().filter(|v| !v.is_native()) {
+        items.push((module_of(sink.name()), gen_data_sink(sink)));
+    }
+    items
+}
+
+/// Put the items into nested modules by their module paths, so that the items of
+/// `a::b` go into `pub mod a { pub mod b { ... } }`.
+pub fn gen_modules(items: &[(CompactString, TokenStream)]) -> TokenStream {
+    #[derive(Default)]
+    struct Module<'a> {
+        tokens: TokenStream,
+        children: std::collections::BTreeMap<&'a str, Module<'a>>,
+    }
+
+    fn to_tokens(module: Module) -> TokenStream {
+        let children = module.children.into_iter().map(|(name, child)| {
+            let name = name.ident();
+            let child = to_tokens(child);
+            quote! {
+                pub mod #name {
+                    #child
+                }
+            }
+        });
+        let tokens = module.tokens;
+        quote! {
+            #tokens
+            #(#children)*
+        }
+    }
+
+    let mut root = Module::default();
+    for (path, tokens) in items {
+        let segments = path.split("::").filter(|v| !v.is_empty());
+        let module = segments.fold(&mut root, |module, name| {
+            module.children.entry(name).or_default()
+        });
+        module.tokens.append_all(tokens.clone());
+    }
+    to_tokens(root)
+}
+
+/// Generate `let` statements that create and check every binding of the context.
+/// YAML bindings go first, as they do not depend on anything. Native bindings follow
+/// in the order in which their initializers reference each other.
+fn gen_bindings(ctx: &Ctx) -> TokenStream {
+    info!("Generating bindings");
+    let mut tokens = TokenStream::new();
+    let mut natives = Vec::new();
+
+    for (id, binding, src) in ctx.source_bindings() {
+        if src.is_native() {
+            natives.push((binding.name(), ctx.src_filter(id, &ParamKey::new())));
+            continue;
+        }
+
+        let fields = src.filters().iter().map(|(name, filter)| {
+            let value = ctx
+                .src_filter(id, &ParamKey::from(name.as_str()))
+                .map(|v| quote! { #v })
+                .or_else(|| map_value(filter.ty(), &ctx.src_filter_entries(id, name)));
+            let value = field_value(
+                binding.name(),
+                name,
+                &sibling_path(src.name(), filter_ty(src, name)),
+                value,
+                filter.default().is_some(),
+            );
+            let name = name.ident();
+            quote! { #name: #value }
+        });
+        let reader = quote! { reader: ::core::default::Default::default() };
+        let fields = fields.chain(std::iter::once(reader));
+        tokens.append_all(gen_yaml_binding(binding.name(), src.name(), fields));
+
+        let ident = binding.name().ident();
+        let name = binding.name();
+        tokens.append_all(quote! {
+            #ident.open().map_err(|e| e.with_binding(#name))?;
+        });
+    }
+
+    for (id, binding, sink) in ctx.sink_bindings() {
+        if sink.is_native() {
+            natives.push((binding.name(), ctx.sink_param(id, &ParamKey::new())));
+            continue;
+        }
+
+        let fields = sink.params().iter().map(|(name, param)| {
+            let value = ctx
+                .sink_param(id, &ParamKey::from(name.as_str()))
+                .map(|v| quote! { #v })
+                .or_else(|| map_value(param.ty(), &ctx.sink_param_entries(id, name)));
+            let value = field_value(
+                binding.name(),
+                name,
+                &sibling_path(sink.name(), sink_param_ty(sink, name)),
+                value,
+                param.default().is_some(),
+            );
+            let name = name.ident();
+            quote! { #name: #value }
+        });
+        tokens.append_all(gen_yaml_binding(binding.name(), sink.name(), fields));
+    }
+
+    for (name, init) in order_native_bindings(natives) {
+        let ident = name.ident();
+        if let Some(init) = init {
+            trace!(
+                "Native binding `{name}` initialized with `{}`",
+                quote!(#init)
+            );
+            tokens.append_all(quote! {
+                let mut #ident = #init;
+            });
+        } else {
+            let msg = format!("native binding `{name}` has no initializer");
+            tokens.append_all(quote! {
+                let mut #ident = compile_error!(#msg);
+            });
+        }
     }
 
-    info!("Generator main function finished");
     tokens
 }
 
+fn gen_yaml_binding(
+    binding: &str,
+    item: &str,
+    fields: impl Iterator<Item = TokenStream>,
+) -> TokenStream {
+    trace!("YAML binding `{binding}` of `{item}`");
+    let ident = binding.ident();
+    let item = item.item_path();
+    quote! {
+        let mut #ident = #item {
+            #(#fields),*
+        };
+        #ident.check().map_err(|e| e.with_binding(#binding))?;
+    }
+}
+
+/// Set check policies on the readers of the YAML source bindings. This goes after
+/// all bindings, as the reject sinks are moved into the sources.
+fn gen_check_policies(ctx: &Ctx) -> TokenStream {
+    let mut policies: Vec<_> = ctx.check_policies().collect();
+    policies.sort_by_key(|(src, _)| src.name());
+
+    let policies = policies.into_iter().filter_map(|(src, policy)| {
+        let src = src.name().ident();
+        let policy = match policy {
+            hir::CheckPolicy::Abort => return None,
+            hir::CheckPolicy::Skip => quote! { permute::sys::CheckPolicy::Skip },
+            hir::CheckPolicy::Reject(sink) => {
+                let sink_name = sink.name();
+                let sink = sink_name.ident();
+                quote! { permute::sys::CheckPolicy::reject(#sink_name, #sink) }
+            }
+        };
+        Some(quote! {
+            #src.reader.set_policy(#policy);
+        })
+    });
+    quote! { #(#policies)* }
+}
+
+/// Value of the YAML binding field. Falls back to the default value, if any,
+/// and to a compile error for a missing required field.
+fn field_value(
+    binding: &str,
+    field: &str,
+    field_ty: &TokenStream,
+    value: Option<TokenStream>,
+    has_default: bool,
+) -> TokenStream {
+    if let Some(value) = value {
+        quote! { #field_ty(::core::convert::Into::into(#value)) }
+    } else if has_default {
+        quote! { ::core::default::Default::default() }
+    } else {
+        let msg = format!("binding `{binding}` is missing required `{field}`");
+        quote! { compile_error!(#msg) }
+    }
+}
+
+/// Map value collected from the entries set by nested keys. Entries of a parameter
+/// that is not a map are reported by [Ctx::validate] and are not used here.
+fn map_value(ty: &syn::Type, entries: &[(&str, &syn::Expr)]) -> Option<TokenStream> {
+    if entries.is_empty() {
+        return None;
+    }
+    let map_ty = validate::map_ty(ty)?;
+    let entries = entries.iter().map(|(key, value)| {
+        quote! {
+            (::core::convert::Into::into(#key), ::core::convert::Into::into(#value))
+        }
+    });
+    Some(quote! {
+        <#map_ty as ::core::iter::FromIterator<_>>::from_iter([#(#entries),*])
+    })
+}
+
+/// Order native bindings so that a binding is created before it is used in the
+/// initializer of another one. Cyclic references are left in the original order
+/// for the compiler to report.
+fn order_native_bindings<'a>(
+    mut natives: Vec<(&'a str, Option<&'a syn::Expr>)>,
+) -> Vec<(&'a str, Option<&'a syn::Expr>)> {
+    let mut ordered = Vec::with_capacity(natives.len());
+    while !natives.is_empty() {
+        let ready = natives.iter().position(|(name, init)| {
+            let init = quote!(#init);
+            !natives
+                .iter()
+                .any(|(other, _)| other != name && references(init.clone(), other))
+        });
+        // On a cycle take whatever is first, the compiler will complain about it.
+        ordered.push(natives.remove(ready.unwrap_or_default()));
+    }
+    ordered
+}
+
+fn opt_str_tokens(s: Option<&str>) -> TokenStream {
+    if let Some(s) = s {
+        quote! { Some(#s) }
+    } else {
+        quote! { None }
+    }
+}
+
 /// Generate the data source struct and impls.
 pub fn gen_data_src(src: &DataSource) -> TokenStream {
     let struc = gen_data_src_struc(src);
     let impls = gen_data_src_impls(src);
+    let row = gen_data_src_row(src);
     let uses = use_tree_tokens(src.uses());
     let mod_name = src.name().underscored_ident();
     quote! {
@@ -49,48 +323,184 @@ pub fn gen_data_src(src: &DataSource) -> TokenStream {
             #uses
             #struc
             #impls
+            #row
         }
         pub use #mod_name::*;
     }
 }
 
+/// User imports, along with the helpers for the check expressions.
 fn use_tree_tokens(uses: &[syn::UseTree]) -> TokenStream {
     quote! {
+        #[allow(unused_imports)]
+        use permute::check::*;
         #(use #uses;)*
     }
 }
 
 fn gen_data_src_struc(src: &DataSource) -> TokenStream {
-    let src_name = src.name().ident();
+    let src_name = src.name().item_ident();
+    let row_name = src.row_name().item_ident();
     info!("Generating data source `{src_name}` struct");
 
-    let filters = src.filters().iter().map(|(k, v)| {
+    let filters = src.filters().iter().map(|(k, _)| {
         let name = k.ident();
-        let ty = v.ty();
+        let ty = filter_ty(src, k);
         quote! {
-            #name: #ty
+            pub #name: #ty,
         }
     });
     quote! {
         #[allow(non_camel_case_types)]
         #[derive(Debug)]
         pub struct #src_name {
-            #(#filters),*
+            #(#filters)*
+
+            /// Reader of the rows, set by [Self::open].
+            pub reader: permute::sys::Reader<#row_name>,
+        }
+    }
+}
+
+/// Generate the row struct from the source columns, with the column checks.
+fn gen_data_src_row(src: &DataSource) -> TokenStream {
+    let row_name = src.row_name().item_ident();
+    info!("Generating data source `{row_name}` row");
+
+    let fields = src.columns().iter().map(|col| {
+        let name = col.name().ident();
+        let ty = col.ty();
+        let doc = col.explain().map(|explain| quote! { #[doc = #explain] });
+        quote! {
+            #doc
+            pub #name: #ty,
+        }
+    });
+    let col_checks = src.columns().iter().map(|col| {
+        let name = col.name().ident();
+        let check = format!("check_{}", col.name()).ident();
+        let col_ty = column_ty(src, col.name());
+        quote! {
+            pub fn #check(&self) -> Result<(), permute::sys::ColumnCheckErr<#col_ty<'static>>> {
+                #col_ty(&self.#name).check()
+            }
+        }
+    });
+    let checks = src.columns().iter().map(|col| {
+        let check = format!("check_{}", col.name()).ident();
+        let name = col.name();
+        quote! {
+            self.#check()
+                .map_err(|e| permute::SourceError::from(e).with_field(#name))?;
+        }
+    });
+    let additional_checks = src.column_checks().iter().map(|check| {
+        let expr = check_cond(check);
+        let explain = check.explain().unwrap_or_default();
+        quote! {
+            if !#expr {
+                return Err(permute::sys::ColumnCheckErr::<#row_name>::new(#explain).into());
+            }
+        }
+    });
+    let cols = src.columns().iter().map(|col| {
+        let ty = col.ty();
+        let col_ty = column_ty(src, col.name());
+        let explain = opt_str_tokens(col.explain());
+        let check = col.checks().iter().map(|check| {
+            let expr = check_cond(check);
+            let explain = check.explain().unwrap_or_default();
+            quote! {
+                if !#expr {
+                    return Err(permute::sys::ColumnCheckErr::new(#explain));
+                }
+            }
+        });
+        quote! {
+            #[allow(non_camel_case_types)]
+            #[derive(Debug)]
+            pub struct #col_ty<'a>(pub &'a #ty);
+
+            impl std::ops::Deref for #col_ty<'_> {
+                type Target = #ty;
+
+                fn deref(&self) -> &Self::Target {
+                    self.0
+                }
+            }
+
+            impl #col_ty<'_> {
+                pub fn explain() -> Option<&'static str> {
+                    #explain
+                }
+
+                pub fn check(&self) -> Result<(), permute::sys::ColumnCheckErr<#col_ty<'static>>> {
+                    #(#check)*
+                    Ok(())
+                }
+            }
+        }
+    });
+
+    let src_name = src.name().item_ident();
+    quote! {
+        #[derive(Debug)]
+        pub struct #row_name {
+            #(#fields)*
+        }
+
+        impl #row_name {
+            #(#col_checks)*
+
+            /// Run checks of all columns and of the row as a whole.
+            pub fn check(&self) -> Result<(), permute::SourceError> {
+                #(#checks)*
+                #(#additional_checks)*
+                Ok(())
+            }
+        }
+        #(#cols)*
+
+        impl permute::Source for #src_name {
+            type Item = #row_name;
+            type Error = permute::SourceError;
+
+            fn next(&mut self) -> Option<Result<Self::Item, Self::Error>> {
+                self.reader.next(#row_name::check)
+            }
         }
     }
 }
 
 fn gen_data_src_impls(src: &DataSource) -> TokenStream {
-    let src_name = src.name().ident();
+    let src_name = src.name().item_ident();
     info!("Generating data source `{src_name}` impls");
 
-    let impls = src.filters().iter().map(|(name, _)| {
-        let fmt_ty = filter_ty(src, name);
+    let impls = src.filters().iter().map(|(name, filter)| {
+        let ty = filter.ty();
         let name = name.ident();
         // Generate getter for the filter.
         quote! {
-            pub fn #name(&self) -> #fmt_ty {
-                #fmt_ty(&self.#name)
+            pub fn #name(&self) -> &#ty {
+                &self.#name.0
+            }
+        }
+    });
+    let checks = src.filters().keys().map(|name| {
+        let ident = name.ident();
+        let name = name.as_str();
+        quote! {
+            self.#ident
+                .check()
+                .map_err(|e| permute::SourceError::from(e).with_field(#name))?;
+        }
+    });
+    let additional_checks = src.filter_checks().iter().map(|check| {
+        let expr = check_cond(check);
+        let explain = check.explain().unwrap_or_default();
+        quote! {
+            if !#expr {
+                return Err(permute::sys::FilterCheckErr::<#src_name>::new(#explain).into());
             }
         }
     });
@@ -118,11 +528,11 @@ fn gen_data_src_impls(src: &DataSource) -> TokenStream {
             quote! {}
         };
         let check = v.checks().iter().map(|check| {
-            let expr = check.expr();
-            let explain = check.explain();
+            let expr = check_cond(check);
+            let explain = check.explain().unwrap_or_default();
             quote! {
                 if !#expr {
-                    return Err(permute::sys::FilterCheckErr<#fmt_ty>::new(#explain));
+                    return Err(permute::sys::FilterCheckErr::<#fmt_ty>::new(#explain));
                 }
             }
         });
@@ -131,6 +541,14 @@ fn gen_data_src_impls(src: &DataSource) -> TokenStream {
             #[derive(Debug)]
             pub struct #fmt_ty(pub #ty);
 
+            impl std::ops::Deref for #fmt_ty {
+                type Target = #ty;
+
+                fn deref(&self) -> &Self::Target {
+                    &self.0
+                }
+            }
+
             impl #fmt_ty {
                 pub fn explain() -> Option<&'static str> {
                     #explain
@@ -149,17 +567,89 @@ fn gen_data_src_impls(src: &DataSource) -> TokenStream {
     quote! {
         impl #src_name {
             #(#impls)*
+
+            /// Run checks of all filters and of the source as a whole.
+            pub fn check(&self) -> Result<(), permute::SourceError> {
+                #(#checks)*
+                #(#additional_checks)*
+                Ok(())
+            }
+
+            /// Set the reader of the rows from the `permute::ReadRows` impl
+            /// of the project Rust code.
+            pub fn open(&mut self) -> Result<(), permute::SourceError> {
+                let rows = <Self as permute::ReadRows>::read_rows(self)?;
+                self.reader.set(rows);
+                Ok(())
+            }
         }
         #(#fmts)*
     }
 }
 
+/// Condition of the check. Expressions with `?` are evaluated in a closure, so that
+/// `None` short-circuits the check to pass or to fail, as configured for the check.
+fn check_cond(check: &ExplainExpr) -> TokenStream {
+    let mut expr = check.expr().clone();
+    let mut visitor = OptionTry::default();
+    visitor.visit_expr_mut(&mut expr);
+
+    if visitor.found {
+        let none_passes = check.none_passes();
+        quote! {
+            (|| -> ::core::option::Option<bool> {
+                ::core::option::Option::Some(#expr)
+            })()
+            .unwrap_or(#none_passes)
+        }
+    } else {
+        quote! { #expr }
+    }
+}
+
+/// Rewrites `expr?` into `Option::as_ref(&expr)?`. This lets `?` see through the generated
+/// wrappers, which deref to the value, and avoids moving the value out of `self`.
+/// Closures in the expression have their own `?` scope and are left as they are.
+#[derive(Default)]
+struct OptionTry {
+    found: bool,
+}
+
+impl VisitMut for OptionTry {
+    fn visit_expr_mut(&mut self, expr: &mut syn::Expr) {
+        syn::visit_mut::visit_expr_mut(self, expr);
+        if let syn::Expr::Try(try_expr) = expr {
+            self.found = true;
+            let inner = &try_expr.expr;
+            *try_expr.expr = syn::parse_quote! { ::core::option::Option::as_ref(&#inner) };
+        }
+    }
+
+    fn visit_expr_closure_mut(&mut self, _: &mut syn::ExprClosure) {}
+}
+
 fn filter_ty(src: &DataSource, filter: &str) -> syn::Ident {
-    format!("{}_{filter}", src.name()).ident()
+    format!("{}_{filter}", src.name().item_ident()).ident()
+}
+
+fn column_ty(src: &DataSource, column: &str) -> syn::Ident {
+    format!("{}_{column}", src.row_name().item_ident()).ident()
 }
 
 fn sink_param_ty(sink: &Sink, param: &str) -> syn::Ident {
-    format!("{}_{param}", sink.name()).ident()
+    format!("{}_{param}", sink.name().item_ident()).ident()
+}
+
+/// Path from the crate root to the item generated in the same module
+/// as the YAML item of the given name.
+fn sibling_path(item: &str, ident: syn::Ident) -> TokenStream {
+    match item.rsplit_once("::") {
+        Some((module, _)) => {
+            let module = module.item_path();
+            quote! { #module::#ident }
+        }
+        None => quote! { #ident },
+    }
 }
 
 /// Generate the data sink struct and impls.
@@ -179,7 +669,7 @@ pub fn gen_data_sink(sink: &Sink) -> TokenStream {
 }
 
 fn gen_data_sink_struc(sink: &Sink) -> TokenStream {
-    let sink_name = sink.name().ident();
+    let sink_name = sink.name().item_ident();
     info!("Generating data sink `{sink_name}` struct");
 
     let impls = sink.params().iter().map(|(k, v)| {
@@ -198,18 +688,18 @@ fn gen_data_sink_struc(sink: &Sink) -> TokenStream {
         };
 
         let checks = v.checks().iter().map(|check| {
-            let expr = check.expr();
-            let explain = check.explain();
+            let expr = check_cond(check);
+            let explain = check.explain().unwrap_or_default();
             quote! {
                 if !#expr {
-                    return Err(permute::sys::FilterCheckErr::new(#explain));
+                    return Err(permute::sys::ParamCheckErr::new(#explain));
                 }
             }
         });
 
+        // No `Debug`, as the parameters are often functions, e.g. formatters.
         quote! {
             #[allow(non_camel_case_types)]
-            #[derive(Debug)]
             pub struct #name(pub #ty);
 
             impl std::ops::Deref for #name {
@@ -221,7 +711,7 @@ fn gen_data_sink_struc(sink: &Sink) -> TokenStream {
             }
 
             impl #name {
-                pub fn check(&self) -> Result<(), permute::sys::FilterCheckErr<#name>> {
+                pub fn check(&self) -> Result<(), permute::sys::ParamCheckErr<#name>> {
                     #(#checks)*
                     Ok(())
                 }
@@ -235,13 +725,12 @@ fn gen_data_sink_struc(sink: &Sink) -> TokenStream {
         let name = sink_param_ty(sink, k);
         let ident = k.ident();
         quote! {
-            #ident: #name
+            pub #ident: #name
         }
     });
 
     quote! {
         #[allow(non_camel_case_types)]
-        #[derive(Debug)]
         pub struct #sink_name {
             #(#params),*
         }
@@ -251,17 +740,34 @@ fn gen_data_sink_struc(sink: &Sink) -> TokenStream {
 }
 
 fn gen_data_sink_impls(sink: &Sink) -> TokenStream {
-    let sink_name = sink.name().ident();
+    let sink_name = sink.name().item_ident();
     info!("Generating data sink `{sink_name}` impls");
 
     let impls = sink.params().iter().map(|(name, param)| {
-        let sink_ty_name = sink_param_ty(sink, name);
         let name = name.ident();
         let ty = param.ty();
-        // Generate getter for the filter.
+        // Generate getter for the parameter.
         quote! {
             pub fn #name(&self) -> &#ty {
-                &self.#sink_ty_name.0
+                &self.#name.0
+            }
+        }
+    });
+    let checks = sink.params().keys().map(|name| {
+        let ident = name.ident();
+        let name = name.as_str();
+        quote! {
+            self.#ident
+                .check()
+                .map_err(|e| permute::SinkError::from(e).with_field(#name))?;
+        }
+    });
+    let additional_checks = sink.checks().iter().map(|check| {
+        let expr = check_cond(check);
+        let explain = check.explain().unwrap_or_default();
+        quote! {
+            if !#expr {
+                return Err(permute::sys::ParamCheckErr::<#sink_name>::new(#explain).into());
             }
         }
     });
@@ -269,6 +775,13 @@ fn gen_data_sink_impls(sink: &Sink) -> TokenStream {
     quote! {
         impl #sink_name {
             #(#impls)*
+
+            /// Run checks of all parameters and of the sink as a whole.
+            pub fn check(&self) -> Result<(), permute::SinkError> {
+                #(#checks)*
+                #(#additional_checks)*
+                Ok(())
+            }
         }
     }
 }
@@ -276,7 +789,15 @@ fn gen_data_sink_impls(sink: &Sink) -> TokenStream {
 trait StrExt {
     fn ident(&self) -> syn::Ident;
 
+    /// Identifier of the item by the last segment of its path, like `FileName`
+    /// of `module1::FileName`.
+    fn item_ident(&self) -> syn::Ident;
+
+    /// Like [Self::item_ident], prefixed with an underscore.
     fn underscored_ident(&self) -> syn::Ident;
+
+    /// Path of the item from the crate root, like `module1::FileName`.
+    fn item_path(&self) -> TokenStream;
 }
 
 impl StrExt for str {
@@ -284,8 +805,17 @@ impl StrExt for str {
         syn::Ident::new(self, Span::call_site())
     }
 
+    fn item_ident(&self) -> syn::Ident {
+        self.rsplit("::").next().unwrap_or_default().ident()
+    }
+
     fn underscored_ident(&self) -> syn::Ident {
-        syn::Ident::new(&format!("_{}", self), Span::call_site())
+        syn::Ident::new(&format!("_{}", self.item_ident()), Span::call_site())
+    }
+
+    fn item_path(&self) -> TokenStream {
+        let segments = self.split("::").map(|v| v.ident());
+        quote! { #(#segments)::* }
     }
 }
 
@@ -306,6 +836,27 @@ pub fn trace_printall(tokens: &TokenStream) {
 mod tests {
     use super::*;
 
+    #[test]
+    fn yaml_modules() {
+        let items = [
+            ("a::b".into(), quote! { struct B; }),
+            ("".into(), quote! { struct Root; }),
+            ("a".into(), quote! { struct A; }),
+        ];
+        let expected = quote! {
+            struct Root;
+            pub mod a {
+                struct A;
+                pub mod b {
+                    struct B;
+                }
+            }
+        };
+        assert_eq!(gen_modules(&items).to_string(), expected.to_string());
+        assert_eq!("a::b::Item".item_path().to_string(), "a :: b :: Item");
+        assert_eq!("a::b::Item".underscored_ident(), "_Item");
+    }
+
     #[test]
     fn printall() {
         crate::setup_logger();
@@ -314,4 +865,102 @@ mod tests {
         let tokens = gen_main(&ctx);
         trace_printall(&tokens);
     }
+
+    #[test]
+    fn source_row() {
+        crate::setup_logger();
+
+        let ctx = crate::yaml::load::tests::do_load_project();
+        let src = ctx.source("EmploymentRecord").unwrap();
+        let file: syn::File = syn::parse2(gen_data_src(src)).unwrap();
+        let syn::Item::Mod(module) = &file.items[0] else {
+            panic!("expected the source module");
+        };
+        let items = &module.content.as_ref().unwrap().1;
+
+        let row = items
+            .iter()
+            .find_map(|item| match item {
+                syn::Item::Struct(v) if v.ident == "EmploymentRecordRow" => Some(v),
+                _ => None,
+            })
+            .expect("row struct is generated");
+        let columns: Vec<_> = row
+            .fields
+            .iter()
+            .map(|f| f.ident.as_ref().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            columns,
+            [
+                "employee_id",
+                "hire_date",
+                "termination_date",
+                "salary",
+                "meta"
+            ]
+        );
+
+        let source_impl = items.iter().any(|item| match item {
+            syn::Item::Impl(v) => {
+                let trait_path = v
+                    .trait_
+                    .as_ref()
+                    .map(|(_, path, _)| quote!(#path).to_string());
+                trait_path.as_deref() == Some("permute :: Source")
+            }
+            _ => false,
+        });
+        assert!(source_impl, "source implements `permute::Source`");
+
+        let open = items.iter().any(|item| match item {
+            syn::Item::Impl(v) if v.trait_.is_none() => v
+                .items
+                .iter()
+                .any(|item| matches!(item, syn::ImplItem::Fn(f) if f.sig.ident == "open")),
+            _ => false,
+        });
+        assert!(open, "source opens the rows of `permute::ReadRows`");
+    }
+
+    #[test]
+    fn check_cond_option_try() {
+        let check = |expr: syn::Expr, none_passes| {
+            let check = ExplainExpr {
+                explain: Default::default(),
+                expr,
+                none_passes,
+            };
+            check_cond(&check).to_string()
+        };
+
+        let plain = check(syn::parse_quote!(self.len() >= 1), true);
+        assert_eq!(plain, quote!(self.len() >= 1).to_string());
+
+        let expected = quote! {
+            (|| -> ::core::option::Option<bool> {
+                ::core::option::Option::Some(
+                    ::core::option::Option::as_ref(&self.header)?.len() == 1
+                )
+            })()
+            .unwrap_or(false)
+        };
+        let wrapped = check(syn::parse_quote!(self.header?.len() == 1), false);
+        assert_eq!(wrapped, expected.to_string());
+
+        // `?` of a closure is not the one of the check.
+        let closure = check(syn::parse_quote!((|| Some(self.a?))().is_some()), true);
+        assert!(!closure.contains("as_ref"), "{closure}");
+    }
+
+    #[test]
+    fn native_bindings_order() {
+        let feed: syn::Expr = syn::parse_quote!(crate::Ee2Csv::new(csv));
+        let csv: syn::Expr = syn::parse_quote!(crate::Csv::new());
+        let natives = vec![("feed", Some(&feed)), ("csv", Some(&csv))];
+
+        let ordered = order_native_bindings(natives);
+        let names: Vec<_> = ordered.into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["csv", "feed"]);
+    }
 }
@@ -0,0 +1,318 @@
+use super::*;
+
+/// Error found by [Ctx::validate] in the values that the main file sets on the bindings.
+#[derive(Debug, thiserror::Error)]
+pub enum ValidateErr {
+    #[error(
+        "Binding `{binding}` has no parameter `{key}`.{}",
+        suggest.as_ref().map(|v| format!(" Did you mean `{v}`?")).unwrap_or_default()
+    )]
+    UnknownKey {
+        binding: CompactString,
+        key: ParamKey,
+        suggest: Option<CompactString>,
+    },
+
+    #[error("Binding `{binding}` is missing required parameter `{param}`, which has no default")]
+    MissingRequired {
+        binding: CompactString,
+        param: CompactString,
+    },
+
+    #[error("Binding `{binding}` sets `{key}`, but parameter `{param}` of type `{ty}` has no such field")]
+    NestedKeyMismatch {
+        binding: CompactString,
+        key: ParamKey,
+        param: CompactString,
+        ty: String,
+    },
+
+    #[error(
+        "Binding `{binding}` calls `{name}`, which is not a constructor of `{item}`. {}",
+        if available.is_empty() {
+            "It has no public associated functions that return `Self`".to_owned()
+        } else {
+            format!("Constructors are: {}", available.join(", "))
+        }
+    )]
+    UnknownConstructor {
+        binding: CompactString,
+        item: CompactString,
+        name: CompactString,
+        available: Vec<CompactString>,
+    },
+
+    #[error("Binding `{binding}` calls `{name}` with {found} arguments, but it takes {expected}")]
+    ConstructorArity {
+        binding: CompactString,
+        name: CompactString,
+        expected: usize,
+        found: usize,
+    },
+
+    #[error("Binding `{binding}` refers to binding `{name}`, which does not exist")]
+    UnknownBinding {
+        binding: CompactString,
+        name: CompactString,
+    },
+}
+
+/// Parameter of a YAML sink or a filter of a YAML source, as seen by the validation.
+struct Declared<'a> {
+    name: &'a str,
+    ty: &'a syn::Type,
+    has_default: bool,
+}
+
+impl Ctx {
+    /// Check the values set on the YAML bindings against the parameters of the sinks
+    /// and the filters of the sources. All found errors are returned.
+    pub fn validate(&self) -> Result<(), Vec<ValidateErr>> {
+        info!("Validate the context");
+        let mut errors = Vec::new();
+
+        for (id, binding, src) in self.source_bindings() {
+            if src.is_native() {
+                let init = self.src_filter(id, &ParamKey::new());
+                self.validate_native_init(
+                    binding.name(),
+                    src.name(),
+                    src.constructors(),
+                    init,
+                    &mut errors,
+                );
+                continue;
+            }
+            let declared = src.filters().iter().map(|(name, filter)| Declared {
+                name,
+                ty: filter.ty(),
+                has_default: filter.default().is_some(),
+            });
+            let keys = self
+                .src_filters
+                .keys()
+                .filter(|(binding, _)| *binding == id)
+                .map(|(_, key)| key);
+            validate_binding(binding.name(), declared.collect(), keys, &mut errors);
+        }
+
+        for (id, binding, sink) in self.sink_bindings() {
+            if sink.is_native() {
+                let init = self.sink_param(id, &ParamKey::new());
+                self.validate_native_init(
+                    binding.name(),
+                    sink.name(),
+                    sink.constructors(),
+                    init,
+                    &mut errors,
+                );
+                continue;
+            }
+            let declared = sink.params().iter().map(|(name, param)| Declared {
+                name,
+                ty: param.ty(),
+                has_default: param.default().is_some(),
+            });
+            let keys = self
+                .sink_params
+                .keys()
+                .filter(|(binding, _)| *binding == id)
+                .map(|(_, key)| key);
+            validate_binding(binding.name(), declared.collect(), keys, &mut errors);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Check the initializer of a native binding that calls a constructor of the item,
+    /// e.g. `crate::ee_to_csv::Ee2Csv::new(csv)`. Other expressions are left for the
+    /// compiler to check.
+    fn validate_native_init(
+        &self,
+        binding: &str,
+        item: &str,
+        ctors: &[compile::Constructor],
+        init: Option<&syn::Expr>,
+        errors: &mut Vec<ValidateErr>,
+    ) {
+        trace!("Validate initializer of native binding `{binding}`");
+        let Some(syn::Expr::Call(call)) = init else {
+            return;
+        };
+        let syn::Expr::Path(func) = &*call.func else {
+            return;
+        };
+        let segments = &func.path.segments;
+        let item_ident = item.rsplit("::").next().unwrap_or(item);
+        if segments.len() < 2 || segments[segments.len() - 2].ident != item_ident {
+            trace!("Initializer of `{binding}` does not call an associated function of `{item}`");
+            return;
+        }
+
+        let name = segments[segments.len() - 1].ident.to_compact_string();
+        match ctors.iter().find(|v| v.name == name) {
+            None => errors.push(ValidateErr::UnknownConstructor {
+                binding: binding.into(),
+                item: item.into(),
+                name,
+                available: ctors.iter().map(|v| v.name.clone()).collect(),
+            }),
+            Some(ctor) if ctor.params.len() != call.args.len() => {
+                errors.push(ValidateErr::ConstructorArity {
+                    binding: binding.into(),
+                    name,
+                    expected: ctor.params.len(),
+                    found: call.args.len(),
+                })
+            }
+            Some(_) => {}
+        }
+
+        for arg in &call.args {
+            let syn::Expr::Path(path) = arg else {
+                continue;
+            };
+            let Some(ident) = path.path.get_ident() else {
+                continue;
+            };
+            let name = ident.to_compact_string();
+
+            // Capitalized names are constants and enum variants, like `None`.
+            let is_binding_like = path.qself.is_none() && name.starts_with(char::is_lowercase);
+            if is_binding_like
+                && self.source_binding_id(&name).is_none()
+                && self.sink_binding_id(&name).is_none()
+            {
+                errors.push(ValidateErr::UnknownBinding {
+                    binding: binding.into(),
+                    name,
+                });
+            }
+        }
+    }
+}
+
+fn validate_binding<'a>(
+    binding: &str,
+    declared: Vec<Declared>,
+    keys: impl Iterator<Item = &'a ParamKey>,
+    errors: &mut Vec<ValidateErr>,
+) {
+    trace!("Validate binding `{binding}`");
+    let mut set = SmallVec::<[&str; 16]>::new();
+
+    for key in keys {
+        let Some(name) = key.iter().next() else {
+            continue;
+        };
+        let Some(param) = declared.iter().find(|v| v.name == name) else {
+            let suggest = suggest(name, declared.iter().map(|v| v.name));
+            errors.push(ValidateErr::UnknownKey {
+                binding: binding.into(),
+                key: key.clone(),
+                suggest: suggest.map(Into::into),
+            });
+            continue;
+        };
+        set.push(param.name);
+
+        // Nested keys are entries of a map parameter, one level deep.
+        if key.len() > 1 && (key.len() > 2 || map_ty(param.ty).is_none()) {
+            let ty = param.ty;
+            errors.push(ValidateErr::NestedKeyMismatch {
+                binding: binding.into(),
+                key: key.clone(),
+                param: param.name.into(),
+                ty: quote::quote!(#ty).to_string(),
+            });
+        }
+    }
+
+    let mut missing: SmallVec<[&str; 8]> = declared
+        .iter()
+        .filter(|v| !v.has_default && !set.contains(&v.name))
+        .map(|v| v.name)
+        .collect();
+    missing.sort_unstable();
+    for param in missing {
+        errors.push(ValidateErr::MissingRequired {
+            binding: binding.into(),
+            param: param.into(),
+        });
+    }
+}
+
+/// Map type of the parameter, possibly wrapped in `Option`. Such parameters can be set
+/// by nested keys in the main file, one for each entry.
+pub(crate) fn map_ty(ty: &syn::Type) -> Option<&syn::Type> {
+    let syn::Type::Path(path) = ty else {
+        return None;
+    };
+    let last = path.path.segments.last()?;
+    match last.ident.to_string().as_str() {
+        "HashMap" | "BTreeMap" | "IndexMap" => Some(ty),
+        "Option" => {
+            let syn::PathArguments::AngleBracketed(args) = &last.arguments else {
+                return None;
+            };
+            match args.args.first()? {
+                syn::GenericArgument::Type(inner) => map_ty(inner),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Closest of the candidates to the unknown name, if any is close enough to be a typo.
+fn suggest<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let max = (name.chars().count() / 3).max(1);
+    candidates
+        .map(|v| (edit_distance(name, v), v))
+        .filter(|(distance, _)| *distance <= max)
+        .min()
+        .map(|(_, v)| v)
+}
+
+/// Levenshtein distance between the strings, in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: SmallVec<[char; 32]> = b.chars().collect();
+    let mut prev: SmallVec<[usize; 32]> = (0..=b.len()).collect();
+    let mut cur = SmallVec::<[usize; 32]>::from_elem(0, b.len() + 1);
+
+    for (i, ca) in a.chars().enumerate() {
+        cur[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let subst = prev[j] + usize::from(ca != *cb);
+            cur[j + 1] = subst.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggest_typo() {
+        let names = ["date_from", "date_to", "exclude_terminations"];
+        assert_eq!(suggest("date_form", names.into_iter()), Some("date_from"));
+        assert_eq!(suggest("date_tu", names.into_iter()), Some("date_to"));
+        assert_eq!(suggest("path", names.into_iter()), None);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn map_types() {
+        let ty: syn::Type = syn::parse_quote!(Option<std::collections::HashMap<String, u32>>);
+        assert!(map_ty(&ty).is_some());
+        let ty: syn::Type = syn::parse_quote!(Option<Vec<String>>);
+        assert!(map_ty(&ty).is_none());
+    }
+}
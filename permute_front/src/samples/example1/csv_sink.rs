@@ -1,3 +1,6 @@
+use permute::SinkError;
+use std::io::Write;
+
 #[derive(Default)]
 pub struct RowSequence {
     current: u32,
@@ -21,3 +24,35 @@ impl RowSequence {
 /// to the writer.
 pub type WriteFn<T> = dyn FnMut(&mut crate::Csv, T);
 
+impl crate::Csv {
+    /// Join the fields into a line of the file.
+    fn line(&self, fields: &[String]) -> String {
+        fields.join(self.delimiter.as_str()) + self.eol.as_str()
+    }
+}
+
+impl permute::Sink<Vec<String>> for crate::Csv {
+    type Error = SinkError;
+
+    /// Append the fields as a line of the file. The header goes first into an empty file.
+    fn put(&mut self, fields: Vec<String>) -> Result<(), Self::Error> {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path.as_str())
+            .map_err(SinkError::from_cause)?;
+        let is_empty = file.metadata().map_err(SinkError::from_cause)?.len() == 0;
+
+        let mut text = String::new();
+        if let (true, Some(header)) = (is_empty, &self.header.0) {
+            text += &self.line(header);
+        }
+        text += &self.line(&fields);
+        file.write_all(text.as_bytes())
+            .map_err(SinkError::from_cause)
+    }
+
+    fn done(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
@@ -1,8 +1,6 @@
-use crate::EmploymentRecord;
+use crate::EmploymentRecordRow;
 use crate::Csv;
 
-use serde::Serialize;
-
 /// Wrapper sink to feed employment records to a CSV file.
 pub struct Ee2Csv {
     sink: Csv,
@@ -15,51 +13,55 @@ impl Ee2Csv {
 }
 
 /// Record to be written to the CSV file.
-/// Derive `RecToVec` to convert the record to a vector of strings.
-#[derive(Serialize)]
 pub struct Record {
-    #[serde(rename = "#")]
     rec_num: u32,
-
-    #[serde(rename = "Employee ID")]
     empl_id: String,
-
-    #[serde(rename = "Hire Date")]
     hire_date: chrono::NaiveDate,
-
-    #[serde(rename = "Termination Date")]
     term_date: Option<chrono::NaiveDate>,
-
-    #[serde(rename = "Salary")]
     salary: crate::monetary::Monetary,
-
-    #[serde(rename = "Department")]
     dept: Option<String>,
-
-    #[serde(rename = "Job Title")]
     title: Option<String>,
 }
 
-impl permute::Sink<EmploymentRecord> for Ee2Csv {
+impl Record {
+    /// Format the fields with the formatters of the CSV sink.
+    pub fn fields(&self, csv: &Csv) -> Vec<String> {
+        let none = |v: &dyn std::any::Any| (csv.none_fmt)(v);
+        vec![
+            self.rec_num.to_string(),
+            self.empl_id.clone(),
+            (csv.date_fmt)(self.hire_date),
+            self.term_date
+                .map_or_else(|| none(&self.term_date), |v| (csv.date_fmt)(v)),
+            self.salary.to_string(),
+            self.dept.clone().unwrap_or_else(|| none(&self.dept)),
+            self.title.clone().unwrap_or_else(|| none(&self.title)),
+        ]
+    }
+}
+
+impl permute::Sink<EmploymentRecordRow> for Ee2Csv {
     type Error = permute::SinkError;
 
-    fn put(&mut self, ee: EmploymentRecord) -> Result<(), Self::Error> {
-        let rec_num = self.csv.row_sequence.advance();
+    fn put(&mut self, ee: EmploymentRecordRow) -> Result<(), Self::Error> {
+        let rec_num = self.sink.row_sequence.0.advance();
 
+        let meta = |key: &str| ee.meta.as_ref().and_then(|v| v.get(key)).cloned();
         let record = Record {
             rec_num,
             empl_id: ee.employee_id,
             hire_date: ee.hire_date,
             term_date: ee.termination_date,
             salary: ee.salary,
-            dept: ee.meta.and_then(|v| v.get("department")),
-            title: ee.meta.and_then(|v| v.get("job_title")),
+            dept: meta("department"),
+            title: meta("job_title"),
         };
 
-        self.csv.put(record)
+        let fields = record.fields(&self.sink);
+        permute::Sink::put(&mut self.sink, fields)
     }
 
     fn done(&mut self) -> Result<(), Self::Error> {
-        self.csv.done()
+        permute::Sink::<Vec<String>>::done(&mut self.sink)
     }
 }
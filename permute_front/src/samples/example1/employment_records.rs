@@ -0,0 +1,72 @@
+//! Rows of the `EmploymentRecord` source. A real project would read them from a file
+//! or a database, the example keeps a few of them in memory.
+
+use crate::monetary::Monetary;
+use crate::{EmploymentRecord, EmploymentRecordRow};
+
+use chrono::NaiveDate;
+use permute::SourceError;
+
+/// Rows that are left after the filters of the source.
+pub struct Rows {
+    rows: std::vec::IntoIter<EmploymentRecordRow>,
+}
+
+impl permute::Source for Rows {
+    type Item = EmploymentRecordRow;
+    type Error = SourceError;
+
+    fn next(&mut self) -> Option<Result<Self::Item, Self::Error>> {
+        self.rows.next().map(Ok)
+    }
+}
+
+impl permute::ReadRows for EmploymentRecord {
+    type Rows = Rows;
+
+    fn read_rows(&self) -> Result<Rows, SourceError> {
+        let rows = vec![
+            row("SID000001", date(2019, 1, 14)?, None, 52_000)?,
+            row(
+                "SID000002",
+                date(2019, 1, 21)?,
+                Some(date(2019, 6, 30)?),
+                61_500,
+            )?,
+            row("SID000003", date(2019, 3, 4)?, None, 48_250)?,
+        ];
+
+        let date_from = *self.date_from();
+        let date_to = *self.date_to();
+        let exclude_terminations = self.exclude_terminations().unwrap_or(false);
+        let rows: Vec<_> = rows
+            .into_iter()
+            .filter(|v| date_from.map_or(true, |from| v.hire_date >= from))
+            .filter(|v| date_to.map_or(true, |to| v.hire_date < to))
+            .filter(|v| !exclude_terminations || v.termination_date.is_none())
+            .collect();
+        Ok(Rows {
+            rows: rows.into_iter(),
+        })
+    }
+}
+
+fn date(year: i32, month: u32, day: u32) -> Result<NaiveDate, SourceError> {
+    NaiveDate::from_ymd_opt(year, month, day).ok_or_else(|| SourceError::new("Invalid date"))
+}
+
+fn row(
+    employee_id: &str,
+    hire_date: NaiveDate,
+    termination_date: Option<NaiveDate>,
+    dollars: u32,
+) -> Result<EmploymentRecordRow, SourceError> {
+    let salary = Monetary::new(dollars, 0).map_err(|_| SourceError::new("Invalid salary"))?;
+    Ok(EmploymentRecordRow {
+        employee_id: employee_id.to_owned(),
+        hire_date,
+        termination_date,
+        salary,
+        meta: None,
+    })
+}
@@ -1,5 +1,6 @@
 //! Example of integrated Rust module into the project.
 
+#[derive(Debug)]
 pub struct Monetary {
     dollar: u32,
     cent: u8,
@@ -27,3 +28,9 @@ impl Monetary {
         self.dollar as f64 + f64::from(self.cent) / 100.0
     }
 }
+
+impl std::fmt::Display for Monetary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{:02}", self.dollar, self.cent)
+    }
+}
@@ -1,6 +1,11 @@
+use compact_str::CompactString;
+
 /// Version 0.1 of YAML format.
 pub mod v01;
 
+/// Version 0.2 of YAML format.
+pub mod v02;
+
 /// High-level representation of the project. This validates the input data and turns
 /// it into a form that is easier to work with during compilation.
 pub mod hir;
@@ -8,6 +13,12 @@ pub mod hir;
 /// Load project from files.
 pub mod load;
 
+/// Migration of files to newer versions of YAML format.
+pub mod migrate;
+
+/// Locations of errors in the project files.
+pub mod span;
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error(transparent)]
@@ -15,4 +26,183 @@ pub enum Error {
 
     #[error(transparent)]
     Yaml(#[from] serde_yml::Error),
+
+    #[error(
+        "Invalid version `{0}`, expected `major.minor`, e.g. `{}`",
+        Version::LATEST
+    )]
+    InvalidVersion(CompactString),
+
+    #[error(
+        "Unsupported version `{0}`. Supported versions: {}",
+        Version::supported_list()
+    )]
+    UnsupportedVersion(CompactString),
+}
+
+/// Version of the YAML format, as in the `permute.version` header of each file.
+/// Files of one project can use different versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Version {
+    V01,
+    V02,
+}
+
+impl Version {
+    pub const ALL: &'static [Version] = &[Version::V01, Version::V02];
+    pub const LATEST: Version = Version::V02;
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Version::V01 => "0.1",
+            Version::V02 => "0.2",
+        }
+    }
+
+    /// Parse the version of the header. The version should be `major.minor`,
+    /// and it should be among the supported ones.
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let s = s.trim();
+        let is_number = |v: &str| !v.is_empty() && v.bytes().all(|b| b.is_ascii_digit());
+        let is_valid = s
+            .split_once('.')
+            .is_some_and(|(major, minor)| is_number(major) && is_number(minor));
+        if !is_valid {
+            return Err(Error::InvalidVersion(s.into()));
+        }
+
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|v| v.as_str() == s)
+            .ok_or_else(|| Error::UnsupportedVersion(s.into()))
+    }
+
+    /// Version of the file, read from its header without parsing the rest of the file.
+    pub fn of_file(s: &str) -> Result<Self, Error> {
+        #[derive(serde::Deserialize)]
+        struct File {
+            permute: Header,
+        }
+
+        #[derive(serde::Deserialize)]
+        struct Header {
+            version: CompactString,
+        }
+
+        let file: File = serde_yml::from_str(s)?;
+        Self::parse(&file.permute.version)
+    }
+
+    fn supported_list() -> String {
+        let list: Vec<_> = Self::ALL.iter().map(|v| v.as_str()).collect();
+        list.join(", ")
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Main file in one of the supported versions.
+#[derive(Debug)]
+pub enum Main {
+    V01(v01::Main),
+    V02(v02::Main),
+}
+
+/// Sink file in one of the supported versions.
+#[derive(Debug)]
+pub enum Sink {
+    V01(v01::Sink),
+    V02(v02::Sink),
+}
+
+/// Source file in one of the supported versions.
+#[derive(Debug)]
+pub enum Source {
+    V01(v01::Source),
+    V02(v02::Source),
+}
+
+impl Main {
+    /// Load the main file from the given path, with the schema of its version.
+    pub fn load_from_path(path: &std::path::Path) -> Result<Self, Error> {
+        let s = std::fs::read_to_string(path)?;
+        Self::load_from_str(&s)
+    }
+
+    /// Load the main file from the given string slice, with the schema of its version.
+    pub fn load_from_str(s: &str) -> Result<Self, Error> {
+        match Version::of_file(s)? {
+            Version::V01 => Ok(Main::V01(v01::Main::load_from_str(s)?)),
+            Version::V02 => Ok(Main::V02(v02::Main::load_from_str(s)?)),
+        }
+    }
+
+    pub fn version(&self) -> Version {
+        match self {
+            Main::V01(_) => Version::V01,
+            Main::V02(_) => Version::V02,
+        }
+    }
+}
+
+impl Sink {
+    pub fn version(&self) -> Version {
+        match self {
+            Sink::V01(_) => Version::V01,
+            Sink::V02(_) => Version::V02,
+        }
+    }
+}
+
+impl Source {
+    pub fn version(&self) -> Version {
+        match self {
+            Source::V01(_) => Version::V01,
+            Source::V02(_) => Version::V02,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_version() {
+        assert_eq!(Version::parse("0.1").unwrap(), Version::V01);
+        assert_eq!(Version::parse(" 0.1 ").unwrap(), Version::V01);
+        assert_eq!(Version::parse("0.2").unwrap(), Version::V02);
+
+        for v in ["0.3", "1.0", "0.10", "00.1"] {
+            let err = Version::parse(v).unwrap_err();
+            assert!(matches!(err, Error::UnsupportedVersion(_)), "{v}: {err}");
+        }
+        for v in ["banana", "", "1", "0.1.0", "0.", ".1", "-0.1", "0.1b"] {
+            let err = Version::parse(v).unwrap_err();
+            assert!(matches!(err, Error::InvalidVersion(_)), "{v}: {err}");
+        }
+    }
+
+    #[test]
+    fn version_of_file() {
+        let file = |version: &str| {
+            format!("permute:\n  version: {version}\n  type: main\n  future_field: 1\nname: N\n")
+        };
+        assert_eq!(Version::of_file(&file("0.1")).unwrap(), Version::V01);
+        assert_eq!(Version::of_file(&file("\"0.1\"")).unwrap(), Version::V01);
+        assert_eq!(Version::of_file(&file("0.2")).unwrap(), Version::V02);
+
+        let err = Main::load_from_str(&file("0.3")).unwrap_err();
+        assert!(
+            matches!(&err, Error::UnsupportedVersion(v) if v == "0.3"),
+            "{err}"
+        );
+        let err = Main::load_from_str(&file("banana")).unwrap_err();
+        assert!(matches!(err, Error::InvalidVersion(_)), "{err}");
+    }
 }
@@ -1,4 +1,4 @@
-use compact_str::{CompactString, ToCompactString};
+use compact_str::{format_compact, CompactString, ToCompactString};
 use hashbrown::HashMap;
 use log::*;
 use smallvec::SmallVec;
@@ -29,6 +29,37 @@ pub struct Main {
 
     /// Use clauses for the main file. These are used to import types from other modules.
     uses: Vec<syn::UseTree>,
+
+    /// What to do with the rows of the source bindings that fail the column checks.
+    check_policies: Vec<(IdentId, CheckPolicy<IdentId>)>,
+
+    /// Functions of the Rust files that are exempt from the analysis checks.
+    allow: compile::Allow,
+}
+
+/// What to do with a source row that fails the column checks.
+/// Parameter is the binding of the sink for rejected rows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CheckPolicy<B> {
+    /// Stop the pipe.
+    #[default]
+    Abort,
+
+    /// Skip the row with a warning.
+    Skip,
+
+    /// Put the row into the sink binding.
+    Reject(B),
+}
+
+impl<B> CheckPolicy<B> {
+    pub fn map<U>(self, f: impl FnOnce(B) -> U) -> CheckPolicy<U> {
+        match self {
+            CheckPolicy::Abort => CheckPolicy::Abort,
+            CheckPolicy::Skip => CheckPolicy::Skip,
+            CheckPolicy::Reject(b) => CheckPolicy::Reject(f(b)),
+        }
+    }
 }
 
 impl Main {
@@ -61,12 +92,49 @@ impl Main {
     pub fn uses(&self) -> impl Iterator<Item = &syn::UseTree> {
         self.uses.iter()
     }
+
+    pub fn allow(&self) -> &compile::Allow {
+        &self.allow
+    }
+
+    /// Resolve the type of a binding through the use clauses of the main file,
+    /// e.g. with `use: [module1::FileName]` type `FileName` is `module1::FileName`.
+    /// Types that are not imported are returned as they are.
+    pub fn resolve_ty(&self, ty: &str) -> CompactString {
+        let (first, rest) = match ty.split_once("::") {
+            Some((first, rest)) => (first, Some(rest)),
+            None => (ty, None),
+        };
+
+        let mut imports = Vec::new();
+        for tree in &self.uses {
+            use_imports(tree, "", &mut imports);
+        }
+        let Some((_, path)) = imports.iter().find(|(name, _)| name == first) else {
+            return ty.into();
+        };
+
+        let path = path.strip_prefix("crate::").unwrap_or(path);
+        trace!("Type `{ty}` is imported from `{path}`");
+        match rest {
+            Some(rest) => format_compact!("{path}::{rest}"),
+            None => path.into(),
+        }
+    }
+
+    /// Check policies of the source bindings, by binding names.
+    pub fn check_policies(&self) -> impl Iterator<Item = (&str, CheckPolicy<&str>)> {
+        self.check_policies.iter().map(move |(src, policy)| {
+            let src = self.idents[*src as usize].as_str();
+            (src, policy.map(|sink| self.idents[sink as usize].as_str()))
+        })
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum MainError {
     #[error("Failed to parse use clause. {0}")]
-    UseParse(syn::Error),
+    UseParse(syn::Error, CompactString),
 
     #[error("Invalid project name. `{0}`")]
     InvalidName(CompactString),
@@ -81,19 +149,51 @@ pub enum MainError {
     },
 
     #[error("Failed to parse binding type. {0}")]
-    BindingTypeParse(syn::Error),
+    BindingTypeParse(syn::Error, CompactString),
+
+    #[error("Binding `{0}` not found for the check policy")]
+    PolicyBindingNotFound(CompactString),
+}
+
+impl MainError {
+    /// Error in the embedded Rust code, with the code as written in the file.
+    pub fn rust_snippet(&self) -> Option<(&syn::Error, &str)> {
+        match self {
+            MainError::UseParse(e, s) | MainError::BindingTypeParse(e, s) => Some((e, s)),
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<super::Main> for Main {
+    type Error = Vec<MainError>;
+
+    fn try_from(input: super::Main) -> Result<Self, Self::Error> {
+        match input {
+            super::Main::V01(v) => v.try_into(),
+            super::Main::V02(v) => v.try_into(),
+        }
+    }
 }
 
 impl TryFrom<super::v01::Main> for Main {
     type Error = Vec<MainError>;
 
     fn try_from(input: super::v01::Main) -> Result<Self, Self::Error> {
+        super::v02::Main::from(input).try_into()
+    }
+}
+
+impl TryFrom<super::v02::Main> for Main {
+    type Error = Vec<MainError>;
+
+    fn try_from(input: super::v02::Main) -> Result<Self, Self::Error> {
         info!("Making main file HIR");
         let mut errors = Vec::new();
 
         let uses = parse_uses(input.header.uses)
             .map_err(|e| {
-                errors.extend(e.into_iter().map(MainError::UseParse));
+                errors.extend(e.into_iter().map(|(e, s)| MainError::UseParse(e, s)));
             })
             .unwrap_or_default();
 
@@ -156,10 +256,44 @@ impl TryFrom<super::v01::Main> for Main {
             pipes
         };
 
+        let check_policies = {
+            use super::v01;
+
+            let find_ident = |name: &str| {
+                idents
+                    .iter()
+                    .position(|i| i.as_str() == name)
+                    .map(|v| v as IdentId)
+                    .ok_or_else(|| MainError::PolicyBindingNotFound(name.into()))
+            };
+
+            let mut policies = Vec::with_capacity(input.on_check_fail.len());
+            for (src, policy) in input.on_check_fail {
+                let policy = match policy {
+                    v01::CheckPolicy::Keyword(v01::CheckPolicyKeyword::Abort) => {
+                        Ok(CheckPolicy::Abort)
+                    }
+                    v01::CheckPolicy::Keyword(v01::CheckPolicyKeyword::Skip) => {
+                        Ok(CheckPolicy::Skip)
+                    }
+                    v01::CheckPolicy::Reject { reject } => {
+                        find_ident(&reject).map(CheckPolicy::Reject)
+                    }
+                };
+                match find_ident(&src).and_then(|src| policy.map(|policy| (src, policy))) {
+                    Ok(v) => policies.push(v),
+                    Err(e) => errors.push(e),
+                }
+            }
+            debug!("Parsed {} check policies", policies.len());
+            policies
+        };
+
         let bindings = {
             let mut bindings = HashMap::with_capacity(input.bindings.bindings.len());
             for (ident, binding) in input.bindings.bindings {
-                let ty = syn::parse_str(&binding.ty.0).map_err(MainError::BindingTypeParse);
+                let ty = syn::parse_str(&binding.ty.0)
+                    .map_err(|e| MainError::BindingTypeParse(e, binding.ty.0.to_owned()));
                 let ty = match ty {
                     Ok(t) => t,
                     Err(e) => {
@@ -187,6 +321,13 @@ impl TryFrom<super::v01::Main> for Main {
                 bindings,
                 idents,
                 uses,
+                check_policies,
+                allow: compile::Allow {
+                    loops: input.allow.loops,
+                    recursion: input.allow.recursion,
+                    apis: input.allow.apis,
+                    crates: input.allow.crates,
+                },
             })
         } else {
             Err(errors)
@@ -210,6 +351,12 @@ impl<'main> Pipe<'main> {
     }
 }
 
+impl std::fmt::Display for Pipe<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} -> {}", self.input, self.output)
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 #[error("Failed to parse pipe. Expected `input -> output`")]
 pub struct StringToPipeParseError(String);
@@ -255,16 +402,35 @@ impl MainBinding {
         &self.ty
     }
 
-    /// Returns the type as a string.
+    /// Returns the type as a string. Plain paths are written relative to the crate root
+    /// without whitespace, e.g. `crate::ee_to_csv::Ee2Csv` becomes `ee_to_csv::Ee2Csv`,
+    /// which is how the items are named in the context.
     pub fn ty_str(&self) -> CompactString {
         use quote::ToTokens;
+
+        if let syn::Type::Path(path) = &self.ty {
+            let is_plain = path.qself.is_none()
+                && path.path.leading_colon.is_none()
+                && path.path.segments.iter().all(|s| s.arguments.is_none());
+            if is_plain {
+                let mut s = CompactString::default();
+                let segments = path.path.segments.iter().map(|s| s.ident.to_string());
+                for segment in segments.skip_while(|s| s == "crate") {
+                    if !s.is_empty() {
+                        s.push_str("::");
+                    }
+                    s.push_str(&segment);
+                }
+                return s;
+            }
+        }
         self.ty.to_token_stream().to_compact_string()
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Sink {
-    /// Name of the sink. This is a valid Rust identifier.
+    /// Name of the sink. This is a path of valid Rust identifiers, like `module1::FileName`.
     pub(crate) name: CompactString,
 
     /// Explanation for the sink. May be empty.
@@ -305,7 +471,7 @@ impl Sink {
 
 impl ToNamed for Sink {
     fn to_named(this: Unnamed<Self>, name: String) -> Result<Self, NameError<Self>> {
-        if name.is_valid_ident() {
+        if name.is_valid_path() {
             Ok(Sink {
                 name: CompactString::from(name),
                 ..this.0
@@ -319,7 +485,7 @@ impl ToNamed for Sink {
 #[derive(Debug, thiserror::Error)]
 pub enum SinkError {
     #[error("Failed to parse use clause. {0}")]
-    Uses(syn::Error),
+    Uses(syn::Error, CompactString),
 
     #[error("Failed to parse parameter type. {0}")]
     TypeParse(syn::Error, CompactString),
@@ -331,10 +497,41 @@ pub enum SinkError {
     CheckParse(syn::Error, CompactString),
 }
 
+impl SinkError {
+    /// Error in the embedded Rust code, with the code as written in the file.
+    pub fn rust_snippet(&self) -> (&syn::Error, &str) {
+        match self {
+            SinkError::Uses(e, s)
+            | SinkError::TypeParse(e, s)
+            | SinkError::DefaultParse(e, s)
+            | SinkError::CheckParse(e, s) => (e, s),
+        }
+    }
+}
+
+impl TryFrom<super::Sink> for Unnamed<Sink> {
+    type Error = Vec<SinkError>;
+
+    fn try_from(input: super::Sink) -> Result<Self, Self::Error> {
+        match input {
+            super::Sink::V01(v) => v.try_into(),
+            super::Sink::V02(v) => v.try_into(),
+        }
+    }
+}
+
 impl TryFrom<super::v01::Sink> for Unnamed<Sink> {
     type Error = Vec<SinkError>;
 
     fn try_from(input: super::v01::Sink) -> Result<Self, Self::Error> {
+        super::v02::Sink::from(input).try_into()
+    }
+}
+
+impl TryFrom<super::v02::Sink> for Unnamed<Sink> {
+    type Error = Vec<SinkError>;
+
+    fn try_from(input: super::v02::Sink) -> Result<Self, Self::Error> {
         info!("Making sink file HIR");
         let mut errors = Vec::new();
 
@@ -349,6 +546,7 @@ impl TryFrom<super::v01::Sink> for Unnamed<Sink> {
                     .map(|define| Check {
                         explain: check.explain().unwrap_or_default().to_owned(),
                         define,
+                        none_passes: check.on_none() == super::v01::OnNone::Pass,
                     })
             }};
         }
@@ -380,15 +578,15 @@ impl TryFrom<super::v01::Sink> for Unnamed<Sink> {
 
         let uses = parse_uses(input.header.uses)
             .map_err(|e| {
-                errors.extend(e.into_iter().map(SinkError::Uses));
+                errors.extend(e.into_iter().map(|(e, s)| SinkError::Uses(e, s)));
             })
             .unwrap_or_default();
 
         let explain = input.explain.unwrap_or_default();
 
         let params = {
-            let mut params = Vec::with_capacity(input.param.len());
-            for (name, param) in input.param {
+            let mut params = Vec::with_capacity(input.params.len());
+            for (name, param) in input.params {
                 let ty = syn::parse_str(&param.ty.0)
                     .map_err(|e| SinkError::TypeParse(e, param.ty.0.to_owned()));
                 let ty = match ty {
@@ -447,13 +645,16 @@ impl TryFrom<super::v01::Sink> for Unnamed<Sink> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Check {
     /// Explanation for the check. May be empty.
     pub(crate) explain: String,
 
     /// The expression that is used to check the condition.
     pub(crate) define: syn::Expr,
+
+    /// Whether the check passes when `?` in the expression meets `None`.
+    pub(crate) none_passes: bool,
 }
 
 impl Check {
@@ -464,9 +665,13 @@ impl Check {
     pub fn define(&self) -> &syn::Expr {
         &self.define
     }
+
+    pub fn none_passes(&self) -> bool {
+        self.none_passes
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SinkParam {
     /// Type of the parameter.
     pub(crate) ty: syn::Type,
@@ -507,9 +712,9 @@ impl SinkParam {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Source {
-    /// Name of the source. This is a valid Rust identifier.
+    /// Name of the source. This is a path of valid Rust identifiers, like `module1::FileName`.
     pub(crate) name: CompactString,
 
     /// Explanation for the source. May be empty.
@@ -572,7 +777,7 @@ impl<T: ToNamed> Unnamed<T> {
 }
 
 #[derive(Debug, thiserror::Error)]
-#[error("Name is not a valid identifier. `{0}`")]
+#[error("Name is not a valid identifier or path of identifiers. `{0}`")]
 pub struct NameError<T>(String, T);
 
 pub trait ToNamed: Sized + Debug {
@@ -581,7 +786,7 @@ pub trait ToNamed: Sized + Debug {
 
 impl ToNamed for Source {
     fn to_named(this: Unnamed<Source>, name: String) -> Result<Source, NameError<Source>> {
-        if name.is_valid_ident() {
+        if name.is_valid_path() {
             Ok(Source {
                 name: CompactString::from(name),
                 ..this.0
@@ -604,7 +809,30 @@ pub enum SourceError {
     CheckParse(syn::Error, CompactString),
 
     #[error("Failed to parse use clause. {0}")]
-    Uses(syn::Error),
+    Uses(syn::Error, CompactString),
+}
+
+impl SourceError {
+    /// Error in the embedded Rust code, with the code as written in the file.
+    pub fn rust_snippet(&self) -> (&syn::Error, &str) {
+        match self {
+            SourceError::TypeParse(e, s)
+            | SourceError::DefaultParse(e, s)
+            | SourceError::CheckParse(e, s)
+            | SourceError::Uses(e, s) => (e, s),
+        }
+    }
+}
+
+impl TryFrom<super::Source> for Unnamed<Source> {
+    type Error = Vec<SourceError>;
+
+    fn try_from(input: super::Source) -> Result<Self, Self::Error> {
+        match input {
+            // The schema of the source files is the same in both versions.
+            super::Source::V01(v) | super::Source::V02(v) => v.try_into(),
+        }
+    }
 }
 
 impl TryFrom<super::v01::Source> for Unnamed<Source> {
@@ -625,6 +853,7 @@ impl TryFrom<super::v01::Source> for Unnamed<Source> {
                     .map(|define| Check {
                         explain: check.explain().unwrap_or_default().to_owned(),
                         define,
+                        none_passes: check.on_none() == super::v01::OnNone::Pass,
                     })
             }};
         }
@@ -738,7 +967,7 @@ impl TryFrom<super::v01::Source> for Unnamed<Source> {
 
         let uses = parse_uses(input.header.uses)
             .map_err(|e| {
-                errors.extend(e.into_iter().map(SourceError::Uses));
+                errors.extend(e.into_iter().map(|(e, s)| SourceError::Uses(e, s)));
             })
             .unwrap_or_default();
 
@@ -758,7 +987,7 @@ impl TryFrom<super::v01::Source> for Unnamed<Source> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SourceFilter {
     /// Name of the filter. This is a valid Rust identifier.
     pub(crate) name: CompactString,
@@ -799,7 +1028,7 @@ impl SourceFilter {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SourceColumn {
     /// Name of the column. This is a valid Rust identifier.
     pub(crate) name: CompactString,
@@ -833,8 +1062,11 @@ impl SourceColumn {
     }
 }
 
-trait StringExt {
+pub(crate) trait StringExt {
     fn is_valid_ident(&self) -> bool;
+
+    /// Whether this is a path of identifiers, like `module1::FileName`.
+    fn is_valid_path(&self) -> bool;
 }
 
 impl StringExt for str {
@@ -842,15 +1074,53 @@ impl StringExt for str {
         let ident: Result<syn::Ident, _> = syn::parse_str(self);
         ident.is_ok()
     }
+
+    fn is_valid_path(&self) -> bool {
+        self.split("::").all(|v| v.is_valid_ident())
+    }
 }
 
-fn parse_uses(input: Vec<CompactString>) -> Result<Vec<syn::UseTree>, Vec<syn::Error>> {
+/// Names that the use tree imports, along with the paths they refer to.
+/// Glob imports are skipped.
+fn use_imports(tree: &syn::UseTree, prefix: &str, out: &mut Vec<(CompactString, CompactString)>) {
+    use syn::UseTree as T;
+
+    let path_of = |ident: &syn::Ident| {
+        if ident == "self" {
+            prefix.into()
+        } else if prefix.is_empty() {
+            ident.to_compact_string()
+        } else {
+            format_compact!("{prefix}::{ident}")
+        }
+    };
+    match tree {
+        T::Path(path) => use_imports(&path.tree, &path_of(&path.ident), out),
+        T::Name(name) => {
+            let path = path_of(&name.ident);
+            let name = path.rsplit("::").next().unwrap_or_default().into();
+            out.push((name, path));
+        }
+        T::Rename(rename) => out.push((rename.rename.to_compact_string(), path_of(&rename.ident))),
+        T::Glob(_) => {}
+        T::Group(group) => {
+            for tree in &group.items {
+                use_imports(tree, prefix, out);
+            }
+        }
+    }
+}
+
+/// Parse the use clauses. Each error comes with the clause it is found in.
+fn parse_uses(
+    input: Vec<CompactString>,
+) -> Result<Vec<syn::UseTree>, Vec<(syn::Error, CompactString)>> {
     let mut errors = Vec::new();
     let mut uses = Vec::with_capacity(input.len());
     for usage in input {
         match syn::parse_str(&usage) {
             Ok(u) => uses.push(u),
-            Err(err) => errors.push(err),
+            Err(err) => errors.push((err, usage)),
         }
     }
     if errors.is_empty() {
@@ -872,6 +1142,23 @@ mod tests {
         println!("{main:#?}");
     }
 
+    #[test]
+    fn resolve_ty() {
+        let mut main = Main::try_from(main()).unwrap();
+        main.uses = [
+            "crate::module1::{FileName, self as m1}",
+            "module2::Other as O",
+        ]
+        .into_iter()
+        .map(|v| syn::parse_str(v).unwrap())
+        .collect();
+
+        assert_eq!(main.resolve_ty("FileName"), "module1::FileName");
+        assert_eq!(main.resolve_ty("m1::Item"), "module1::Item");
+        assert_eq!(main.resolve_ty("O"), "module2::Other");
+        assert_eq!(main.resolve_ty("module3::Item"), "module3::Item");
+    }
+
     #[test]
     fn test_source() {
         let source = Unnamed::<Source>::try_from(source()).unwrap();
@@ -1,17 +1,23 @@
 use std::path::PathBuf;
 
-use compact_str::CompactString;
+use compact_str::{CompactString, ToCompactString};
 use log::*;
 use smallvec::{smallvec, SmallVec};
 
 use crate::context::{Ctx, ParamKey};
-use crate::yaml::hir;
-use crate::yaml::v01;
+use crate::yaml::{self, hir, span::Span, v01, v02};
 
 /// Load input files from a project directory and create the context with them.
 pub struct LoadProjectDir<'a> {
     /// Path to the directory with input files.
     pub path: &'a std::path::Path,
+
+    /// Main file to load, relative to [Self::path]. If not set, it is
+    /// [Self::MAIN_FILE_NAME]. Ignored by [Self::run_all].
+    pub main: Option<&'a std::path::Path>,
+
+    /// Configuration of the compiler that analyzes the Rust files of the project.
+    pub analyze: compile::AnalyzeConfig,
 }
 
 /// Error during loading of the project.
@@ -32,6 +38,21 @@ pub enum LoadError {
     #[error("Error listing other YAML files. {0}")]
     DirList(std::io::Error),
 
+    #[error(
+        "Files resolve to the same item `{path}`: {}",
+        files.iter().map(|v| v.display().to_string()).collect::<Vec<_>>().join(", ")
+    )]
+    PathConflict {
+        path: CompactString,
+        files: Vec<PathBuf>,
+    },
+
+    #[error("Item `{path}` of file {} conflicts with a Rust item of the same path", file.display())]
+    RustPathConflict { path: CompactString, file: PathBuf },
+
+    #[error("File {} cannot be an item, its path segments should be identifiers", .0.display())]
+    InvalidItemPath(PathBuf),
+
     #[error(transparent)]
     MainHir(#[from] hir::MainError),
 
@@ -56,8 +77,202 @@ pub enum LoadError {
     #[error(transparent)]
     AddParam(#[from] crate::context::AddParamErr),
 
+    #[error(transparent)]
+    AddInit(#[from] crate::context::AddInitErr),
+
+    #[error("Invalid pipe `{pipe}`. {err}")]
+    AddPipe {
+        pipe: CompactString,
+        err: crate::context::AddPipeErr,
+    },
+
+    #[error(transparent)]
+    Validate(#[from] crate::context::validate::ValidateErr),
+
+    #[error("Invalid check policy of `{binding}`. {err}")]
+    CheckPolicy {
+        binding: CompactString,
+        err: crate::context::CheckPolicyErr,
+    },
+
     #[error("Error loading Rust files. {0}")]
     RustError(#[from] compile::ProjectContentError),
+
+    #[error("{0}")]
+    RustDiagnostic(compile::diagnostic::Diagnostic),
+
+    #[error("{0}\n{1}")]
+    Located(Box<LoadError>, Span),
+
+    #[error("Error in file {}. {0}", .1.display())]
+    InFile(Box<LoadError>, PathBuf),
+}
+
+impl LoadError {
+    /// Location of the error in a project file, if it is known.
+    pub fn span(&self) -> Option<&Span> {
+        match self {
+            LoadError::Located(_, span) => Some(span),
+            _ => None,
+        }
+    }
+
+    /// File of the project that the error is found in. Paths of the Rust files
+    /// are relative to the project directory.
+    pub fn file(&self) -> Option<&std::path::Path> {
+        match self {
+            LoadError::Located(_, span) => Some(&span.path),
+            LoadError::InFile(_, path)
+            | LoadError::Yaml(_, path)
+            | LoadError::InvalidItemPath(path)
+            | LoadError::RustPathConflict { file: path, .. }
+            | LoadError::MainFileError(MainLoadError::NotFound(path))
+            | LoadError::MainFileError(MainLoadError::NotMain(path)) => Some(path),
+            LoadError::PathConflict { files, .. } => files.first().map(PathBuf::as_path),
+            LoadError::RustDiagnostic(d) => d.loc.as_ref().map(|v| v.file.as_str().as_ref()),
+            _ => None,
+        }
+    }
+
+    /// Line and column of the error in its [file](Self::file), both 1-based.
+    pub fn position(&self) -> Option<(usize, usize)> {
+        match self {
+            LoadError::Located(_, span) => Some((span.line, span.col)),
+            LoadError::RustDiagnostic(d) => d.loc.as_ref().map(|v| (v.line, v.col)),
+            _ => None,
+        }
+    }
+
+    /// Attach the file to the error, with the location in the file if the error has
+    /// one: the position of YAML errors and the position in the embedded Rust code.
+    fn locate(self, path: &std::path::Path, texts: &Texts) -> Self {
+        let Some(text) = texts.get(path) else {
+            return self.in_file(path);
+        };
+        let yaml_span = |e: &crate::yaml::Error| match e {
+            crate::yaml::Error::Yaml(e) => e
+                .location()
+                .map(|v| Span::at(path, text, v.line(), v.column(), 1)),
+            _ => None,
+        };
+        let rust_span = |(e, snippet)| Span::of_rust_snippet(path, text, snippet, e);
+
+        let span = match &self {
+            LoadError::Yaml(e, _) => yaml_span(e),
+            LoadError::MainFileError(MainLoadError::LoadError(e)) => yaml_span(e),
+            LoadError::MainHir(e) => e.rust_snippet().and_then(rust_span),
+            LoadError::SinkHir(e) => rust_span(e.rust_snippet()),
+            LoadError::SourceHir(e) => rust_span(e.rust_snippet()),
+            _ => None,
+        };
+        match span {
+            Some(span) => LoadError::Located(Box::new(self), span),
+            None => self.in_file(path),
+        }
+    }
+
+    fn in_file(self, path: &std::path::Path) -> Self {
+        if self.file().is_some() {
+            self
+        } else {
+            LoadError::InFile(Box::new(self), path.to_owned())
+        }
+    }
+}
+
+/// Texts of the loaded YAML files, to locate the errors in.
+type Texts = std::collections::HashMap<PathBuf, String>;
+
+/// Name of the context if the main file cannot be loaded.
+const PLACEHOLDER_NAME: &str = "Unnamed";
+
+/// Item in place of the one of a file that failed to load, so that the Rust files
+/// that use it still resolve the name. The row type is added too, in case the file
+/// was a source.
+fn placeholder_item(name: &str) -> (CompactString, proc_macro2::TokenStream) {
+    let (module, name) = name.rsplit_once("::").unwrap_or(("", name));
+    let row = quote::format_ident!("{}", crate::context::row_name(name).as_str());
+    let name = quote::format_ident!("{name}");
+    (
+        module.into(),
+        quote::quote! {
+            pub struct #name;
+            pub struct #row;
+        },
+    )
+}
+
+/// Whether the diagnostic names a placeholder of an item that failed to load, like
+/// `EmploymentRecord` or `EmploymentRecordRow`. The placeholders have no fields and
+/// methods, so these diagnostics only follow from the YAML errors.
+/// The diagnostics point into the Rust files that use the placeholders, not into the
+/// placeholders, so they are matched by the names. Only whole identifiers match, so
+/// a failed `Csv` keeps the errors about `CsvWriter` or `Csv2`.
+fn mentions_failed(
+    diagnostic: &compile::diagnostic::Diagnostic,
+    failed: &std::collections::BTreeSet<CompactString>,
+) -> bool {
+    let placeholders: SmallVec<[CompactString; 8]> = failed
+        .iter()
+        .flat_map(|item| {
+            let name = item.rsplit("::").next().unwrap_or(item);
+            [name.into(), crate::context::row_name(name)]
+        })
+        .collect();
+    let mentions = |text: &str| {
+        text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .any(|ident| placeholders.iter().any(|v| v == ident))
+    };
+    mentions(&diagnostic.message) || diagnostic.children.iter().any(|v| mentions(&v.message))
+}
+
+/// Context with the YAML sinks and sources, and a placeholder name until the main file
+/// names it.
+fn items_ctx(items: &Items, errors: &mut SmallVec<[LoadError; 32]>) -> Ctx {
+    let mut ctx = Ctx::new(PLACEHOLDER_NAME.into(), None).expect("placeholder name is not empty");
+    info!("Add sinks to the context");
+    for sink in &items.sinks {
+        if let Err(e) = ctx.add_sink(sink.clone()) {
+            error!("Error adding sink to the context. {e}");
+            errors.push(e.into())
+        }
+    }
+    info!("Add sources to the context");
+    trace!("Sources count: {}", items.srcs.len());
+    for src in &items.srcs {
+        if let Err(e) = ctx.add_source(src.clone()) {
+            error!("Error adding source to the context. {e}");
+            errors.push(e.into())
+        }
+    }
+    ctx
+}
+
+/// Rust items of the YAML sinks and sources of the context, with placeholders for
+/// the items that failed to load.
+fn gen_items(
+    ctx: &Ctx,
+    failed: &std::collections::BTreeSet<CompactString>,
+) -> Vec<(CompactString, proc_macro2::TokenStream)> {
+    use crate::context::codegen;
+
+    info!("Generate Rust code for YAML sources and sinks");
+    let mut items = codegen::gen_yaml_items(ctx);
+    for (_, tokens) in &items {
+        codegen::trace_printall(tokens);
+    }
+    items.extend(failed.iter().map(|name| placeholder_item(name)));
+    items
+}
+
+/// What the Rust files of the project are analyzed with, see
+/// [LoadProjectDir::analysis_input].
+pub struct AnalysisInput {
+    /// Rust items generated from the YAML sinks and sources, by their modules.
+    pub generated: Vec<(CompactString, proc_macro2::TokenStream)>,
+
+    /// Allow lists of the selected main file.
+    pub allow: compile::Allow,
 }
 
 /// Error during loading of the main file.
@@ -66,6 +281,12 @@ pub enum MainLoadError {
     #[error("Main file cannot be found in the project directory ({0})")]
     NotFound(PathBuf),
 
+    #[error("File {0} is not a main file, it should have `type: main`")]
+    NotMain(PathBuf),
+
+    #[error("No main files in the project directory ({0}), a main file has `type: main`")]
+    NoMainFiles(PathBuf),
+
     #[error("Error loading main file. {0}")]
     LoadError(#[from] crate::yaml::Error),
 }
@@ -73,131 +294,341 @@ pub enum MainLoadError {
 impl LoadProjectDir<'_> {
     pub const MAIN_FILE_NAME: &'static str = "main.yaml";
 
+    /// Load the project with the selected main file. All problems found in the project
+    /// are returned together, grouped by file and ordered by their positions in the files.
+    /// Files that fail to load are replaced with placeholders, and the errors that only
+    /// follow from them, like bindings to the missing items, are not reported.
+    /// Other main files of the project are skipped.
     pub fn run(self) -> Result<Ctx, Vec<LoadError>> {
         info!("Load project into context");
-        const EXPECT_NO_ERR: &str = "should be present since there are no errors";
 
-        let mut errors = SmallVec::<[_; 32]>::new();
+        // Nothing can be loaded without the project directory.
         self.validate_path().map_err(vec)?;
 
-        let main = self.load_main().map_err(|e| errors.push(e.into())).ok();
-        let (sinks, srcs) = self.load_sinks_and_sources(&mut errors);
-
-        if !errors.is_empty() {
-            return Err(errors.into_vec());
+        let mut errors = SmallVec::<[_; 32]>::new();
+        let mut texts = Texts::new();
+        let mut items = self.load_items(&mut errors, &mut texts);
+
+        let (main_path, main) = self.take_main(&mut items, &texts, &mut errors);
+        let failed = self.failed_items(&errors, Some(&main_path));
+        let main = main.and_then(|main| Self::main_hir(&main_path, main, &texts, &mut errors));
+        let mut ctx = items_ctx(&items, &mut errors);
+        // Without the main file its allow lists are unknown, and nothing is allowed.
+        let default_allow = compile::Allow::default();
+        let allow = main.as_ref().map_or(&default_allow, |v| v.allow());
+        let rust = self.add_rust(&mut ctx, allow, &failed, &mut errors);
+        let ctx = Self::load_main(ctx, &main_path, main, &failed, rust, &texts, &mut errors);
+        if errors.is_empty() {
+            Ok(ctx)
+        } else {
+            Err(self.sorted(errors))
         }
-        let main = main.expect(EXPECT_NO_ERR);
+    }
 
-        info!("Translate main file into HIR");
-        let main = hir::Main::try_from(main)
-            .map_err(|e| errors.extend(e.into_iter().map(Into::into)))
-            .ok();
+    /// Load a context for each main file of the project, that is each YAML file with
+    /// `type: main`, in the order of their paths. All of them share the sinks and sources
+    /// of the project, and the Rust files are analyzed once for each distinct allow list
+    /// of the main files. Errors are returned as by [Self::run], for all the main files.
+    pub fn run_all(self) -> Result<Vec<(PathBuf, Ctx)>, Vec<LoadError>> {
+        info!("Load project into contexts of all main files");
+        self.validate_path().map_err(vec)?;
 
-        macro_rules! hir_src_sink {
-            ($src_or_sink:expr, $ty:ident) => {
-                $src_or_sink
-                    .into_iter()
-                    .map(|v| {
-                        let s = v.rust_path_string(self.path);
-                        let (_, v) = v.unwrap();
-                        hir::$ty::try_from(v).map(|v| v.to_named(s))
-                    })
-                    .filter_map(|sink| {
-                        sink.map_err(|e| errors.extend(e.into_iter().map(Into::into)))
-                            .ok()
-                    })
-            };
+        let mut errors = SmallVec::<[_; 32]>::new();
+        let mut texts = Texts::new();
+        let mut items = self.load_items(&mut errors, &mut texts);
+        if items.mains.is_empty() && errors.is_empty() {
+            error!("No main files in the project");
+            errors.push(MainLoadError::NoMainFiles(self.path.into()).into());
         }
 
-        info!(
-            "Translate to HIR sinks and sources, also populate error array if there are any found"
-        );
-        let sinks: SmallVec<[_; 32]> = hir_src_sink!(sinks, UnnamedSink).collect();
-        let srcs: SmallVec<[_; 32]> = hir_src_sink!(srcs, UnnamedSource).collect();
+        let failed = self.failed_items(&errors, None);
+        let ctx = items_ctx(&items, &mut errors);
+
+        // Contexts with the Rust items, by the allow lists they are analyzed with.
+        let default_allow = compile::Allow::default();
+        let mut analyzed: Vec<(compile::Allow, Ctx, bool)> = Vec::new();
+        let mut not_compiled = None;
+        let mut ctxs = Vec::with_capacity(items.mains.len());
+        for (path, main) in std::mem::take(&mut items.mains) {
+            info!("Load context of main file {}", path.display());
+            let main = Self::main_hir(&path, main, &texts, &mut errors);
+            let allow = main.as_ref().map_or(&default_allow, |v| v.allow());
+            let found = analyzed.iter().position(|(v, ..)| v == allow);
+            let idx = match found.or(not_compiled) {
+                Some(idx) => idx,
+                None => {
+                    let from = errors.len();
+                    let mut ctx = ctx.clone();
+                    let rust = self.add_rust(&mut ctx, allow, &failed, &mut errors);
+                    analyzed.push((allow.clone(), ctx, rust));
+                    // Errors of the compiler are the same with any allow lists.
+                    if errors[from..]
+                        .iter()
+                        .any(|e| matches!(e, LoadError::RustDiagnostic(_)))
+                    {
+                        not_compiled = Some(analyzed.len() - 1);
+                    }
+                    analyzed.len() - 1
+                }
+            };
+            let (_, ctx, rust) = &analyzed[idx];
+            let ctx = Self::load_main(
+                ctx.clone(),
+                &path,
+                main,
+                &failed,
+                *rust,
+                &texts,
+                &mut errors,
+            );
+            ctxs.push((path, ctx));
+        }
 
-        if !errors.is_empty() {
-            return Err(errors.into_vec());
+        if errors.is_empty() {
+            Ok(ctxs)
         } else {
-            debug!("HIR is ready, no errors by this point");
+            Err(self.sorted(errors))
         }
-        let main = main.expect(EXPECT_NO_ERR);
-        let sinks = sinks.into_iter().map(|v| v.expect(EXPECT_NO_ERR));
-        let srcs = srcs.into_iter().map(|v| v.expect(EXPECT_NO_ERR));
+    }
 
-        info!("Creating new context");
-        let ctx = Ctx::new(main.name().into(), Some(main.explain().into()));
-        let mut ctx = match ctx {
-            Ok(ctx) => ctx,
-            Err(e) => {
-                error!("Error creating context. {e}");
-                errors.push(e.into());
-                return Err(errors.into_vec());
-            }
-        };
+    /// Rust items generated from the YAML sinks and sources of the project, and the allow
+    /// lists of the selected main file. These are what the Rust files of the project are
+    /// analyzed with. Bindings are not loaded, but errors of any YAML file, the main files
+    /// included, are returned as by [Self::run].
+    pub fn analysis_input(&self) -> Result<AnalysisInput, Vec<LoadError>> {
+        info!("Load the input of the analysis of the project");
+        self.validate_path().map_err(vec)?;
 
-        info!("Add sinks to the context");
-        for sink in sinks {
-            if let Err(e) = ctx.add_sink(sink) {
-                error!("Error adding sink to the context. {e}");
-                errors.push(e.into())
-            }
+        let mut errors = SmallVec::<[_; 32]>::new();
+        let mut texts = Texts::new();
+        let mut items = self.load_items(&mut errors, &mut texts);
+        let (main_path, main) = self.take_main(&mut items, &texts, &mut errors);
+        let main = main.and_then(|main| Self::main_hir(&main_path, main, &texts, &mut errors));
+        let ctx = items_ctx(&items, &mut errors);
+        match main {
+            Some(main) if errors.is_empty() => Ok(AnalysisInput {
+                generated: gen_items(&ctx, &Default::default()),
+                allow: main.allow().clone(),
+            }),
+            _ => Err(self.sorted(errors)),
         }
-        info!("Add sources to the context");
-        trace!("Sources count: {}", srcs.len());
-        for src in srcs {
-            if let Err(e) = ctx.add_source(src) {
-                error!("Error adding source to the context. {e}");
-                errors.push(e.into())
+    }
+
+    /// Take the selected main file out of the items, with its path. The other main files
+    /// are skipped. If the main file is missing, or is not a main file, the error is added
+    /// to `errors`.
+    fn take_main(
+        &self,
+        items: &mut Items,
+        texts: &Texts,
+        errors: &mut SmallVec<[LoadError; 32]>,
+    ) -> (PathBuf, Option<yaml::Main>) {
+        let main_path = self.main_path(texts);
+        let main = match items.mains.iter().position(|(path, _)| *path == main_path) {
+            Some(idx) => Some(items.mains.swap_remove(idx).1),
+            None => {
+                if !errors.iter().any(|e| e.file() == Some(&main_path)) {
+                    error!("Main file not found: {}", main_path.display());
+                    let err = if texts.contains_key(&main_path) {
+                        MainLoadError::NotMain(main_path.clone())
+                    } else {
+                        MainLoadError::NotFound(main_path.clone())
+                    };
+                    errors.push(err.into());
+                }
+                None
             }
+        };
+        for (path, _) in &items.mains {
+            debug!("Skipping other main file {}", path.display());
         }
+        (main_path, main)
+    }
+
+    /// Path of the main file that [Self::run] loads. It is the path of the file as listed
+    /// in the project, also if it is selected as e.g. `./daily.yaml` or by absolute path.
+    fn main_path(&self, texts: &Texts) -> PathBuf {
+        let name = self.main.unwrap_or(Self::MAIN_FILE_NAME.as_ref());
+        let path = self.path.join(name);
+        let canonical = path.canonicalize().ok();
+        texts
+            .keys()
+            .find(|v| **v == path || (canonical.is_some() && v.canonicalize().ok() == canonical))
+            .cloned()
+            .unwrap_or(path)
+    }
 
-        info!("Generate Rust code for YAML sources and sinks");
-        let generated = {
-            use crate::context::codegen;
-            use quote::{quote, TokenStreamExt};
+    /// Translate the main file into HIR. The errors are added to `errors`.
+    fn main_hir(
+        main_path: &std::path::Path,
+        main: yaml::Main,
+        texts: &Texts,
+        errors: &mut SmallVec<[LoadError; 32]>,
+    ) -> Option<hir::Main> {
+        info!("Translate main file into HIR");
+        debug!("Main file version: {}", main.version());
+        hir::Main::try_from(main)
+            .map_err(|e| {
+                errors.extend(
+                    e.into_iter()
+                        .map(|e| LoadError::from(e).locate(main_path, texts)),
+                )
+            })
+            .ok()
+    }
 
-            let mut tokens = quote! {};
+    /// Analyze the Rust files of the project with the allow lists, and add their items
+    /// to the context. The errors are added to `errors`. Returns whether the Rust items
+    /// are loaded.
+    fn add_rust(
+        &self,
+        ctx: &mut Ctx,
+        allow: &compile::Allow,
+        failed: &std::collections::BTreeSet<CompactString>,
+        errors: &mut SmallVec<[LoadError; 32]>,
+    ) -> bool {
+        let generated = gen_items(ctx, failed);
 
-            info!("Generating sources");
-            for src in ctx.sources() {
-                tokens.append_all(codegen::gen_data_src(src));
+        info!("Load Rust files");
+        let rust = compile::ProjectContent::load_from_project_dir(
+            self.path,
+            &generated,
+            allow,
+            &self.analyze,
+        );
+        let rust = match rust {
+            Ok(rust) => rust,
+            // Each diagnostic is reported on its own, same as the YAML errors.
+            Err(compile::ProjectContentError::Compile(diagnostics)) => {
+                errors.extend(
+                    diagnostics
+                        .into_iter()
+                        .filter(|v| v.level == compile::diagnostic::Level::Error)
+                        .filter(|v| !mentions_failed(v, failed))
+                        .map(LoadError::RustDiagnostic),
+                );
+                return false;
             }
-            info!("Generating sinks");
-            for sink in ctx.sinks() {
-                tokens.append_all(codegen::gen_data_sink(sink));
+            Err(e) => {
+                errors.push(e.into());
+                return false;
             }
-
-            info!("Generator main function finished");
-            codegen::trace_printall(&tokens);
-            tokens
         };
 
-        info!("Load Rust files");
-        let rust = compile::ProjectContent::load_from_project_dir(self.path, generated);
-        let rust = rust.map_err(|e| errors.push(e.into())).ok();
-
         // Add rust first as it can be used in the bindings.
+        // YAML items can implement the traits in Rust too, these are already in the context
+        // and only get their item types.
         info!("Add Rust items to the context");
-        if let Some(rust) = rust {
-            for sink in rust.sinks() {
+        // Generated items are in their own modules, so only Rust items match exactly.
+        let yaml_items: SmallVec<[_; 32]> = ctx
+            .sources()
+            .iter()
+            .filter(|v| !v.is_native())
+            .map(|v| v.name().to_compact_string())
+            .chain(
+                ctx.sinks()
+                    .iter()
+                    .filter(|v| !v.is_native())
+                    .map(|v| v.name().into()),
+            )
+            .chain(failed.iter().cloned())
+            .collect();
+        for ty in &rust.pub_types {
+            let path = ty.to_compact_string();
+            if yaml_items.contains(&path) {
+                error!("YAML item `{path}` conflicts with a Rust item");
+                let file = self.path.join(path.replace("::", "/"));
+                errors.push(LoadError::RustPathConflict {
+                    path,
+                    file: file.with_extension("yaml"),
+                });
+            }
+        }
+
+        for &id in &rust.sinks {
+            let sink = &rust.pub_types[id as usize];
+            let name = sink.to_compact_string();
+            if !ctx.sink(&name).is_some_and(|v| !v.is_native()) {
                 trace!("Add native sink: {sink}");
                 if let Err(e) = ctx.add_native_sink(sink) {
                     error!("Error adding native sink to the context. {e}");
-                    errors.push(e.into())
+                    errors.push(e.into());
+                    continue;
                 }
             }
-            for source in rust.sources() {
+            for ty in rust.sink_tys(id) {
+                ctx.add_sink_item_ty(&name, &ty.ty, &ty.params)
+                    .expect("sink was added to the context above");
+            }
+            for ctor in rust.constructors(id) {
+                ctx.add_constructor(&name, ctor.clone())
+                    .expect("sink was added to the context above");
+            }
+        }
+        for &id in &rust.sources {
+            let source = &rust.pub_types[id as usize];
+            let name = source.to_compact_string();
+            if !ctx.source(&name).is_some_and(|v| !v.is_native()) {
                 trace!("Add native source: {source}");
                 if let Err(e) = ctx.add_native_source(source) {
                     error!("Error adding native source to the context. {e}");
-                    errors.push(e.into())
+                    errors.push(e.into());
+                    continue;
                 }
             }
+            if let Some(ty) = rust.source_ty(id) {
+                ctx.set_source_item_ty(&name, &ty.ty, &ty.params)
+                    .expect("source was added to the context above");
+            }
+            for ctor in rust.constructors(id) {
+                ctx.add_constructor(&name, ctor.clone())
+                    .expect("source was added to the context above");
+            }
         }
 
+        true
+    }
+
+    /// Load the bindings and the pipes of the main file into the context with the items.
+    /// The errors are added to `errors`, and the context has what could be loaded despite
+    /// them. Without the main file, the context keeps its placeholder name.
+    fn load_main(
+        mut ctx: Ctx,
+        main_path: &std::path::Path,
+        main: Option<hir::Main>,
+        failed: &std::collections::BTreeSet<CompactString>,
+        rust: bool,
+        texts: &Texts,
+        errors: &mut SmallVec<[LoadError; 32]>,
+    ) -> Ctx {
+        let Some(main) = main else {
+            // Bindings and pipes are in the main file.
+            warn!("Main file is not loaded, skipping the bindings");
+            return ctx;
+        };
+
+        info!("Name the context after the main file");
+        if let Err(e) = ctx.rename(main.name().into(), Some(main.explain().into())) {
+            error!("Error naming the context. {e}");
+            errors.push(LoadError::from(e).locate(main_path, texts));
+        }
+
+        // Errors of the main file are reported with it.
+        let main_errors_from = errors.len();
+
         info!("Fill in the bindings from the main file");
+        // Bindings to the items that failed to load. Without the Rust files, the native
+        // items are unknown too.
+        let mut skipped = std::collections::HashSet::new();
         for (name, cfg) in main.bindings() {
-            let ty = cfg.ty_str();
+            let ty = main.resolve_ty(&cfg.ty_str());
+            let is_known = ctx.sink(&ty).is_some() || ctx.source(&ty).is_some();
+            if failed.contains(&ty) || (!rust && !is_known) {
+                debug!("Skipping binding `{name}` to unavailable item `{ty}`");
+                skipped.insert(name);
+                continue;
+            }
+
             let result = ctx.add_binding(name.into(), &ty);
             match result {
                 Err(e) => {
@@ -206,20 +637,117 @@ impl LoadProjectDir<'_> {
                 }
                 Ok(_) => {
                     for (key, value) in BindingCfgIter::new(cfg.cfg()) {
-                        let result = ctx.add_param(name, key, value);
+                        // Blank key is the inline Rust expression of a native binding.
+                        let result = if key.is_empty() {
+                            ctx.add_native_init(name, value).map_err(Into::into)
+                        } else {
+                            ctx.add_param(name, key, value).map_err(Into::into)
+                        };
                         if let Err(e) = result {
-                            errors.push(e.into());
+                            errors.push(e);
                         }
                     }
                 }
             }
         }
 
-        if errors.is_empty() {
-            Ok(ctx)
-        } else {
-            Err(errors.into_vec())
+        info!("Add pipes from the main file");
+        for pipe in main.pipes() {
+            if skipped.contains(pipe.input()) || skipped.contains(pipe.output()) {
+                debug!("Skipping pipe `{pipe}` of a skipped binding");
+                continue;
+            }
+            if let Err(err) = ctx.add_pipe(pipe.input(), pipe.output()) {
+                error!("Error adding pipe to the context. {err}");
+                errors.push(LoadError::AddPipe {
+                    pipe: pipe.to_compact_string(),
+                    err,
+                });
+            }
+        }
+
+        if let Err(e) = ctx.validate() {
+            use crate::context::validate::ValidateErr;
+
+            error!("Invalid bindings in the main file");
+            errors.extend(
+                e.into_iter()
+                    .filter(|e| {
+                        !matches!(e, ValidateErr::UnknownBinding { name, .. }
+                            if skipped.contains(name.as_str()))
+                    })
+                    .map(Into::into),
+            );
         }
+
+        info!("Add check policies from the main file");
+        for (src, policy) in main.check_policies() {
+            let reject = match policy {
+                hir::CheckPolicy::Reject(sink) => Some(sink),
+                _ => None,
+            };
+            if skipped.contains(src) || reject.is_some_and(|v| skipped.contains(v)) {
+                debug!("Skipping check policy of `{src}` with a skipped binding");
+                continue;
+            }
+            if let Err(err) = ctx.set_check_policy(src, policy) {
+                error!("Error setting check policy in the context. {err}");
+                errors.push(LoadError::CheckPolicy {
+                    binding: src.into(),
+                    err,
+                });
+            }
+        }
+
+        let main_errors: SmallVec<[_; 32]> = errors.drain(main_errors_from..).collect();
+        errors.extend(main_errors.into_iter().map(|e| e.locate(main_path, texts)));
+
+        ctx
+    }
+
+    /// Items of the files that failed to load, by their paths.
+    /// The main file is not an item, even if it fails to load.
+    fn failed_items(
+        &self,
+        errors: &[LoadError],
+        main_path: Option<&std::path::Path>,
+    ) -> std::collections::BTreeSet<CompactString> {
+        use crate::yaml::hir::StringExt;
+
+        errors
+            .iter()
+            .filter_map(|e| {
+                let file = match e {
+                    LoadError::PathConflict { path, .. } => return Some(path.clone()),
+                    LoadError::Yaml(..) | LoadError::Located(..) | LoadError::InFile(..) => {
+                        e.file()?
+                    }
+                    _ => return None,
+                };
+                if Some(file) == main_path {
+                    return None;
+                }
+                let item = file.to_path_buf().wrap(()).rust_path_string(self.path);
+                item.is_valid_path().then(|| item.into())
+            })
+            .collect()
+    }
+
+    /// Sort the errors by files and by positions in the files. Errors of the whole
+    /// project go first, errors without a position go after the others of their file.
+    fn sorted(&self, errors: SmallVec<[LoadError; 32]>) -> Vec<LoadError> {
+        let mut errors = errors.into_vec();
+        errors.sort_by(|a, b| {
+            let key = |e: &LoadError| {
+                (
+                    e.file().map(|v| v.to_path_buf()),
+                    e.position().is_none(),
+                    e.position(),
+                )
+            };
+            key(a).cmp(&key(b))
+        });
+        errors
     }
 
     fn validate_path(&self) -> Result<(), LoadError> {
@@ -237,90 +765,178 @@ impl LoadProjectDir<'_> {
         Ok(())
     }
 
-    fn load_main(&self) -> Result<v01::Main, MainLoadError> {
-        debug!("Load main file");
-        let main_file = self.path.join("main.yaml");
-        if !main_file.exists() {
-            return Err(MainLoadError::NotFound(main_file));
+    /// Load all YAML files of the project directory: the main files, and the sinks and
+    /// sources translated into HIR.
+    fn load_items(&self, errors: &mut SmallVec<[LoadError; 32]>, texts: &mut Texts) -> Items {
+        debug!("Load YAML files");
+
+        let list = self.list_yaml_files().map_err(LoadError::DirList);
+        let list = match list {
+            Ok(list) => list,
+            Err(e) => {
+                error!("Error listing YAML files: {e:?}");
+                errors.push(e);
+                return Items::default();
+            }
+        };
+
+        let mut mains = Vec::new();
+        let mut files = std::collections::HashMap::new();
+        for path in list {
+            let text = match std::fs::read_to_string(&path) {
+                Ok(v) => texts.entry(path.clone()).or_insert(v),
+                Err(e) => {
+                    errors.push(LoadError::Yaml(e.into(), path));
+                    continue;
+                }
+            };
+            match YamlFile::load(&path, text) {
+                Ok(YamlFile::Main(main)) => mains.push((path, main)),
+                Ok(file) => {
+                    files.insert(path, file);
+                }
+                Err(e) => errors.push(LoadError::Yaml(e, path.clone()).locate(&path, texts)),
+            }
         }
 
-        let main = v01::Main::load_from_path(&main_file)?;
-        Ok(main)
-    }
+        let mut item_paths: Vec<_> = files.keys().cloned().collect();
+        item_paths.sort_unstable();
+        self.retain_unique_item_paths(&mut item_paths, errors);
+
+        let mut sinks = SmallVec::<[_; 32]>::new();
+        let mut srcs = SmallVec::<[_; 32]>::new();
+        for path in item_paths {
+            match files.remove(&path) {
+                Some(YamlFile::Sink(sink)) => sinks.push(path.wrap(sink)),
+                Some(YamlFile::Source(src)) => srcs.push(path.wrap(src)),
+                Some(YamlFile::Main(_)) | None => unreachable!("only item files are retained"),
+            }
+        }
+        info!("Loaded main files: {:?}", mains.len());
+        info!("Loaded sinks: {:?}", sinks.len());
+        info!("Loaded sources: {:?}", srcs.len());
+
+        macro_rules! hir_src_sink {
+            ($src_or_sink:expr, $ty:ident) => {
+                $src_or_sink
+                    .into_iter()
+                    .map(|v| {
+                        let s = v.rust_path_string(self.path);
+                        let (path, v) = v.unwrap();
+                        hir::$ty::try_from(v)
+                            .map(|v| v.to_named(s))
+                            .map_err(|e| (e, path))
+                    })
+                    .filter_map(|sink| {
+                        sink.map_err(|(e, path)| {
+                            errors.extend(
+                                e.into_iter()
+                                    .map(|e| LoadError::from(e).locate(&path, &texts)),
+                            )
+                        })
+                        .ok()
+                    })
+            };
+        }
 
-    /// List all YAML files except for main in the project directory.
-    fn list_other_yaml_files(&self) -> std::io::Result<Vec<std::path::PathBuf>> {
-        debug!("List other YAML files");
+        info!(
+            "Translate to HIR sinks and sources, also populate error array if there are any found"
+        );
+        let sinks: SmallVec<[_; 32]> = hir_src_sink!(sinks, UnnamedSink).collect();
+        let srcs: SmallVec<[_; 32]> = hir_src_sink!(srcs, UnnamedSource).collect();
 
-        let mut files = SmallVec::<[_; 32]>::new();
-        for entry in std::fs::read_dir(self.path)? {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_file()
-                && path.extension() == Some("yaml".as_ref())
-                && path.file_name() != Some(Self::MAIN_FILE_NAME.as_ref())
-            {
-                files.push(path);
+        const VALID_PATHS: &str = "item paths are validated when listing the files";
+        Items {
+            mains,
+            sinks: sinks.into_iter().map(|v| v.expect(VALID_PATHS)).collect(),
+            srcs: srcs.into_iter().map(|v| v.expect(VALID_PATHS)).collect(),
+        }
+    }
+
+    /// List all YAML files in the project directory and its subdirectories.
+    fn list_yaml_files(&self) -> std::io::Result<Vec<std::path::PathBuf>> {
+        fn visit(
+            dir: &std::path::Path,
+            files: &mut SmallVec<[PathBuf; 32]>,
+        ) -> std::io::Result<()> {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    visit(&path, files)?;
+                } else if path.is_file() && path.extension() == Some("yaml".as_ref()) {
+                    files.push(path);
+                }
             }
+            Ok(())
         }
 
+        debug!("List YAML files");
+        let mut files = SmallVec::new();
+        visit(self.path, &mut files)?;
+        files.sort_unstable();
         Ok(files.into_vec())
     }
 
-    /// Load all sinks and sources from the project directory.
-    ///
-    /// # Failure
-    /// On error, the error array is filled with errors and function returns empty arrays.
-    #[allow(clippy::type_complexity)]
-    fn load_sinks_and_sources(
+    /// Remove the files that cannot be loaded as items under their paths: the ones with
+    /// paths that are not Rust paths and the ones that resolve to the same path.
+    fn retain_unique_item_paths(
         &self,
+        list: &mut Vec<PathBuf>,
         errors: &mut SmallVec<[LoadError; 32]>,
-    ) -> (
-        SmallVec<[File<v01::Sink>; 32]>,
-        SmallVec<[File<v01::Source>; 32]>,
     ) {
-        debug!("Load sinks and sources");
+        use crate::yaml::hir::StringExt;
 
-        let list = self.list_other_yaml_files().map_err(LoadError::DirList);
-        let list = match list {
-            Ok(list) => list,
-            Err(e) => {
-                error!("Error listing other YAML files: {e:?}");
-                errors.push(e);
-                return Default::default();
+        let mut by_path = std::collections::BTreeMap::<_, Vec<_>>::new();
+        for path in list.drain(..) {
+            let item = path.clone().wrap(()).rust_path_string(self.path);
+            if item.is_valid_path() {
+                by_path.entry(item).or_default().push(path);
+            } else {
+                error!("Invalid item path of file {}", path.display());
+                errors.push(LoadError::InvalidItemPath(path));
             }
-        };
-
-        let mut sinks = SmallVec::new();
-        let mut srcs = SmallVec::new();
+        }
 
-        let loader = list.into_iter().map(|path| {
-            let v = SinkOrSource::load(&path);
-            path.wrap(v)
-        });
-        for file in loader {
-            use SinkOrSource::*;
-            let (path, val) = file.unwrap();
-            match val {
-                Ok(Sink(sink)) => sinks.push(path.wrap(sink)),
-                Ok(Source(src)) => srcs.push(path.wrap(src)),
-                Err(e) => errors.push(LoadError::Yaml(e, path)),
+        for (path, files) in by_path {
+            if files.len() == 1 {
+                list.extend(files);
+            } else {
+                error!("Files conflict on the item path `{path}`");
+                errors.push(LoadError::PathConflict {
+                    path: path.into(),
+                    files,
+                });
             }
         }
-
-        info!("Loaded sinks: {:?}", sinks.len());
-        info!("Loaded sources: {:?}", srcs.len());
-        (sinks, srcs)
     }
 }
 
-enum SinkOrSource {
-    Sink(v01::Sink),
-    Source(v01::Source),
+/// YAML files of the project, loaded once and shared by the contexts of the main files.
+#[derive(Default)]
+struct Items {
+    mains: Vec<(PathBuf, yaml::Main)>,
+    sinks: SmallVec<[hir::Sink; 32]>,
+    srcs: SmallVec<[hir::Source; 32]>,
+}
+
+enum YamlFile {
+    Main(yaml::Main),
+    Sink(yaml::Sink),
+    Source(yaml::Source),
 }
 
-impl SinkOrSource {
-    fn load(file: &std::path::Path) -> Result<Self, crate::yaml::Error> {
+impl YamlFile {
+    /// Load the text of the file with the schema of the version in its header.
+    fn load(file: &std::path::Path, s: &str) -> Result<Self, crate::yaml::Error> {
+        let version = yaml::Version::of_file(s)?;
+        trace!("File {} has version {version}", file.display());
+        match version {
+            yaml::Version::V01 => Self::load_v01(s),
+            yaml::Version::V02 => Self::load_v02(s),
+        }
+    }
+
+    fn load_v01(s: &str) -> Result<Self, crate::yaml::Error> {
         use serde_yml::from_str;
 
         #[derive(serde::Deserialize)]
@@ -328,19 +944,48 @@ impl SinkOrSource {
             permute: v01::Header,
         }
 
-        let s = std::fs::read_to_string(file)?;
-        let header = from_str::<FileWithHeader>(&s)?.permute;
+        let header = from_str::<FileWithHeader>(s)?.permute;
 
         use v01::FileKind::*;
         match header.ty {
-            Main => unreachable!("main should be loaded by separate function"),
+            Main => {
+                let main = v01::Main::load_from_str(s)?;
+                Ok(Self::Main(yaml::Main::V01(main)))
+            }
+            Sink => {
+                let sink = from_str::<v01::Sink>(s)?;
+                Ok(Self::Sink(yaml::Sink::V01(sink)))
+            }
+            Source => {
+                let source = from_str::<v01::Source>(s)?;
+                Ok(Self::Source(yaml::Source::V01(source)))
+            }
+        }
+    }
+
+    fn load_v02(s: &str) -> Result<Self, crate::yaml::Error> {
+        use serde_yml::from_str;
+
+        #[derive(serde::Deserialize)]
+        struct FileWithHeader {
+            permute: v02::Header,
+        }
+
+        let header = from_str::<FileWithHeader>(s)?.permute;
+
+        use v02::FileKind::*;
+        match header.ty {
+            Main => {
+                let main = v02::Main::load_from_str(s)?;
+                Ok(Self::Main(yaml::Main::V02(main)))
+            }
             Sink => {
-                let sink = from_str::<v01::Sink>(&s)?;
-                Ok(Self::Sink(sink))
+                let sink = from_str::<v02::Sink>(s)?;
+                Ok(Self::Sink(yaml::Sink::V02(sink)))
             }
             Source => {
-                let source = from_str::<v01::Source>(&s)?;
-                Ok(Self::Source(source))
+                let source = from_str::<v02::Source>(s)?;
+                Ok(Self::Source(yaml::Source::V02(source)))
             }
         }
     }
@@ -565,15 +1210,308 @@ pub(crate) mod tests {
         );
     }
 
+    #[test]
+    fn unique_item_paths() {
+        let load = LoadProjectDir {
+            path: Path::new("root"),
+            main: None,
+            analyze: Default::default(),
+        };
+        let mut list = vec![
+            PathBuf::from("root/records/Row.yaml"),
+            PathBuf::from("root/records.d/Row.yaml"),
+            PathBuf::from("root/1records/Row.yaml"),
+            PathBuf::from("root/records/Other.yaml"),
+        ];
+        let mut errors = SmallVec::new();
+        load.retain_unique_item_paths(&mut list, &mut errors);
+
+        assert_eq!(list, [PathBuf::from("root/records/Other.yaml")]);
+        assert_eq!(errors.len(), 2, "{errors:#?}");
+        assert!(matches!(
+            &errors[0],
+            LoadError::InvalidItemPath(path) if path == Path::new("root/1records/Row.yaml")
+        ));
+        assert!(matches!(
+            &errors[1],
+            LoadError::PathConflict { path, files } if path == "records::Row" && files.len() == 2
+        ));
+    }
+
+    #[test]
+    fn locate_errors() {
+        let path = PathBuf::from("root/Src.yaml");
+        let text = "permute:\n  version: 0.1\n  type: source\ncolumn:\n  date:\n    type: Option<NaiveDate\n";
+        let texts = Texts::from([(path.clone(), text.to_owned())]);
+
+        let snippet = "Option<NaiveDate";
+        let err = syn::parse_str::<syn::Type>(snippet).unwrap_err();
+        let err = LoadError::from(hir::SourceError::TypeParse(err, snippet.into()));
+        let err = err.locate(&path, &texts);
+        let span = err.span().expect("error should be located");
+        assert_eq!((span.line, span.col), (6, 27));
+        assert!(
+            err.to_string()
+                .ends_with("6 |     type: Option<NaiveDate\n  |                           ^"),
+            "{err}"
+        );
+
+        let err = serde_yml::from_str::<v01::Header>("version: [\n").unwrap_err();
+        let err = LoadError::Yaml(err.into(), path.clone()).locate(&path, &texts);
+        assert!(err.span().is_some(), "{err}");
+
+        let other = Path::new("root/Other.yaml");
+        let err = LoadError::Yaml(std::io::Error::other("").into(), other.into());
+        assert!(err.locate(other, &texts).span().is_none());
+    }
+
+    #[test]
+    fn recover_from_broken_files() {
+        let dir = sample_copy("recover");
+        let record = dir.join("EmploymentRecord.yaml");
+        let text = std::fs::read_to_string(&record).unwrap();
+        let text = text.replacen("Option<NaiveDate>", "Option<NaiveDate", 1);
+        std::fs::write(&record, text).unwrap();
+        std::fs::write(dir.join("Broken.yaml"), "permute: [\n").unwrap();
+
+        let errors = LoadProjectDir {
+            path: &dir,
+            main: None,
+            analyze: Default::default(),
+        }
+        .run()
+        .err()
+        .expect("project has errors");
+
+        let yaml_errors: Vec<_> = errors
+            .iter()
+            .filter(|e| e.file().is_some_and(|v| v.starts_with(&dir)))
+            .collect();
+        assert_eq!(yaml_errors.len(), 2, "{errors:#?}");
+        assert_eq!(
+            yaml_errors[0].file(),
+            Some(dir.join("Broken.yaml").as_path())
+        );
+        assert_eq!(yaml_errors[1].file(), Some(record.as_path()));
+        assert_eq!(yaml_errors[1].position(), Some((21, 27)));
+
+        // The binding to the broken source and its pipe are skipped, as are the errors
+        // of the Rust files that use the fields and methods of the source.
+        assert!(
+            !errors.iter().any(|e| e.to_string().contains("Binding")),
+            "{errors:#?}"
+        );
+        assert!(
+            !errors
+                .iter()
+                .any(|e| matches!(e, LoadError::RustDiagnostic(_))),
+            "{errors:#?}"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keep_unrelated_rust_errors() {
+        let dir = sample_copy("unrelated");
+        let record = dir.join("EmploymentRecord.yaml");
+        let text = std::fs::read_to_string(&record).unwrap();
+        let text = text.replacen("Option<NaiveDate>", "Option<NaiveDate", 1);
+        std::fs::write(&record, text).unwrap();
+        let writer = "pub struct EmploymentRecordWriter;\n\n\
+            pub fn writer() -> EmploymentRecordWriter {\n    0\n}\n";
+        std::fs::write(dir.join("writer.rs"), writer).unwrap();
+
+        let errors = LoadProjectDir {
+            path: &dir,
+            main: None,
+            analyze: Default::default(),
+        }
+        .run()
+        .err()
+        .expect("project has errors");
+        assert!(
+            errors
+                .iter()
+                .any(|e| matches!(e, LoadError::RustDiagnostic(_))
+                    && e.file().is_some_and(|v| v.ends_with("writer.rs"))),
+            "{errors:#?}"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn mentions_failed_whole_names() {
+        let diagnostic = |message: &str| compile::diagnostic::Diagnostic {
+            level: compile::diagnostic::Level::Error,
+            code: None,
+            message: message.into(),
+            loc: None,
+            children: Vec::new(),
+            suggestions: Vec::new(),
+        };
+        let failed = std::collections::BTreeSet::from(["a::Csv".into()]);
+        assert!(mentions_failed(
+            &diagnostic("no field `x` on type `Csv`"),
+            &failed
+        ));
+        assert!(mentions_failed(
+            &diagnostic("expected `_Csv::CsvRow`"),
+            &failed
+        ));
+        assert!(!mentions_failed(
+            &diagnostic("expected `CsvWriter`"),
+            &failed
+        ));
+        assert!(!mentions_failed(&diagnostic("expected `Csv2`"), &failed));
+        assert!(!mentions_failed(&diagnostic("expected `MyCsv`"), &failed));
+    }
+
+    #[test]
+    fn analyze_without_main_file() {
+        let dir = sample_copy("no-main");
+        std::fs::write(dir.join("main.yaml"), "permute: [\n").unwrap();
+        let monetary = std::fs::read_to_string(dir.join("monetary.rs")).unwrap();
+        let monetary = monetary + "\npub fn broken() -> u32 {\n    \"\"\n}\n";
+        std::fs::write(dir.join("monetary.rs"), monetary).unwrap();
+
+        let errors = LoadProjectDir {
+            path: &dir,
+            main: None,
+            analyze: Default::default(),
+        }
+        .run()
+        .err()
+        .expect("project has errors");
+        let main = dir.join("main.yaml");
+        assert!(
+            errors.iter().any(|e| e.file() == Some(main.as_path())),
+            "{errors:#?}"
+        );
+        assert!(
+            errors
+                .iter()
+                .any(|e| matches!(e, LoadError::RustDiagnostic(_))),
+            "{errors:#?}"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn multiple_main_files() {
+        let dir = sample_copy("mains");
+        let main = std::fs::read_to_string(dir.join("main.yaml")).unwrap();
+        let daily = main.replacen("SampleProcessName", "DailyProcess", 1);
+        std::fs::write(dir.join("daily.yaml"), daily).unwrap();
+
+        fn load<'a>(dir: &'a Path, main: Option<&'a str>) -> LoadProjectDir<'a> {
+            LoadProjectDir {
+                path: dir,
+                main: main.map(Path::new),
+                analyze: Default::default(),
+            }
+        }
+
+        let ctxs = load(&dir, None)
+            .run_all()
+            .unwrap_or_else(|e| panic!("{e:#?}"));
+        let names: Vec<_> = ctxs.iter().map(|(_, ctx)| ctx.name()).collect();
+        assert_eq!(names, ["DailyProcess", "SampleProcessName"]);
+        for (_, ctx) in &ctxs {
+            assert_eq!(ctx.sinks().len(), 2);
+            assert_eq!(ctx.sources().len(), 1);
+            assert_eq!(ctx.pipes().count(), 1);
+        }
+
+        // The other main file is skipped, not loaded as an item.
+        let ctx = load(&dir, None).run().unwrap_or_else(|e| panic!("{e:#?}"));
+        assert_eq!(ctx.name(), "SampleProcessName");
+        let ctx = load(&dir, Some("daily.yaml"))
+            .run()
+            .unwrap_or_else(|e| panic!("{e:#?}"));
+        assert_eq!(ctx.name(), "DailyProcess");
+        let ctx = load(&dir, Some("./daily.yaml"))
+            .run()
+            .unwrap_or_else(|e| panic!("{e:#?}"));
+        assert_eq!(ctx.name(), "DailyProcess");
+        let daily = dir.join("daily.yaml");
+        let ctx = load(&dir, daily.to_str())
+            .run()
+            .unwrap_or_else(|e| panic!("{e:#?}"));
+        assert_eq!(ctx.name(), "DailyProcess");
+
+        let errors = load(&dir, Some("Csv.yaml"))
+            .run()
+            .err()
+            .expect("not a main file");
+        assert!(
+            matches!(
+                errors[..],
+                [LoadError::MainFileError(MainLoadError::NotMain(_))]
+            ),
+            "{errors:#?}"
+        );
+        let errors = load(&dir, Some("missing.yaml"))
+            .run()
+            .err()
+            .expect("no main file");
+        assert!(
+            matches!(
+                errors[..],
+                [LoadError::MainFileError(MainLoadError::NotFound(_))]
+            ),
+            "{errors:#?}"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn run_all_reports_rust_errors_once() {
+        let dir = sample_copy("mains-rust");
+        let main = std::fs::read_to_string(dir.join("main.yaml")).unwrap();
+        let daily = main.replacen("SampleProcessName", "DailyProcess", 1);
+        std::fs::write(dir.join("daily.yaml"), daily).unwrap();
+        let weekly = main.replacen("SampleProcessName", "WeeklyProcess", 1) + "    - ee_to_csv\n";
+        std::fs::write(dir.join("weekly.yaml"), weekly).unwrap();
+        let monetary = std::fs::read_to_string(dir.join("monetary.rs")).unwrap();
+        let monetary = monetary + "\npub fn broken() -> u32 {\n    \"\"\n}\n";
+        std::fs::write(dir.join("monetary.rs"), monetary).unwrap();
+
+        let load = || LoadProjectDir {
+            path: &dir,
+            main: None,
+            analyze: Default::default(),
+        };
+        let rust_errors = |errors: Vec<LoadError>| {
+            errors
+                .into_iter()
+                .filter(|e| matches!(e, LoadError::RustDiagnostic(_)))
+                .count()
+        };
+        let errors = load().run().err().expect("project has errors");
+        let count = rust_errors(errors);
+        assert!(count > 0);
+        let errors = load().run_all().err().expect("project has errors");
+        assert_eq!(rust_errors(errors), count);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
     pub fn do_load_project() -> Ctx {
         let result = LoadProjectDir {
             path: std::path::Path::new("src/samples/example1"),
+            main: None,
+            analyze: Default::default(),
         }
         .run();
         match result {
             Ok(ctx) => {
                 assert_eq!(ctx.sinks().len(), 2);
                 assert_eq!(ctx.sources().len(), 1);
+                assert_eq!(ctx.pipes().count(), 1);
                 ctx
             }
             Err(errors) => {
@@ -609,4 +1547,229 @@ pub(crate) mod tests {
         crate::setup_logger();
         do_load_project();
     }
+
+    #[test]
+    fn pipe_type_check() {
+        crate::setup_logger();
+        let mut ctx = do_load_project();
+
+        // `er -> feed` is in the main file, so `EmploymentRecord -> Ee2Csv` is accepted.
+        ctx.add_pipe("er", "feed").unwrap();
+
+        let err = ctx.add_pipe("er", "csv").unwrap_err();
+        assert!(
+            matches!(err, crate::context::AddPipeErr::TypeMismatch { .. }),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn validate_bindings() {
+        use crate::context::validate::ValidateErr;
+
+        crate::setup_logger();
+        let mut ctx = do_load_project();
+        ctx.validate().unwrap();
+
+        ctx.add_param("er", "date_form".into(), syn::parse_quote!(None))
+            .unwrap();
+        ctx.add_binding("csv2".into(), "Csv").unwrap();
+        let mut key = ParamKey::from("header");
+        key.push("x".into());
+        ctx.add_param("csv2", key, syn::parse_quote!(1)).unwrap();
+
+        let errors = ctx.validate().unwrap_err();
+        let messages: Vec<_> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(errors.len(), 4, "{messages:#?}");
+        assert!(errors.iter().any(|e| matches!(
+            e,
+            ValidateErr::UnknownKey { suggest: Some(v), .. } if v == "date_from"
+        )));
+        let mut missing: Vec<_> = errors
+            .iter()
+            .filter_map(|e| match e {
+                ValidateErr::MissingRequired { param, .. } => Some(param.as_str()),
+                _ => None,
+            })
+            .collect();
+        missing.sort_unstable();
+        assert_eq!(missing, ["date_fmt", "path"]);
+        assert!(errors.iter().any(
+            |e| matches!(e, ValidateErr::NestedKeyMismatch { param, .. } if param == "header")
+        ));
+    }
+
+    #[test]
+    fn validate_native_init() {
+        use crate::context::validate::ValidateErr;
+
+        crate::setup_logger();
+        let mut ctx = do_load_project();
+
+        let inits: [(&str, syn::Expr); 4] = [
+            (
+                "feed2",
+                syn::parse_quote!(crate::ee_to_csv::Ee2Csv::make(csv)),
+            ),
+            ("feed3", syn::parse_quote!(Ee2Csv::new(csv, 1))),
+            ("feed4", syn::parse_quote!(Ee2Csv::new(cvs))),
+            ("feed5", syn::parse_quote!(Other::make(None))),
+        ];
+        for (name, init) in inits {
+            ctx.add_binding(name.into(), "ee_to_csv::Ee2Csv").unwrap();
+            ctx.add_native_init(name, init).unwrap();
+        }
+
+        let errors = ctx.validate().unwrap_err();
+        let messages: Vec<_> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(errors.len(), 3, "{messages:#?}");
+        assert!(matches!(
+            &errors[0],
+            ValidateErr::UnknownConstructor { binding, name, available, .. }
+                if binding == "feed2" && name == "make" && available == &["new"]
+        ));
+        assert!(matches!(
+            &errors[1],
+            ValidateErr::ConstructorArity {
+                expected: 1,
+                found: 2,
+                ..
+            }
+        ));
+        assert!(matches!(
+            &errors[2],
+            ValidateErr::UnknownBinding { binding, name } if binding == "feed4" && name == "cvs"
+        ));
+    }
+
+    #[test]
+    fn check_policy() {
+        use crate::context::CheckPolicyErr;
+
+        crate::setup_logger();
+        let mut ctx = do_load_project();
+
+        let policies: Vec<_> = ctx
+            .check_policies()
+            .map(|(src, policy)| (src.name(), policy.map(|sink| sink.name())))
+            .collect();
+        assert_eq!(policies, [("er", hir::CheckPolicy::Skip)]);
+
+        ctx.add_binding("rejected".into(), "ee_to_csv::Ee2Csv")
+            .unwrap();
+        ctx.set_check_policy("er", hir::CheckPolicy::Reject("rejected"))
+            .unwrap();
+
+        let err = ctx
+            .set_check_policy("er", hir::CheckPolicy::Reject("csv"))
+            .unwrap_err();
+        assert!(matches!(err, CheckPolicyErr::TypeMismatch { .. }), "{err}");
+
+        // The source takes the reject sink, so it cannot be a pipe output as well.
+        let err = ctx
+            .set_check_policy("er", hir::CheckPolicy::Reject("feed"))
+            .unwrap_err();
+        assert!(matches!(err, CheckPolicyErr::SinkInUse(_)), "{err}");
+
+        let err = ctx
+            .set_check_policy("feed", hir::CheckPolicy::Skip)
+            .unwrap_err();
+        assert!(matches!(err, CheckPolicyErr::NotSource(_)), "{err}");
+    }
+
+    #[test]
+    fn reject_sink_compiles() {
+        const MAIN: &str = r#"
+permute:
+  version: 0.1
+  type: main
+name: RejectProcess
+pipe:
+  - er -> feed
+let:
+  er:
+    EmploymentRecord: {}
+  csv:
+    Csv:
+      path: '"output.csv"'
+      date_fmt: &date_fmt |
+        Box::new(|v: chrono::NaiveDate| v.to_string()) as Box<dyn Fn(chrono::NaiveDate) -> String>
+  rejected_csv:
+    Csv:
+      path: '"rejected.csv"'
+      date_fmt: *date_fmt
+  feed:
+    crate::ee_to_csv::Ee2Csv: crate::ee_to_csv::Ee2Csv::new(csv)
+  rejected:
+    crate::ee_to_csv::Ee2Csv: crate::ee_to_csv::Ee2Csv::new(rejected_csv)
+on_check_fail:
+  er:
+    reject: rejected
+"#;
+
+        crate::setup_logger();
+        let dir = sample_copy("reject");
+        std::fs::write(dir.join("main.yaml"), MAIN).unwrap();
+
+        let ctx = LoadProjectDir {
+            path: &dir,
+            main: None,
+            analyze: Default::default(),
+        }
+        .run()
+        .unwrap_or_else(|e| panic!("{e:#?}"));
+        let policies: Vec<_> = ctx
+            .check_policies()
+            .map(|(src, policy)| (src.name(), policy.map(|sink| sink.name())))
+            .collect();
+        assert_eq!(policies, [("er", hir::CheckPolicy::Reject("rejected"))]);
+
+        assert_main_compiles(&dir, &ctx);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sample_main_compiles() {
+        crate::setup_logger();
+        let ctx = do_load_project();
+        assert_main_compiles(Path::new("src/samples/example1"), &ctx);
+    }
+
+    /// Generate the main of the context and type-check it with the project files.
+    fn assert_main_compiles(dir: &Path, ctx: &Ctx) {
+        // Generated main logs and returns the exit code, which the project files may not.
+        let main = crate::context::codegen::gen_main(ctx);
+        let allow = compile::Allow {
+            crates: vec!["chrono".into(), "log".into()],
+            apis: vec!["csv_sink".into()],
+            ..Default::default()
+        };
+        let config = compile::AnalyzeConfig {
+            forbid: compile::Forbid {
+                paths: Vec::new(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        compile::ProjectContent::load_from_project_dir(
+            dir,
+            &[(Default::default(), main)],
+            &allow,
+            &config,
+        )
+        .unwrap_or_else(|e| panic!("{e:#?}"));
+    }
+
+    /// Copy of the example project in a temporary directory, for the tests that change it.
+    fn sample_copy(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("permute-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for entry in std::fs::read_dir("src/samples/example1").unwrap() {
+            let path = entry.unwrap().path();
+            std::fs::copy(&path, dir.join(path.file_name().unwrap())).unwrap();
+        }
+        dir
+    }
 }
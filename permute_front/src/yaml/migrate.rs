@@ -0,0 +1,387 @@
+//! Files are migrated one version at a time, by the steps registered in [STEPS]. A step
+//! converts the text of a file from one version to the next one, and reports the changes
+//! it cannot make automatically. After each step the `permute.version` header is rewritten
+//! in place, so that the comments and the layout of the file are kept.
+//!
+//! The step from v0.1 converts the parsed file into the structs of v0.2, then makes the
+//! same change in the text, key by key. If the text cannot be changed so that it reads
+//! into the converted structs, the file is written from the structs instead, which drops
+//! its comments, and this is reported as a manual change.
+
+use std::path::Path;
+
+use log::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{v01, v02, Version};
+
+#[derive(Debug, thiserror::Error)]
+pub enum MigrateError {
+    #[error(transparent)]
+    Yaml(#[from] super::Error),
+
+    #[error("Cannot migrate from version {from} down to {to}")]
+    Downgrade { from: Version, to: Version },
+
+    #[error("No migration from version {from} to {to}")]
+    NoStep { from: Version, to: Version },
+
+    #[error("Version header `permute.version` cannot be found")]
+    NoVersionHeader,
+}
+
+/// Result of a migration of a file.
+#[derive(Debug)]
+pub struct Migrated {
+    /// Version of the original file.
+    pub from: Version,
+
+    pub to: Version,
+
+    /// Text of the migrated file.
+    pub text: String,
+
+    /// Changes that could not be made automatically and are left to the user.
+    pub manual: Vec<ManualChange>,
+}
+
+impl Migrated {
+    pub fn is_changed(&self) -> bool {
+        self.from != self.to
+    }
+}
+
+/// Change that a migration step could not make on its own.
+#[derive(Debug, Clone)]
+pub struct ManualChange {
+    /// Line of the migrated file, 1-based, if the change is about a specific place.
+    pub line: Option<usize>,
+
+    pub message: String,
+}
+
+impl std::fmt::Display for ManualChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {line}: {}", self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+/// Conversion of the text of a file from one version to the next one.
+struct Step {
+    from: Version,
+    to: Version,
+    run: fn(&str, &mut Vec<ManualChange>) -> Result<String, MigrateError>,
+}
+
+/// Registered migration steps, one for each version except for the latest one.
+const STEPS: &[Step] = &[Step {
+    from: Version::V01,
+    to: Version::V02,
+    run: v01_to_v02,
+}];
+
+/// Rename `pipe` of the main files to `pipes`, and `param` of the sink files to `params`.
+/// Source files are the same in both versions.
+fn v01_to_v02(s: &str, manual: &mut Vec<ManualChange>) -> Result<String, MigrateError> {
+    #[derive(serde::Deserialize)]
+    struct FileWithHeader {
+        permute: v01::Header,
+    }
+
+    let header = serde_yml::from_str::<FileWithHeader>(s)
+        .map_err(super::Error::from)?
+        .permute;
+    match header.ty {
+        v01::FileKind::Main => {
+            let main: v01::Main = from_str(s)?;
+            let renames = [("pipe", "pipes")];
+            rewrite(s, Version::V02, &v02::Main::from(main), &renames, manual)
+        }
+        v01::FileKind::Sink => {
+            let sink: v01::Sink = from_str(s)?;
+            let renames = [("param", "params")];
+            rewrite(s, Version::V02, &v02::Sink::from(sink), &renames, manual)
+        }
+        v01::FileKind::Source => {
+            let source: v01::Source = from_str(s)?;
+            rewrite(s, Version::V02, &v02::source_from_v01(source), &[], manual)
+        }
+    }
+}
+
+fn from_str<T: DeserializeOwned>(s: &str) -> Result<T, MigrateError> {
+    Ok(serde_yml::from_str(s).map_err(super::Error::from)?)
+}
+
+/// Text of the file converted to `next` of the version `to`, by setting the version and
+/// renaming the top-level keys. If the keys cannot be renamed in place, the text is
+/// serialized from `next` instead.
+fn rewrite<T: Serialize + DeserializeOwned>(
+    s: &str,
+    to: Version,
+    next: &T,
+    renames: &[(&str, &str)],
+    manual: &mut Vec<ManualChange>,
+) -> Result<String, MigrateError> {
+    let s = set_version(s, to.as_str())?;
+    let renamed = renames
+        .iter()
+        .try_fold(s, |text, (from, to)| rename_top_level_key(&text, from, to));
+    let expected = serde_yml::to_value(next).map_err(super::Error::from)?;
+    let is_same = |text: &str| {
+        let read = serde_yml::from_str::<T>(text).and_then(serde_yml::to_value);
+        read.is_ok_and(|v| v == expected)
+    };
+    if let Some(text) = renamed.filter(|v| is_same(v)) {
+        return Ok(text);
+    }
+
+    warn!("Keys cannot be renamed in place, serializing the migrated file");
+    let keys: Vec<_> = renames
+        .iter()
+        .map(|(from, to)| format!("`{from}` to `{to}`"))
+        .collect();
+    manual.push(ManualChange {
+        line: None,
+        message: format!(
+            "The file is rewritten as a whole to rename {}, restore its comments",
+            keys.join(" and ")
+        ),
+    });
+    Ok(serde_yml::to_string(next).map_err(super::Error::from)?)
+}
+
+/// Rename the key of the top-level block mapping, keeping the value and the comments.
+/// None if the key is not found as a plain key at the start of exactly one line.
+fn rename_top_level_key(s: &str, from: &str, to: &str) -> Option<String> {
+    let mut found = None;
+    let mut offset = 0;
+    for line in s.split_inclusive('\n') {
+        let rest = line.strip_prefix(from).and_then(|v| v.strip_prefix(':'));
+        let is_key = rest.is_some_and(|v| v.trim().is_empty() || v.starts_with([' ', '\t']));
+        if is_key {
+            if found.is_some() {
+                return None;
+            }
+            found = Some(offset);
+        }
+        offset += line.len();
+    }
+
+    let at = found?;
+    let mut out = String::with_capacity(s.len() + to.len());
+    out.push_str(&s[..at]);
+    out.push_str(to);
+    out.push_str(&s[at + from.len()..]);
+    Some(out)
+}
+
+/// Migrate the text of a file of any supported version to the version `to`.
+pub fn migrate_str(s: &str, to: Version) -> Result<Migrated, MigrateError> {
+    let from = Version::of_file(s)?;
+    if to < from {
+        return Err(MigrateError::Downgrade { from, to });
+    }
+
+    let mut version = from;
+    let mut text = s.to_owned();
+    let mut manual = Vec::new();
+    while version < to {
+        let step = STEPS
+            .iter()
+            .find(|v| v.from == version)
+            .ok_or(MigrateError::NoStep { from: version, to })?;
+        debug!("Migrating from version {} to {}", step.from, step.to);
+        text = (step.run)(&text, &mut manual)?;
+        text = set_version(&text, step.to.as_str())?;
+        version = step.to;
+    }
+
+    Ok(Migrated {
+        from,
+        to,
+        text,
+        manual,
+    })
+}
+
+/// Migrate the file to the version `to`. With `write`, the file is rewritten in place
+/// if the migration changed it.
+pub fn migrate_file(path: &Path, to: Version, write: bool) -> Result<Migrated, MigrateError> {
+    let s = std::fs::read_to_string(path).map_err(super::Error::from)?;
+    let migrated = migrate_str(&s, to)?;
+    if write && migrated.is_changed() {
+        info!("Rewriting {} to version {to}", path.display());
+        std::fs::write(path, &migrated.text).map_err(super::Error::from)?;
+    }
+    Ok(migrated)
+}
+
+/// Replace the value of `permute.version` in the text, keeping the rest of the file as is.
+/// The value can be on the line of the key or on the next one, and it can be quoted.
+fn set_version(s: &str, version: &str) -> Result<String, MigrateError> {
+    let mut in_header = false;
+    let mut key_found = false;
+    let mut offset = 0;
+    for line in s.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+
+        let trimmed = line.trim_start();
+        if trimmed.trim_end().is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if trimmed.len() == line.len() {
+            // Top-level key, the header ends with the next one.
+            if key_found {
+                break;
+            }
+            in_header = trimmed.starts_with("permute:");
+            continue;
+        }
+        if !in_header {
+            continue;
+        }
+
+        let indent = line.len() - trimmed.len();
+        let value_at = if key_found {
+            start + indent
+        } else if let Some(rest) = trimmed.strip_prefix("version:") {
+            let value = rest.trim_start();
+            if value.is_empty() || value.starts_with('#') {
+                key_found = true;
+                continue;
+            }
+            start + line.len() - value.len()
+        } else {
+            continue;
+        };
+
+        let value = &s[value_at..offset];
+        let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'');
+        let len = match quote {
+            Some(q) => value[1..].find(q).map_or(value.len(), |v| v + 2),
+            None => value
+                .find(|c: char| c.is_whitespace() || c == '#')
+                .unwrap_or(value.len()),
+        };
+        let new = match quote {
+            Some(q) => format!("{q}{version}{q}"),
+            None => version.to_owned(),
+        };
+
+        let mut out = String::with_capacity(s.len() + new.len());
+        out.push_str(&s[..value_at]);
+        out.push_str(&new);
+        out.push_str(&s[value_at + len..]);
+        return Ok(out);
+    }
+    Err(MigrateError::NoVersionHeader)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_version_is_unchanged() {
+        let s = include_str!("../samples/example1/main.yaml");
+        let migrated = migrate_str(s, Version::V01).unwrap();
+        assert!(!migrated.is_changed());
+        assert_eq!(migrated.text, s);
+        assert!(migrated.manual.is_empty());
+
+        let err = migrate_str(&s.replacen("0.1", "0.2", 1), Version::V01).unwrap_err();
+        assert!(matches!(err, MigrateError::Downgrade { .. }), "{err}");
+    }
+
+    #[test]
+    fn v01_to_v02_keeps_comments() {
+        let s = include_str!("../samples/example1/main.yaml");
+        let migrated = migrate_str(s, Version::V02).unwrap();
+        assert!(migrated.is_changed());
+        assert!(migrated.manual.is_empty(), "{:?}", migrated.manual);
+        let expected = s
+            .replacen("    0.1 # Version", "    0.2 # Version", 1)
+            .replacen("\npipe: # Pipelines", "\npipes: # Pipelines", 1);
+        assert_eq!(migrated.text, expected);
+        let main = v02::Main::load_from_str(&migrated.text).unwrap();
+        assert_eq!(main.pipes, ["er -> feed"]);
+
+        let s = include_str!("../samples/example1/Csv.yaml");
+        let migrated = migrate_str(s, Version::V02).unwrap();
+        assert!(migrated.manual.is_empty(), "{:?}", migrated.manual);
+        let expected =
+            s.replacen("version: 0.1", "version: 0.2", 1)
+                .replacen("\nparam:\n", "\nparams:\n", 1);
+        assert_eq!(migrated.text, expected);
+        let sink: v02::Sink = serde_yml::from_str(&migrated.text).unwrap();
+        assert!(sink.params.contains_key("date_fmt"));
+
+        let s = include_str!("../samples/example1/EmploymentRecord.yaml");
+        let migrated = migrate_str(s, Version::V02).unwrap();
+        assert!(migrated.manual.is_empty(), "{:?}", migrated.manual);
+        assert_eq!(migrated.text, s.replacen("version: 0.1", "version: 0.2", 1));
+    }
+
+    #[test]
+    fn v01_to_v02_reports_manual_changes() {
+        // Quoted key is not renamed in place.
+        let s = "\
+# Sink of the tests.
+permute:
+  version: 0.1
+  type: sink
+\"param\":
+  path:
+    type: String
+";
+        let migrated = migrate_str(s, Version::V02).unwrap();
+        assert!(migrated.is_changed());
+        let [change] = &migrated.manual[..] else {
+            panic!("expected one manual change: {:?}", migrated.manual);
+        };
+        assert_eq!(change.line, None);
+        assert!(change.message.contains("`param` to `params`"), "{change}");
+        assert!(!migrated.text.contains("# Sink of the tests."));
+
+        let sink: v02::Sink = serde_yml::from_str(&migrated.text).unwrap();
+        assert_eq!(sink.header.version, "0.2");
+        assert_eq!(sink.params["path"].ty.0, "String");
+    }
+
+    #[test]
+    fn rename_key() {
+        let s = "a: 1\npipe: # Comment.\n  - x\nb:\n  pipe: 2\npipeline: 3\n";
+        assert_eq!(
+            rename_top_level_key(s, "pipe", "pipes").unwrap(),
+            "a: 1\npipes: # Comment.\n  - x\nb:\n  pipe: 2\npipeline: 3\n"
+        );
+        assert_eq!(
+            rename_top_level_key("pipe:\n", "pipe", "pipes").unwrap(),
+            "pipes:\n"
+        );
+        assert!(rename_top_level_key("\"pipe\": []\n", "pipe", "pipes").is_none());
+        assert!(rename_top_level_key("pipe: []\npipe: []\n", "pipe", "pipes").is_none());
+    }
+
+    #[test]
+    fn set_version_keeps_comments() {
+        let s = include_str!("../samples/example1/main.yaml");
+        let out = set_version(s, "0.2").unwrap();
+        assert_eq!(out, s.replacen("    0.1 # Version", "    0.2 # Version", 1));
+
+        let s = "# File.\npermute:\n  type: sink\n  version: '0.1' # Comment.\nversion: 0.1\n";
+        let out = set_version(s, "0.2").unwrap();
+        assert_eq!(
+            out,
+            "# File.\npermute:\n  type: sink\n  version: '0.2' # Comment.\nversion: 0.1\n"
+        );
+
+        let err = set_version("name: A\nversion: 0.1\n", "0.2").unwrap_err();
+        assert!(matches!(err, MigrateError::NoVersionHeader), "{err}");
+    }
+}
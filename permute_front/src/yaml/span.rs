@@ -0,0 +1,258 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use serde_yml::libyml::parser::ScalarStyle;
+
+/// Location of an error in a project file, with the line of the file to show it on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub path: PathBuf,
+
+    /// Line number, 1-based.
+    pub line: usize,
+
+    /// Column number in characters, 1-based.
+    pub col: usize,
+
+    /// Number of the marked characters, at least 1.
+    pub len: usize,
+
+    /// Text of the line, without the line break.
+    pub source_line: String,
+}
+
+impl Span {
+    /// Span at the line and column of the text, both 1-based. Out of range
+    /// positions are clamped to the text.
+    pub fn at(path: &Path, text: &str, line: usize, col: usize, len: usize) -> Self {
+        let lines: Vec<&str> = text.lines().collect();
+        let line = line.clamp(1, lines.len().max(1));
+        let source_line = lines.get(line - 1).copied().unwrap_or_default();
+        let col = col.clamp(1, source_line.chars().count() + 1);
+        Span {
+            path: path.to_owned(),
+            line,
+            col,
+            len: len.max(1),
+            source_line: source_line.to_owned(),
+        }
+    }
+
+    /// Span of the error in Rust code embedded into a YAML scalar. The scalar is found
+    /// among the scalars of the file by its content, and the position of the error in
+    /// the code is offset from the position of the scalar. None if the file has no such
+    /// scalar, or if the code is not written as is, e.g. in a folded or a multi-line plain
+    /// scalar, or in a quoted scalar with escapes.
+    pub fn of_rust_snippet(
+        path: &Path,
+        text: &str,
+        snippet: &str,
+        err: &syn::Error,
+    ) -> Option<Self> {
+        let node = scalars(text).into_iter().find(|v| v.value == snippet)?;
+        let lines = node.locate_lines(text)?;
+
+        let start = err.span().start();
+        let end = err.span().end();
+        let snippet_lines: Vec<&str> = snippet.lines().collect();
+        let (line, col, len) = if err.span().source_text().is_none() {
+            // Errors at the end of the input have the span of the call site, which
+            // is not in the code, so they are shown after its last character.
+            let last = snippet_lines.len().max(1);
+            let col = snippet_lines.last().map_or(0, |v| v.chars().count());
+            (last, col, 1)
+        } else {
+            let len = if end.line == start.line {
+                end.column.saturating_sub(start.column)
+            } else {
+                1
+            };
+            (start.line, start.column, len)
+        };
+
+        let (text_line, text_col) = lines.get(line - 1).copied()?;
+        Some(Span::at(path, text, text_line, text_col + col + 1, len))
+    }
+}
+
+/// Scalar value of a YAML file, with the position where the parser found it.
+struct Scalar {
+    value: String,
+    style: ScalarStyle,
+
+    /// Line of the start of the scalar, 0-based.
+    line: usize,
+
+    /// Column of the start of the scalar in characters, 0-based. It is the position
+    /// of the quote of quoted scalars and of the indicator of block scalars.
+    col: usize,
+}
+
+impl Scalar {
+    /// Lines and columns (1-based line, 0-based column in characters) where each line
+    /// of the value starts in the text. None if the lines of the value are not written
+    /// as is in the text.
+    fn locate_lines(&self, text: &str) -> Option<Vec<(usize, usize)>> {
+        let text_lines: Vec<&str> = text.lines().collect();
+        match self.style {
+            ScalarStyle::Plain | ScalarStyle::SingleQuoted | ScalarStyle::DoubleQuoted => {
+                if self.value.contains('\n') {
+                    return None;
+                }
+                let col = match self.style {
+                    ScalarStyle::Plain => self.col,
+                    _ => self.col + 1,
+                };
+                let text_line = text_lines.get(self.line)?;
+                let rest: String = text_line.chars().skip(col).collect();
+                rest.starts_with(&self.value)
+                    .then(|| vec![(self.line + 1, col)])
+            }
+            // Lines of the value start on the line after the indicator, indented.
+            ScalarStyle::Literal => self
+                .value
+                .lines()
+                .enumerate()
+                .map(|(i, line)| {
+                    let text_line = text_lines.get(self.line + 1 + i)?;
+                    let indent = text_line.strip_suffix(line)?.chars().count();
+                    Some((self.line + 2 + i, indent))
+                })
+                .collect(),
+            ScalarStyle::Folded => None,
+        }
+    }
+}
+
+/// Scalars of the file, keys and values, in the order of the text. Rust code is in the
+/// values, except for the types of the bindings, which are keys. If the file fails to
+/// parse, the scalars before the error are returned.
+fn scalars(text: &str) -> Vec<Scalar> {
+    use serde_yml::libyml::parser::{Event, Parser};
+
+    let mut parser = Parser::new(text.as_bytes().into());
+    let mut scalars = Vec::new();
+    while let Ok((event, mark)) = parser.parse_next_event() {
+        match event {
+            Event::StreamEnd => break,
+            Event::Scalar(scalar) => scalars.push(Scalar {
+                value: String::from_utf8_lossy(&scalar.value).into_owned(),
+                style: scalar.style,
+                line: mark.line() as usize,
+                col: mark.column() as usize,
+            }),
+            _ => {}
+        }
+    }
+    scalars
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let gutter = self.line.to_string().len();
+        let pad = " ".repeat(gutter);
+        writeln!(
+            f,
+            "{pad}--> {}:{}:{}",
+            self.path.display(),
+            self.line,
+            self.col
+        )?;
+        writeln!(f, "{pad} |")?;
+        writeln!(f, "{} | {}", self.line, self.source_line)?;
+
+        // Tabs are kept, so that the caret is aligned under the marked code.
+        let indent: String = self
+            .source_line
+            .chars()
+            .take(self.col - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        write!(f, "{pad} | {indent}{}", "^".repeat(self.len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "\
+permute:
+  version: 0.1
+  type: source
+
+column:
+  date:
+    type: Option<NaiveDate
+  name:
+    type: String
+    check: |
+      name.len() > 0
+        && name.len() <> 10
+  other:
+    check: \"> 1\"
+";
+
+    fn span<T: syn::parse::Parse>(snippet: &str) -> Span {
+        let err = syn::parse_str::<T>(snippet).map(|_| ()).unwrap_err();
+        Span::of_rust_snippet(Path::new("Src.yaml"), TEXT, snippet, &err).unwrap()
+    }
+
+    #[test]
+    fn end_of_input() {
+        let span = span::<syn::Type>("Option<NaiveDate");
+        assert_eq!((span.line, span.col, span.len), (7, 27, 1));
+        let expected = [
+            " --> Src.yaml:7:27",
+            "  |",
+            "7 |     type: Option<NaiveDate",
+            "  |                           ^",
+        ];
+        assert_eq!(span.to_string(), expected.join("\n"));
+    }
+
+    #[test]
+    fn block_scalar() {
+        let span = span::<syn::Expr>("name.len() > 0\n  && name.len() <> 10\n");
+        assert_eq!(
+            (span.line, span.col, span.source_line.as_str()),
+            (12, 24, "        && name.len() <> 10")
+        );
+    }
+
+    #[test]
+    fn start_of_input() {
+        let span = span::<syn::Expr>("> 1");
+        assert_eq!((span.line, span.col, span.len), (14, 13, 1));
+    }
+
+    #[test]
+    fn scalar_not_comment() {
+        let text = "\
+# Type `Vec<u8` of the comment.
+columns:
+  - 'Vec<u8'
+";
+        let err = syn::parse_str::<syn::Type>("Vec<u8").unwrap_err();
+        let span = Span::of_rust_snippet(Path::new("Src.yaml"), text, "Vec<u8", &err).unwrap();
+        assert_eq!((span.line, span.col), (3, 12));
+    }
+
+    #[test]
+    fn not_as_is() {
+        let text = "a: \"Vec\\x3Cu8\"\nb: >\n  Vec<\n  u8\n";
+        for snippet in ["Vec<u8", "Vec< u8\n"] {
+            let err = syn::parse_str::<syn::Type>(snippet).unwrap_err();
+            let span = Span::of_rust_snippet(Path::new("Src.yaml"), text, snippet, &err);
+            assert!(span.is_none(), "{snippet}: {span:?}");
+        }
+    }
+
+    #[test]
+    fn not_found() {
+        let err = syn::parse_str::<syn::Type>("Vec<").unwrap_err();
+        assert!(Span::of_rust_snippet(Path::new("Src.yaml"), TEXT, "Vec<", &err).is_none());
+    }
+}
@@ -1,5 +1,6 @@
 use compact_str::CompactString;
 use hashbrown::HashMap;
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -37,6 +38,59 @@ pub struct Main {
 
     #[serde(rename = "let")]
     pub bindings: MainBindings,
+
+    /// What to do with the rows of the source bindings that fail the column checks.
+    #[serde(default)]
+    pub on_check_fail: HashMap<CompactString, CheckPolicy>,
+
+    /// Functions of the Rust files that are exempt from the analysis checks,
+    /// and external crates that the Rust files may use.
+    #[serde(default)]
+    pub allow: Allow,
+}
+
+/// Lists of function paths, e.g. `csv_sink::RowSequence::advance`, which are
+/// exempt from the corresponding check.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Allow {
+    /// Functions that may have loops.
+    #[serde(default)]
+    pub loops: Vec<CompactString>,
+
+    /// Functions that may be part of a recursion cycle.
+    #[serde(default)]
+    pub recursion: Vec<CompactString>,
+
+    /// Items that may use the forbidden APIs, such as `std::fs`. Modules are
+    /// allowed with all their items.
+    #[serde(default)]
+    pub apis: Vec<CompactString>,
+
+    /// External crates that the Rust files may use, besides the runtime and
+    /// the standard library.
+    #[serde(default)]
+    pub crates: Vec<CompactString>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum CheckPolicy {
+    /// `abort` or `skip`.
+    Keyword(CheckPolicyKeyword),
+
+    /// `reject: <sink binding>` to put the row into the sink binding with the given name.
+    Reject { reject: CompactString },
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckPolicyKeyword {
+    /// Stop the pipe. This is the default.
+    Abort,
+
+    /// Skip the row with a warning.
+    Skip,
 }
 
 impl Main {
@@ -140,7 +194,8 @@ pub struct Source {
     pub header: Header,
     pub explain: Option<CompactString>,
     pub filters: HashMap<CompactString, SourceFilter>,
-    pub columns: HashMap<CompactString, SourceColumn>,
+    /// Columns in the order of declaration, which is the order of the row fields.
+    pub columns: IndexMap<CompactString, SourceColumn>,
     pub filter_check: Option<Check>,
     pub column_check: Option<Check>,
 }
@@ -174,18 +229,38 @@ pub enum Check {
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum CheckExpr {
-    ExprExpl { explain: CompactString, define: RustExpr },
+    ExprExpl {
+        explain: Option<CompactString>,
+        define: RustExpr,
+        on_none: Option<OnNone>,
+    },
     Expr(RustExpr),
 }
 
+/// Outcome of the check when `?` in its expression meets `None`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnNone {
+    #[default]
+    Pass,
+    Fail,
+}
+
 impl CheckExpr {
     pub fn explain(&self) -> Option<&str> {
         match self {
-            CheckExpr::ExprExpl { explain, .. } => Some(explain),
+            CheckExpr::ExprExpl { explain, .. } => explain.as_deref(),
             CheckExpr::Expr(_) => None,
         }
     }
 
+    pub fn on_none(&self) -> OnNone {
+        match self {
+            CheckExpr::ExprExpl { on_none, .. } => on_none.unwrap_or_default(),
+            CheckExpr::Expr(_) => OnNone::default(),
+        }
+    }
+
     pub fn expr(&self) -> &RustExpr {
         match self {
             CheckExpr::ExprExpl { define, .. } => define,
@@ -0,0 +1,97 @@
+//! Version 0.2 of YAML format. The main file lists its pipelines under `pipes`, and the
+//! sink files list their parameters under `params`, instead of `pipe` and `param` of
+//! version 0.1. Source files and all the other parts of the files are the same as in
+//! version 0.1.
+
+use compact_str::CompactString;
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+
+use super::v01;
+pub use super::v01::{
+    Allow, BindingCfg, Check, CheckExpr, CheckPolicy, CheckPolicyKeyword, FileKind, Header,
+    MainBinding, MainBindingField, MainBindings, OnNone, RustExpr, RustTy, SinkColumn, Source,
+    SourceColumn, SourceFilter,
+};
+
+/// The main file of the project.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Main {
+    #[serde(rename = "permute")]
+    pub header: Header,
+    pub name: CompactString,
+    pub explain: Option<CompactString>,
+
+    pub pipes: Vec<CompactString>,
+
+    #[serde(rename = "let")]
+    pub bindings: MainBindings,
+
+    /// What to do with the rows of the source bindings that fail the column checks.
+    #[serde(default)]
+    pub on_check_fail: HashMap<CompactString, CheckPolicy>,
+
+    /// Functions of the Rust files that are exempt from the analysis checks,
+    /// and external crates that the Rust files may use.
+    #[serde(default)]
+    pub allow: Allow,
+}
+
+impl Main {
+    /// Load the main file from the given string slice.
+    pub fn load_from_str(s: &str) -> Result<Self, super::Error> {
+        let main: Main = serde_yml::from_str(s)?;
+        Ok(main)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Sink {
+    #[serde(rename = "permute")]
+    pub header: Header,
+    pub explain: Option<CompactString>,
+    pub params: HashMap<CompactString, SinkColumn>,
+    pub check: Option<Check>,
+}
+
+impl From<v01::Main> for Main {
+    fn from(main: v01::Main) -> Self {
+        Main {
+            header: header(main.header),
+            name: main.name,
+            explain: main.explain,
+            pipes: main.pipes,
+            bindings: main.bindings,
+            on_check_fail: main.on_check_fail,
+            allow: main.allow,
+        }
+    }
+}
+
+impl From<v01::Sink> for Sink {
+    fn from(sink: v01::Sink) -> Self {
+        Sink {
+            header: header(sink.header),
+            explain: sink.explain,
+            params: sink.param,
+            check: sink.check,
+        }
+    }
+}
+
+/// Convert the source file, which has the same schema in both versions.
+pub fn source_from_v01(source: v01::Source) -> Source {
+    Source {
+        header: header(source.header),
+        ..source
+    }
+}
+
+fn header(header: Header) -> Header {
+    Header {
+        version: super::Version::V02.as_str().into(),
+        ..header
+    }
+}
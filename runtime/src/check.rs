@@ -0,0 +1,290 @@
+//! Helpers for the check expressions of the configuration files. Generated code imports
+//! everything from here, so that checks can be written as `self.regex_is_match("^\\d+$")`
+//! or `self.is_between(1, 10)`.
+//!
+//! The traits are implemented for the plain value types, so the generated wrappers of
+//! parameters, filters and columns reach them through `Deref`.
+
+use std::{collections::HashMap, sync::Mutex};
+
+use chrono::{Datelike, NaiveDate};
+use lazy_regex::{regex_is_match, Lazy, Regex};
+
+/// Checks of string values.
+pub trait StrCheck {
+    /// Whether the string matches the regular expression. Each pattern is compiled
+    /// once and cached for the lifetime of the program. Invalid pattern never matches.
+    fn regex_is_match(&self, pattern: &str) -> bool;
+
+    /// Whether the string is an email address of a plain `name@domain.tld` form.
+    fn is_email(&self) -> bool;
+
+    /// Whether the string is a UUID in the hyphenated form.
+    fn is_uuid(&self) -> bool;
+
+    /// Whether the string is an identifier of the given prefix followed by exactly
+    /// `digits` ASCII digits, e.g. `is_id("SID", 6)` accepts `SID000123`.
+    fn is_id(&self, prefix: &str, digits: usize) -> bool;
+}
+
+impl<T: AsRef<str> + ?Sized> StrCheck for T {
+    fn regex_is_match(&self, pattern: &str) -> bool {
+        cached_regex(pattern).is_some_and(|re| re.is_match(self.as_ref()))
+    }
+
+    fn is_email(&self) -> bool {
+        regex_is_match!(r"^[^@\s]+@[^@\s]+\.[^@\s.]+$", self.as_ref())
+    }
+
+    fn is_uuid(&self) -> bool {
+        regex_is_match!(
+            r"^[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}$",
+            self.as_ref()
+        )
+    }
+
+    fn is_id(&self, prefix: &str, digits: usize) -> bool {
+        self.as_ref()
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.len() == digits && rest.bytes().all(|b| b.is_ascii_digit()))
+    }
+}
+
+/// Compile the pattern or take it from the cache. Compilation errors are logged
+/// once per pattern and cached as well.
+fn cached_regex(pattern: &str) -> Option<Regex> {
+    static CACHE: Lazy<Mutex<HashMap<String, Option<Regex>>>> = Lazy::new(Default::default);
+
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(re) = cache.get(pattern) {
+        // Regex clone is cheap, it shares the compiled program.
+        return re.clone();
+    }
+
+    let re = Regex::new(pattern)
+        .map_err(|e| log::error!("Invalid regex `{pattern}` in a check. {e}"))
+        .ok();
+    cache.insert(pattern.to_owned(), re.clone());
+    re
+}
+
+/// Length of a value, as counted by the length checks. Strings count characters.
+pub trait CheckLen {
+    fn check_len(&self) -> usize;
+}
+
+impl CheckLen for str {
+    fn check_len(&self) -> usize {
+        self.chars().count()
+    }
+}
+
+impl CheckLen for String {
+    fn check_len(&self) -> usize {
+        self.as_str().check_len()
+    }
+}
+
+impl CheckLen for compact_str::CompactString {
+    fn check_len(&self) -> usize {
+        self.as_str().check_len()
+    }
+}
+
+impl<T> CheckLen for [T] {
+    fn check_len(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T> CheckLen for Vec<T> {
+    fn check_len(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<K, V, S> CheckLen for HashMap<K, V, S> {
+    fn check_len(&self) -> usize {
+        self.len()
+    }
+}
+
+/// Checks of the value length.
+pub trait LenCheck {
+    fn is_non_empty(&self) -> bool;
+
+    /// Whether the length is within `min..=max`.
+    fn len_between(&self, min: usize, max: usize) -> bool;
+}
+
+impl<T: CheckLen + ?Sized> LenCheck for T {
+    fn is_non_empty(&self) -> bool {
+        self.check_len() != 0
+    }
+
+    fn len_between(&self, min: usize, max: usize) -> bool {
+        (min..=max).contains(&self.check_len())
+    }
+}
+
+/// Checks of ordered values, such as numbers.
+pub trait RangeCheck {
+    /// Whether the value is within `min..=max`.
+    fn is_between(&self, min: Self, max: Self) -> bool;
+
+    fn is_at_least(&self, min: Self) -> bool;
+
+    fn is_at_most(&self, max: Self) -> bool;
+}
+
+impl<T: PartialOrd> RangeCheck for T {
+    fn is_between(&self, min: Self, max: Self) -> bool {
+        min <= *self && *self <= max
+    }
+
+    fn is_at_least(&self, min: Self) -> bool {
+        *self >= min
+    }
+
+    fn is_at_most(&self, max: Self) -> bool {
+        *self <= max
+    }
+}
+
+/// Checks of dates. Bounds are ISO 8601 dates, e.g. `2019-01-01`.
+/// Invalid bound fails the check.
+pub trait DateCheck {
+    /// Whether the date is within `from..=to`.
+    fn is_date_between(&self, from: &str, to: &str) -> bool;
+
+    /// Whether the date is strictly before the given one.
+    fn is_before(&self, date: &str) -> bool;
+
+    /// Whether the date is strictly after the given one.
+    fn is_after(&self, date: &str) -> bool;
+}
+
+impl<T: Datelike> DateCheck for T {
+    fn is_date_between(&self, from: &str, to: &str) -> bool {
+        match (date_of(self), parse_date(from), parse_date(to)) {
+            (Some(date), Some(from), Some(to)) => from <= date && date <= to,
+            _ => false,
+        }
+    }
+
+    fn is_before(&self, date: &str) -> bool {
+        match (date_of(self), parse_date(date)) {
+            (Some(this), Some(date)) => this < date,
+            _ => false,
+        }
+    }
+
+    fn is_after(&self, date: &str) -> bool {
+        match (date_of(self), parse_date(date)) {
+            (Some(this), Some(date)) => this > date,
+            _ => false,
+        }
+    }
+}
+
+fn date_of(v: &impl Datelike) -> Option<NaiveDate> {
+    NaiveDate::from_ymd_opt(v.year(), v.month(), v.day())
+}
+
+fn parse_date(s: &str) -> Option<NaiveDate> {
+    s.parse()
+        .map_err(|e| log::error!("Invalid date `{s}` in a check. {e}"))
+        .ok()
+}
+
+/// Check that the value is one of the listed ones.
+pub trait OneOfCheck {
+    /// Whether the value equals any of the options, e.g. `self.is_one_of(&["A", "B"])`.
+    fn is_one_of<U: PartialEq<Self>>(&self, options: &[U]) -> bool;
+}
+
+impl<T: PartialEq + ?Sized> OneOfCheck for T {
+    fn is_one_of<U: PartialEq<Self>>(&self, options: &[U]) -> bool {
+        options.iter().any(|v| v == self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regex_cache() {
+        assert!("SID000123".regex_is_match(r"^SID\d{6}$"));
+        assert!(!"SID00012".regex_is_match(r"^SID\d{6}$"));
+        // Second lookup takes the cached regex.
+        assert!(cached_regex(r"^SID\d{6}$").is_some_and(|re| re.is_match("SID000001")));
+
+        // Invalid pattern is cached as well, and never matches.
+        assert!(!"(".regex_is_match("("));
+        assert!(cached_regex("(").is_none());
+    }
+
+    #[test]
+    fn str_checks() {
+        assert!("SID000123".is_id("SID", 6));
+        assert!(!"SID00012".is_id("SID", 6));
+        assert!(!"SID0001234".is_id("SID", 6));
+        assert!(!"SID00012a".is_id("SID", 6));
+        assert!(!"XID000123".is_id("SID", 6));
+        // Unicode digits are not ASCII ones.
+        assert!(!"SID00012٣".is_id("SID", 6));
+
+        assert!("john.doe@example.com".is_email());
+        assert!(!"john.doe@example".is_email());
+        assert!(!"john doe@example.com".is_email());
+        assert!(!"john@doe@example.com".is_email());
+        assert!(!"@example.com".is_email());
+
+        assert!("123e4567-e89b-12d3-a456-426614174000".is_uuid());
+        assert!(!"123e4567e89b12d3a456426614174000".is_uuid());
+    }
+
+    #[test]
+    fn len_between() {
+        assert!("abc".len_between(3, 5));
+        assert!("abcde".len_between(3, 5));
+        assert!(!"ab".len_between(3, 5));
+        assert!(!"abcdef".len_between(3, 5));
+        // Characters are counted, not bytes.
+        assert!("äöü".len_between(3, 3));
+
+        assert!(vec![1, 2].len_between(2, 2));
+        assert!(!String::new().is_non_empty());
+        assert!([0].is_non_empty());
+    }
+
+    #[test]
+    fn date_bounds() {
+        let date = NaiveDate::from_ymd_opt(2019, 1, 31).unwrap();
+
+        // `is_date_between` includes the bounds.
+        assert!(date.is_date_between("2019-01-01", "2019-01-31"));
+        assert!(date.is_date_between("2019-01-31", "2019-02-01"));
+        assert!(!date.is_date_between("2019-02-01", "2019-03-01"));
+
+        // `is_before` and `is_after` exclude the date itself.
+        assert!(!date.is_before("2019-01-31"));
+        assert!(date.is_before("2019-02-01"));
+        assert!(!date.is_after("2019-01-31"));
+        assert!(date.is_after("2019-01-30"));
+
+        // Invalid bound fails the check.
+        assert!(!date.is_date_between("2019-01-01", "2019-02-30"));
+        assert!(!date.is_before("tomorrow"));
+    }
+
+    #[test]
+    fn one_of() {
+        // Columns are `String`s, compared to the literals of the check.
+        assert!(String::from("B").is_one_of(&["A", "B"]));
+        assert!(!String::from("C").is_one_of(&["A", "B"]));
+        assert!(2.is_one_of(&[1, 2, 3]));
+        assert!(!2.is_one_of::<i32>(&[]));
+    }
+}
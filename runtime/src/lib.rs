@@ -3,15 +3,23 @@
 
 // List crates here so that they are forced to be built even
 // despite being potentially unused in this particular sub-project.
+extern crate chrono;
+extern crate compact_str;
 extern crate lazy_regex;
-extern crate smallvec;
+extern crate log;
 extern crate serde;
 extern crate serde_derive;
-extern crate chrono;
-extern crate log;
-extern crate compact_str;
+extern crate smallvec;
+
+/// Items used by the generated project code.
+pub mod sys;
+
+/// Helpers for the check expressions, imported into the generated code.
+pub mod check;
+
+pub use sys::{SinkError, SourceError};
 
-/// A sink to feed to the values of a given type. 
+/// A sink to feed to the values of a given type.
 pub trait Sink<T> {
     /// The error type that can be returned by the sink.
     type Error;
@@ -36,6 +44,17 @@ pub trait Source {
     fn next(&mut self) -> Option<Result<Self::Item, Self::Error>>;
 }
 
+/// Reader of the rows of a source that is described in a YAML file. The YAML file only
+/// describes the filters and the columns, so the project Rust code implements this trait
+/// for the generated source to provide the rows, e.g. from the file named in a filter.
+pub trait ReadRows {
+    /// Rows of the source, with the generated row type as the item.
+    type Rows: Source<Error = SourceError> + 'static;
+
+    /// Open the rows. Called once for a binding, after its filters pass the checks.
+    fn read_rows(&self) -> Result<Self::Rows, SourceError>;
+}
+
 impl<I, E> std::iter::Iterator for dyn Source<Item = I, Error = E> {
     type Item = Result<I, E>;
 
@@ -0,0 +1,489 @@
+//! Support items that the generated project code relies on. These are not meant to be
+//! used directly by hand-written code, except for the error types which sinks and sources
+//! can reuse.
+
+use std::{fmt, marker::PhantomData};
+
+use compact_str::CompactString;
+
+/// Environment variable that sets the log level of a generated project.
+/// Accepts the same values as [log::LevelFilter], e.g. `info` or `trace`.
+pub const LOG_ENV: &str = "PERMUTE_LOG";
+
+/// Initialize the logger for the generated project. The log level is taken from
+/// the [LOG_ENV] environment variable and defaults to `info`.
+///
+/// Calling this function more than once is allowed, only the first call has an effect.
+pub fn init_logger() {
+    let level = std::env::var(LOG_ENV)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(log::LevelFilter::Info);
+
+    static LOGGER: StderrLogger = StderrLogger;
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+/// Logger that writes all records to the standard error stream.
+struct StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{} {}] {}", record.level(), record.target(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+macro_rules! check_err {
+    ($(#[$meta:meta])* $name:ident, $what:literal) => {
+        $(#[$meta])*
+        ///
+        /// The type parameter is the generated type of the checked value, so that
+        /// the error can name what exactly failed the check.
+        pub struct $name<T> {
+            explain: &'static str,
+            _ty: PhantomData<fn() -> T>,
+        }
+
+        impl<T> $name<T> {
+            pub fn new(explain: &'static str) -> Self {
+                Self {
+                    explain,
+                    _ty: PhantomData,
+                }
+            }
+
+            /// Explanation of the failed check, as written in the configuration.
+            /// Empty string means no explanation.
+            pub fn explain(&self) -> &'static str {
+                self.explain
+            }
+
+            /// Name of the generated type whose check failed.
+            pub fn checked_type(&self) -> &'static str {
+                std::any::type_name::<T>()
+            }
+        }
+
+        impl<T> fmt::Debug for $name<T> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_struct(stringify!($name))
+                    .field("ty", &self.checked_type())
+                    .field("explain", &self.explain)
+                    .finish()
+            }
+        }
+
+        impl<T> fmt::Display for $name<T> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{} check failed for `{}`", $what, self.checked_type())?;
+                if !self.explain.is_empty() {
+                    write!(f, ". {}", self.explain)?;
+                }
+                Ok(())
+            }
+        }
+
+        impl<T> std::error::Error for $name<T> {}
+    };
+}
+
+check_err!(
+    /// Source filter value did not pass the check defined in the source configuration.
+    FilterCheckErr,
+    "Filter"
+);
+
+check_err!(
+    /// Sink parameter value did not pass the check defined in the sink configuration.
+    ParamCheckErr,
+    "Parameter"
+);
+
+check_err!(
+    /// Source column value did not pass the check defined in the source configuration.
+    ColumnCheckErr,
+    "Column"
+);
+
+macro_rules! io_err {
+    ($(#[$meta:meta])* $name:ident, $what:literal) => {
+        $(#[$meta])*
+        #[derive(Debug)]
+        pub struct $name {
+            /// Name of the binding in the main file that failed. Empty if unknown.
+            binding: CompactString,
+
+            /// Name of the field (parameter, filter or column) that failed. Empty if unknown.
+            field: CompactString,
+
+            /// Explanation of what happened. Empty string means no explanation.
+            explain: CompactString,
+
+            /// Number of the row that failed, counting from 1.
+            row: Option<u64>,
+
+            /// Underlying error, if any.
+            cause: Option<Box<dyn std::error::Error + Send + Sync>>,
+        }
+
+        impl $name {
+            pub fn new(explain: impl Into<CompactString>) -> Self {
+                Self {
+                    binding: Default::default(),
+                    field: Default::default(),
+                    explain: explain.into(),
+                    row: None,
+                    cause: None,
+                }
+            }
+
+            /// Create an error that wraps the underlying cause.
+            pub fn from_cause(cause: impl std::error::Error + Send + Sync + 'static) -> Self {
+                Self {
+                    binding: Default::default(),
+                    field: Default::default(),
+                    explain: Default::default(),
+                    row: None,
+                    cause: Some(Box::new(cause)),
+                }
+            }
+
+            /// Attach the binding name, unless it is already set.
+            /// Inner layers know the failure better, so they win.
+            pub fn with_binding(mut self, binding: impl Into<CompactString>) -> Self {
+                if self.binding.is_empty() {
+                    self.binding = binding.into();
+                }
+                self
+            }
+
+            /// Attach the field name, unless it is already set.
+            pub fn with_field(mut self, field: impl Into<CompactString>) -> Self {
+                if self.field.is_empty() {
+                    self.field = field.into();
+                }
+                self
+            }
+
+            /// Attach the row number, unless it is already set.
+            pub fn with_row(mut self, row: u64) -> Self {
+                self.row.get_or_insert(row);
+                self
+            }
+
+            pub fn binding(&self) -> Option<&str> {
+                if self.binding.is_empty() {
+                    None
+                } else {
+                    Some(&self.binding)
+                }
+            }
+
+            pub fn field(&self) -> Option<&str> {
+                if self.field.is_empty() {
+                    None
+                } else {
+                    Some(&self.field)
+                }
+            }
+
+            pub fn explain(&self) -> Option<&str> {
+                if self.explain.is_empty() {
+                    None
+                } else {
+                    Some(&self.explain)
+                }
+            }
+
+            pub fn row(&self) -> Option<u64> {
+                self.row
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, $what)?;
+                if let Some(binding) = self.binding() {
+                    write!(f, " `{binding}`")?;
+                }
+                if let Some(field) = self.field() {
+                    write!(f, " at `{field}`")?;
+                }
+                write!(f, " failed")?;
+                if let Some(row) = self.row {
+                    write!(f, " on row {row}")?;
+                }
+                if let Some(explain) = self.explain() {
+                    write!(f, ". {explain}")?;
+                }
+                if let Some(cause) = &self.cause {
+                    write!(f, ". {cause}")?;
+                }
+                Ok(())
+            }
+        }
+
+        impl std::error::Error for $name {
+            fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+                self.cause.as_deref().map(|e| e as _)
+            }
+        }
+
+        impl From<std::io::Error> for $name {
+            fn from(e: std::io::Error) -> Self {
+                Self::from_cause(e)
+            }
+        }
+    };
+}
+
+io_err!(
+    /// Error of a sink. Carries the failing binding, field and explanation, so that
+    /// the generated main function can report where the pipeline broke.
+    SinkError,
+    "Sink"
+);
+
+io_err!(
+    /// Error of a source. Carries the failing binding, field and explanation, so that
+    /// the generated main function can report where the pipeline broke.
+    SourceError,
+    "Source"
+);
+
+// Check errors are kept as the cause, so that the failed type and the explanation
+// can be taken back from the error source.
+
+impl<T: 'static> From<ParamCheckErr<T>> for SinkError {
+    fn from(e: ParamCheckErr<T>) -> Self {
+        Self::from_cause(e)
+    }
+}
+
+impl<T: 'static> From<FilterCheckErr<T>> for SourceError {
+    fn from(e: FilterCheckErr<T>) -> Self {
+        Self::from_cause(e)
+    }
+}
+
+impl<T: 'static> From<ColumnCheckErr<T>> for SourceError {
+    fn from(e: ColumnCheckErr<T>) -> Self {
+        Self::from_cause(e)
+    }
+}
+
+/// What to do with a source row that fails the column checks.
+#[derive(Default)]
+pub enum CheckPolicy<T> {
+    /// Stop the pipe with the check error.
+    #[default]
+    Abort,
+
+    /// Skip the row and log a warning.
+    Skip,
+
+    /// Put the row into the given sink, log a warning and continue.
+    Reject {
+        binding: &'static str,
+        sink: Box<dyn crate::Sink<T, Error = SinkError>>,
+    },
+}
+
+impl<T> CheckPolicy<T> {
+    /// Reject the failed rows to the sink of the given binding.
+    pub fn reject<K>(binding: &'static str, sink: K) -> Self
+    where
+        K: crate::Sink<T> + 'static,
+        K::Error: Into<SinkError>,
+    {
+        Self::Reject {
+            binding,
+            sink: Box::new(IntoSinkError(sink)),
+        }
+    }
+}
+
+impl<T> fmt::Debug for CheckPolicy<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Abort => write!(f, "Abort"),
+            Self::Skip => write!(f, "Skip"),
+            Self::Reject { binding, .. } => f.debug_tuple("Reject").field(binding).finish(),
+        }
+    }
+}
+
+/// Sink adapter that converts the errors into [SinkError].
+struct IntoSinkError<K>(K);
+
+impl<T, K> crate::Sink<T> for IntoSinkError<K>
+where
+    K: crate::Sink<T>,
+    K::Error: Into<SinkError>,
+{
+    type Error = SinkError;
+
+    fn put(&mut self, value: T) -> Result<(), Self::Error> {
+        self.0.put(value).map_err(Into::into)
+    }
+
+    fn done(&mut self) -> Result<(), Self::Error> {
+        self.0.done().map_err(Into::into)
+    }
+}
+
+/// Reader that fills a YAML source with rows. The YAML file only describes the rows,
+/// so the reader is set by the generated `open` method of the source, from the
+/// [crate::ReadRows] impl of the project Rust code.
+///
+/// Rows are checked as they are read, failed rows are handled by the [CheckPolicy].
+pub struct Reader<T> {
+    inner: Option<Box<dyn crate::Source<Item = T, Error = SourceError>>>,
+    policy: CheckPolicy<T>,
+
+    /// Number of rows read so far.
+    row: u64,
+}
+
+impl<T> Reader<T> {
+    /// Set the source of the rows, replacing the previous one.
+    pub fn set(&mut self, reader: impl crate::Source<Item = T, Error = SourceError> + 'static) {
+        self.inner = Some(Box::new(reader));
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    pub fn set_policy(&mut self, policy: CheckPolicy<T>) {
+        self.policy = policy;
+    }
+
+    /// Read the next row that passes the check. Reading without the reader set is an error.
+    /// Errors carry the number of the row they happened on.
+    pub fn next(
+        &mut self,
+        mut check: impl FnMut(&T) -> Result<(), SourceError>,
+    ) -> Option<Result<T, SourceError>> {
+        let Some(inner) = &mut self.inner else {
+            return Some(Err(SourceError::new("No reader is set for the source")));
+        };
+
+        loop {
+            let Some(row) = inner.next() else {
+                return self.done().err().map(Err);
+            };
+            self.row += 1;
+            let row = match row {
+                Ok(row) => row,
+                Err(e) => return Some(Err(e.with_row(self.row))),
+            };
+            let Err(e) = check(&row) else {
+                return Some(Ok(row));
+            };
+
+            let e = e.with_row(self.row);
+            match &mut self.policy {
+                CheckPolicy::Abort => return Some(Err(e)),
+                CheckPolicy::Skip => log::warn!("Skipped the row. {e}"),
+                CheckPolicy::Reject { binding, sink } => {
+                    log::warn!("Rejected the row to `{binding}`. {e}");
+                    if let Err(e) = sink.put(row) {
+                        let e = e.with_binding(*binding).with_row(self.row);
+                        return Some(Err(SourceError::from_cause(e)));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Close the reject sink, if any, once the rows are over.
+    fn done(&mut self) -> Result<(), SourceError> {
+        if let CheckPolicy::Reject { binding, mut sink } = std::mem::take(&mut self.policy) {
+            sink.done()
+                .map_err(|e| SourceError::from_cause(e.with_binding(binding)))?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> Default for Reader<T> {
+    fn default() -> Self {
+        Self {
+            inner: None,
+            policy: CheckPolicy::Abort,
+            row: 0,
+        }
+    }
+}
+
+impl<T> fmt::Debug for Reader<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reader")
+            .field("is_set", &self.is_set())
+            .field("policy", &self.policy)
+            .field("row", &self.row)
+            .finish()
+    }
+}
+
+/// Drain the source into the sink and close the sink afterwards.
+/// Binding names are attached to the errors, to tell where the pipeline broke.
+pub fn pipe<S, K>(
+    src_binding: &str,
+    src: &mut S,
+    sink_binding: &str,
+    sink: &mut K,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    S: crate::Source,
+    S::Error: Into<SourceError>,
+    K: crate::Sink<S::Item>,
+    K::Error: Into<SinkError>,
+{
+    let mut count = 0usize;
+    while let Some(item) = src.next() {
+        let item = item.map_err(|e| e.into().with_binding(src_binding))?;
+        sink.put(item)
+            .map_err(|e| e.into().with_binding(sink_binding))?;
+        count += 1;
+    }
+    log::debug!("Pipe `{src_binding}` -> `{sink_binding}` moved {count} items");
+
+    sink.done()
+        .map_err(|e| e.into().with_binding(sink_binding))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Param;
+
+    #[test]
+    fn check_err_is_the_cause() {
+        let e = SinkError::from(ParamCheckErr::<Param>::new("Path must not be empty."))
+            .with_binding("csv")
+            .with_field("path");
+        assert_eq!(e.binding(), Some("csv"));
+        assert_eq!(e.field(), Some("path"));
+
+        let cause = std::error::Error::source(&e).expect("check error is the cause");
+        let cause = cause
+            .downcast_ref::<ParamCheckErr<Param>>()
+            .expect("cause keeps its type");
+        assert_eq!(cause.explain(), "Path must not be empty.");
+        assert!(e.to_string().ends_with("Path must not be empty."), "{e}");
+    }
+}